@@ -0,0 +1,646 @@
+use anyhow::{anyhow, bail, Context, Result};
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use der::asn1::{ObjectIdentifier, OctetString};
+use der::{Decode, Encode, Sequence};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x509_cert::ext::pkix::ExtendedKeyUsage;
+use x509_cert::name::Name;
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+const OID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const OID_SPC_INDIRECT_DATA: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.2.1.4");
+const OID_CONTENT_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.3");
+const OID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+const OID_CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+const OID_COMMON_NAME: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.3");
+const OID_ORGANIZATION: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.5.4.10");
+
+/// Maximum number of certificates between the signer and a trusted root
+const MAX_CHAIN_LENGTH: usize = 8;
+
+/// `WIN_CERT_TYPE_PKCS_SIGNED_DATA`, the certificate type of Authenticode signatures
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+
+/// Common names of Microsoft's code signing roots, trusted by [`AuthenticodeVerifier::microsoft`]
+pub const MICROSOFT_ROOT_NAMES: [&str; 2] = [
+    "Microsoft Root Certificate Authority 2010",
+    "Microsoft Root Certificate Authority 2011",
+];
+
+/// `SpcIndirectDataContent`, the signed content of an Authenticode signature
+#[derive(Sequence)]
+struct SpcIndirectDataContent {
+    data: der::Any,
+    message_digest: DigestInfo,
+}
+
+#[derive(Sequence)]
+struct DigestInfo {
+    digest_algorithm: AlgorithmIdentifierOwned,
+    digest: OctetString,
+}
+
+/// Details of a verified Authenticode signature
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureInfo {
+    /// Common name of the signing certificate
+    pub signer: String,
+    /// Common names of the certificates from the signer up to the trusted root
+    pub chain: Vec<String>,
+}
+
+/// Verifies the Authenticode signature embedded in a Windows executable.
+///
+/// Checks the signature covers the executable's contents, the signed attributes are signed by the
+/// signing certificate, and each certificate in the chain is signed by the next one up to a trusted
+/// root. Certificate validity periods aren't checked, as signatures are timestamped.
+///
+/// Example usage:
+/// ```no_run
+/// use bundler::authenticode::AuthenticodeVerifier;
+///
+/// let data = std::fs::read("MicrosoftEdgeWebview2Setup.exe").unwrap();
+/// let info = AuthenticodeVerifier::microsoft().verify(&data).expect("Invalid signature");
+/// println!("Signed by {}", info.signer);
+/// ```
+#[derive(Default)]
+pub struct AuthenticodeVerifier {
+    /// Root certificates, the last certificate in the chain must be signed by one of their keys
+    roots: Vec<Certificate>,
+    organization: Option<String>,
+}
+
+impl AuthenticodeVerifier {
+    /// Creates a verifier without any trusted roots
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a verifier for executables signed by Microsoft Corporation, chaining to Microsoft's roots.
+    ///
+    /// The roots aren't embedded in signed files, so they're loaded from the Windows root
+    /// certificate store. On other systems there are no trusted roots, add Microsoft's root
+    /// certificates with [`AuthenticodeVerifier::add_root_certificate`].
+    pub fn microsoft() -> Self {
+        let mut verifier = Self::new().require_organization("Microsoft Corporation");
+        for der in system_root_certificates() {
+            let Ok(certificate) = Certificate::from_der(&der) else {
+                continue;
+            };
+            let name = common_name(&certificate.tbs_certificate.subject);
+            if MICROSOFT_ROOT_NAMES.contains(&name.as_str()) {
+                verifier.roots.push(certificate);
+            }
+        }
+        verifier
+    }
+
+    /// Requires the organization of the signing certificate to match
+    pub fn require_organization(mut self, organization: &str) -> Self {
+        self.organization = Some(organization.to_string());
+        self
+    }
+
+    /// Trusts a DER encoded root certificate
+    pub fn add_root_certificate(&mut self, der: &[u8]) -> Result<()> {
+        let certificate = Certificate::from_der(der).context("Failed to parse root certificate")?;
+        self.roots.push(certificate);
+        Ok(())
+    }
+
+    /// Verifies the executable's signature, returning the signer when it's valid
+    pub fn verify(&self, pe_data: &[u8]) -> Result<SignatureInfo> {
+        let pe = PeImage::parse(pe_data)?;
+        let signature = pe
+            .signature()
+            .ok_or_else(|| anyhow!("The executable is not signed"))?;
+
+        let content_info =
+            ContentInfo::from_der(signature).context("Failed to parse the signature")?;
+        if content_info.content_type != OID_SIGNED_DATA {
+            bail!("The signature is not PKCS#7 signed data");
+        }
+        let signed_data: SignedData = content_info
+            .content
+            .decode_as()
+            .context("Failed to parse the signed data")?;
+
+        // The signed content holds the digest of the executable
+        let encap = &signed_data.encap_content_info;
+        if encap.econtent_type != OID_SPC_INDIRECT_DATA {
+            bail!("The signature is not an Authenticode signature");
+        }
+        let content = encap
+            .econtent
+            .as_ref()
+            .ok_or_else(|| anyhow!("The signature has no signed content"))?;
+        let indirect_data: SpcIndirectDataContent = content
+            .decode_as()
+            .context("Failed to parse the Authenticode content")?;
+
+        let digest_algorithm = &indirect_data.message_digest.digest_algorithm.oid;
+        let image_digest = pe.authenticode_digest(digest_algorithm)?;
+        if image_digest != indirect_data.message_digest.digest.as_bytes() {
+            bail!("The executable has been modified since it was signed");
+        }
+
+        let signer_info = signed_data
+            .signer_infos
+            .0
+            .get(0)
+            .ok_or_else(|| anyhow!("The signature has no signers"))?;
+        let certificates: Vec<&Certificate> = signed_data
+            .certificates
+            .iter()
+            .flat_map(|set| set.0.iter())
+            .filter_map(|choice| match choice {
+                CertificateChoices::Certificate(certificate) => Some(certificate),
+                CertificateChoices::Other(_) => None,
+            })
+            .collect();
+
+        let signer = find_signer(signer_info, &certificates)?;
+        verify_signer_info(signer_info, signer, content.value())?;
+        self.check_signer(signer)?;
+
+        let chain = self.verify_chain(signer, &certificates)?;
+        Ok(SignatureInfo {
+            signer: common_name(&signer.tbs_certificate.subject),
+            chain,
+        })
+    }
+
+    fn check_signer(&self, signer: &Certificate) -> Result<()> {
+        let code_signing = signer
+            .tbs_certificate
+            .get::<ExtendedKeyUsage>()
+            .context("Failed to parse the signer's extended key usage")?
+            .is_some_and(|(_, usage)| usage.0.contains(&OID_CODE_SIGNING));
+        if !code_signing {
+            bail!("The signing certificate is not for code signing");
+        }
+
+        if let Some(organization) = &self.organization {
+            let signer_organization =
+                name_attribute(&signer.tbs_certificate.subject, OID_ORGANIZATION);
+            if signer_organization.as_deref() != Some(organization.as_str()) {
+                bail!(
+                    "The executable is signed by {}, not {}",
+                    signer_organization.unwrap_or_else(|| "an unknown organization".to_string()),
+                    organization
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follows the issuers from the signer to a trusted root, verifying each certificate's signature
+    fn verify_chain(
+        &self,
+        signer: &Certificate,
+        certificates: &[&Certificate],
+    ) -> Result<Vec<String>> {
+        let mut chain = vec![common_name(&signer.tbs_certificate.subject)];
+        let mut current = signer;
+
+        for _ in 0..MAX_CHAIN_LENGTH {
+            if let Some(root) = self.trusted_root(current) {
+                chain.push(root);
+                return Ok(chain);
+            }
+
+            let issuer = certificates
+                .iter()
+                .find(|certificate| {
+                    certificate.tbs_certificate.subject == current.tbs_certificate.issuer
+                        && verify_certificate(current, certificate).is_ok()
+                })
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The certificate chain is not trusted, {} is not issued by a trusted root",
+                        common_name(&current.tbs_certificate.subject)
+                    )
+                })?;
+
+            // A self-signed certificate that isn't trusted is the end of the chain
+            if std::ptr::eq(*issuer, current) {
+                bail!(
+                    "The certificate chain ends at {}, which is not a trusted root",
+                    common_name(&current.tbs_certificate.subject)
+                );
+            }
+
+            chain.push(common_name(&issuer.tbs_certificate.subject));
+            current = issuer;
+        }
+
+        bail!("The certificate chain is too long")
+    }
+
+    /// Returns the name of the trusted root that is, or signed, the certificate, if any
+    fn trusted_root(&self, certificate: &Certificate) -> Option<String> {
+        self.roots
+            .iter()
+            .find(|root| {
+                *root == certificate
+                    || (root.tbs_certificate.subject == certificate.tbs_certificate.issuer
+                        && verify_certificate(certificate, root).is_ok())
+            })
+            .map(|root| common_name(&root.tbs_certificate.subject))
+    }
+}
+
+/// The DER encoded certificates in the Windows root certificate store
+#[cfg(windows)]
+fn system_root_certificates() -> Vec<Vec<u8>> {
+    use windows::core::w;
+    use windows::Win32::Security::Cryptography::{
+        CertCloseStore, CertEnumCertificatesInStore, CertOpenSystemStoreW, HCRYPTPROV_LEGACY,
+    };
+
+    let mut certificates = Vec::new();
+    unsafe {
+        let Ok(store) = CertOpenSystemStoreW(HCRYPTPROV_LEGACY::default(), w!("ROOT")) else {
+            return certificates;
+        };
+        let mut context = CertEnumCertificatesInStore(store, None);
+        while !context.is_null() {
+            let encoded = std::slice::from_raw_parts(
+                (*context).pbCertEncoded,
+                (*context).cbCertEncoded as usize,
+            );
+            certificates.push(encoded.to_vec());
+            context = CertEnumCertificatesInStore(store, Some(context));
+        }
+        let _ = CertCloseStore(store, 0);
+    }
+    certificates
+}
+
+#[cfg(not(windows))]
+fn system_root_certificates() -> Vec<Vec<u8>> {
+    Vec::new()
+}
+
+/// A parsed Windows PE image, just enough to compute its Authenticode digest
+struct PeImage<'a> {
+    data: &'a [u8],
+    checksum_offset: usize,
+    security_directory_offset: usize,
+    certificate_table: Option<(usize, usize)>,
+}
+
+impl<'a> PeImage<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        let read_u16 = |offset: usize| -> Result<u16> {
+            let bytes = data
+                .get(offset..offset + 2)
+                .ok_or_else(|| anyhow!("The executable is truncated"))?;
+            Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        let read_u32 = |offset: usize| -> Result<u32> {
+            let bytes = data
+                .get(offset..offset + 4)
+                .ok_or_else(|| anyhow!("The executable is truncated"))?;
+            Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+
+        if data.get(0..2) != Some(b"MZ") {
+            bail!("The file is not a Windows executable");
+        }
+        let pe_offset = read_u32(0x3c)? as usize;
+        if data.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            bail!("The file is not a Windows executable");
+        }
+
+        // The optional header follows the 4 byte signature and the 20 byte COFF header
+        let optional_header = pe_offset + 24;
+        let (rva_count_offset, directories_offset) = match read_u16(optional_header)? {
+            0x10b => (optional_header + 92, optional_header + 96),
+            0x20b => (optional_header + 108, optional_header + 112),
+            magic => bail!("Unknown PE optional header magic {:#x}", magic),
+        };
+
+        // The security directory is the fifth data directory, its address is a file offset
+        let security_directory_offset = directories_offset + 4 * 8;
+        let certificate_table = if read_u32(rva_count_offset)? > 4 {
+            let offset = read_u32(security_directory_offset)? as usize;
+            let size = read_u32(security_directory_offset + 4)? as usize;
+            if offset == 0 || size == 0 {
+                None
+            } else if offset.saturating_add(size) > data.len() {
+                bail!("The executable's certificate table is out of bounds");
+            } else {
+                Some((offset, size))
+            }
+        } else {
+            None
+        };
+
+        Ok(Self {
+            data,
+            checksum_offset: optional_header + 64,
+            security_directory_offset,
+            certificate_table,
+        })
+    }
+
+    /// The PKCS#7 signature from the first entry of the certificate table
+    fn signature(&self) -> Option<&'a [u8]> {
+        let (offset, size) = self.certificate_table?;
+        let table = &self.data[offset..offset + size];
+
+        // WIN_CERTIFICATE: dwLength, wRevision and wCertificateType, then the certificate
+        let length = u32::from_le_bytes(table.get(0..4)?.try_into().ok()?) as usize;
+        let certificate_type = u16::from_le_bytes(table.get(6..8)?.try_into().ok()?);
+        if certificate_type != WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            return None;
+        }
+        table.get(8..length)
+    }
+
+    /// The Authenticode digest, the hash of the image excluding the checksum, the security
+    /// directory entry and the certificate table
+    fn authenticode_digest(&self, algorithm: &ObjectIdentifier) -> Result<Vec<u8>> {
+        let (table_start, table_end) = self
+            .certificate_table
+            .map_or((self.data.len(), self.data.len()), |(offset, size)| {
+                (offset, offset + size)
+            });
+
+        let ranges = [
+            0..self.checksum_offset,
+            self.checksum_offset + 4..self.security_directory_offset,
+            self.security_directory_offset + 8..table_start,
+            table_end..self.data.len(),
+        ];
+        let chunks = ranges.map(|range| &self.data[range]);
+        digest(algorithm, &chunks)
+    }
+}
+
+/// Hashes the data with the digest algorithm
+fn digest(algorithm: &ObjectIdentifier, chunks: &[&[u8]]) -> Result<Vec<u8>> {
+    fn hash<D: Digest>(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = D::new();
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    Ok(match algorithm.to_string().as_str() {
+        "1.3.14.3.2.26" => hash::<sha1::Sha1>(chunks),
+        "2.16.840.1.101.3.4.2.1" => hash::<Sha256>(chunks),
+        "2.16.840.1.101.3.4.2.2" => hash::<Sha384>(chunks),
+        "2.16.840.1.101.3.4.2.3" => hash::<Sha512>(chunks),
+        _ => bail!("Unsupported digest algorithm {}", algorithm),
+    })
+}
+
+/// The PKCS#1 v1.5 padding scheme for the digest algorithm
+fn pkcs1v15_scheme(algorithm: &ObjectIdentifier) -> Result<Pkcs1v15Sign> {
+    Ok(match algorithm.to_string().as_str() {
+        "1.3.14.3.2.26" => Pkcs1v15Sign::new::<sha1::Sha1>(),
+        "2.16.840.1.101.3.4.2.1" => Pkcs1v15Sign::new::<Sha256>(),
+        "2.16.840.1.101.3.4.2.2" => Pkcs1v15Sign::new::<Sha384>(),
+        "2.16.840.1.101.3.4.2.3" => Pkcs1v15Sign::new::<Sha512>(),
+        _ => bail!("Unsupported digest algorithm {}", algorithm),
+    })
+}
+
+/// Verifies an RSA PKCS#1 v1.5 signature of the data with the certificate's public key
+fn verify_rsa(
+    certificate: &Certificate,
+    digest_algorithm: &ObjectIdentifier,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    let public_key_der = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()?;
+    let public_key = RsaPublicKey::from_public_key_der(&public_key_der)
+        .context("Only RSA signatures are supported")?;
+
+    let hashed = digest(digest_algorithm, &[data])?;
+    public_key
+        .verify(pkcs1v15_scheme(digest_algorithm)?, &hashed, signature)
+        .context("The signature is invalid")
+}
+
+/// Verifies the certificate is signed by the issuer
+fn verify_certificate(certificate: &Certificate, issuer: &Certificate) -> Result<()> {
+    // Only RSA with PKCS#1 v1.5 padding, as used by Microsoft
+    let digest_algorithm = match certificate.signature_algorithm.oid.to_string().as_str() {
+        "1.2.840.113549.1.1.5" => ObjectIdentifier::new_unwrap("1.3.14.3.2.26"),
+        "1.2.840.113549.1.1.11" => ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1"),
+        "1.2.840.113549.1.1.12" => ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.2"),
+        "1.2.840.113549.1.1.13" => ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.3"),
+        oid => bail!("Unsupported certificate signature algorithm {}", oid),
+    };
+
+    let signature = certificate
+        .signature
+        .as_bytes()
+        .ok_or_else(|| anyhow!("The certificate signature is invalid"))?;
+    let tbs_der = certificate.tbs_certificate.to_der()?;
+    verify_rsa(issuer, &digest_algorithm, &tbs_der, signature)
+}
+
+/// Finds the certificate of the signer in the signature's certificates
+fn find_signer<'a>(
+    signer_info: &SignerInfo,
+    certificates: &[&'a Certificate],
+) -> Result<&'a Certificate> {
+    let SignerIdentifier::IssuerAndSerialNumber(id) = &signer_info.sid else {
+        bail!("The signer is not identified by issuer and serial number");
+    };
+
+    certificates
+        .iter()
+        .find(|certificate| {
+            certificate.tbs_certificate.issuer == id.issuer
+                && certificate.tbs_certificate.serial_number == id.serial_number
+        })
+        .copied()
+        .ok_or_else(|| anyhow!("The signing certificate is not included in the signature"))
+}
+
+/// Verifies the signed attributes match the signed content, and are signed by the signer
+fn verify_signer_info(
+    signer_info: &SignerInfo,
+    signer: &Certificate,
+    content: &[u8],
+) -> Result<()> {
+    let signed_attrs = signer_info
+        .signed_attrs
+        .as_ref()
+        .ok_or_else(|| anyhow!("The signature has no signed attributes"))?;
+    let attribute = |oid: ObjectIdentifier| {
+        signed_attrs
+            .iter()
+            .find(|attribute| attribute.oid == oid)
+            .and_then(|attribute| attribute.values.get(0))
+            .ok_or_else(|| anyhow!("The signature has no {} attribute", oid))
+    };
+
+    let content_type: ObjectIdentifier = attribute(OID_CONTENT_TYPE)?.decode_as()?;
+    if content_type != OID_SPC_INDIRECT_DATA {
+        bail!("The signed content type is not Authenticode");
+    }
+
+    // Authenticode digests the contents of the SpcIndirectDataContent, without its tag and length
+    let message_digest: OctetString = attribute(OID_MESSAGE_DIGEST)?.decode_as()?;
+    let digest_algorithm = &signer_info.digest_alg.oid;
+    if digest(digest_algorithm, &[content])? != message_digest.as_bytes() {
+        bail!("The signed content does not match the signature");
+    }
+
+    // The signature is over the DER encoding of the attributes as a SET OF
+    verify_rsa(
+        signer,
+        digest_algorithm,
+        &signed_attrs.to_der()?,
+        signer_info.signature.as_bytes(),
+    )
+}
+
+fn name_attribute(name: &Name, oid: ObjectIdentifier) -> Option<String> {
+    name.0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|attribute| attribute.oid == oid)
+        .and_then(|attribute| std::str::from_utf8(attribute.value.value()).ok())
+        .map(str::to_string)
+}
+
+fn common_name(name: &Name) -> String {
+    name_attribute(name, OID_COMMON_NAME).unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x509_cert::ext::pkix::{AuthorityKeyIdentifier, SubjectKeyIdentifier};
+
+    // Generated by testdata/authenticode/generate.py
+    const SIGNED_EXE: &[u8] = include_bytes!("../testdata/authenticode/signed.exe");
+    const FORGED_EXE: &[u8] = include_bytes!("../testdata/authenticode/forged.exe");
+    const UNSIGNED_EXE: &[u8] = include_bytes!("../testdata/authenticode/unsigned.exe");
+    const ROOT_CER: &[u8] = include_bytes!("../testdata/authenticode/root.cer");
+    const OTHER_ROOT_CER: &[u8] = include_bytes!("../testdata/authenticode/other-root.cer");
+
+    fn test_verifier() -> AuthenticodeVerifier {
+        let mut verifier = AuthenticodeVerifier::new().require_organization("Test Corporation");
+        verifier.add_root_certificate(ROOT_CER).unwrap();
+        verifier
+    }
+
+    #[test]
+    fn test_verify_signed() {
+        let info = test_verifier()
+            .verify(SIGNED_EXE)
+            .expect("Failed to verify");
+
+        assert_eq!(info.signer, "Test Corporation");
+        assert_eq!(
+            info.chain,
+            vec![
+                "Test Corporation",
+                "Test Code Signing PCA",
+                "Test Root Certificate Authority"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_forged_chain() {
+        // The intermediate copies the test root's name and key identifier, but is signed by another key
+        let root = Certificate::from_der(ROOT_CER).unwrap();
+        let (_, root_key_id) = root
+            .tbs_certificate
+            .get::<SubjectKeyIdentifier>()
+            .unwrap()
+            .unwrap();
+        let signed_data: SignedData =
+            ContentInfo::from_der(PeImage::parse(FORGED_EXE).unwrap().signature().unwrap())
+                .unwrap()
+                .content
+                .decode_as()
+                .unwrap();
+        let forged = signed_data
+            .certificates
+            .iter()
+            .flat_map(|set| set.0.iter())
+            .find_map(|choice| match choice {
+                CertificateChoices::Certificate(certificate)
+                    if certificate.tbs_certificate.issuer == root.tbs_certificate.subject =>
+                {
+                    Some(certificate)
+                }
+                _ => None,
+            })
+            .unwrap();
+        let (_, forged_key_id) = forged
+            .tbs_certificate
+            .get::<AuthorityKeyIdentifier>()
+            .unwrap()
+            .unwrap();
+        assert_eq!(forged_key_id.key_identifier, Some(root_key_id.0));
+
+        let error = test_verifier().verify(FORGED_EXE).unwrap_err();
+        assert!(error.to_string().contains("not issued by a trusted root"));
+    }
+
+    #[test]
+    fn test_verify_modified() {
+        // Modify a byte in the section data
+        let mut data = SIGNED_EXE.to_vec();
+        data[0x400] ^= 0xff;
+
+        let error = test_verifier().verify(&data).unwrap_err();
+        assert!(error.to_string().contains("modified"));
+    }
+
+    #[test]
+    fn test_verify_unsigned() {
+        let error = test_verifier().verify(UNSIGNED_EXE).unwrap_err();
+        assert!(error.to_string().contains("not signed"));
+
+        let error = test_verifier().verify(b"not an executable").unwrap_err();
+        assert!(error.to_string().contains("not a Windows executable"));
+    }
+
+    #[test]
+    fn test_verify_untrusted() {
+        let mut verifier = AuthenticodeVerifier::new();
+        verifier.add_root_certificate(OTHER_ROOT_CER).unwrap();
+        assert!(verifier.verify(SIGNED_EXE).is_err());
+
+        // Signed by Test Corporation, not Microsoft
+        let error = AuthenticodeVerifier::microsoft()
+            .verify(SIGNED_EXE)
+            .unwrap_err();
+        assert!(error.to_string().contains("not Microsoft Corporation"));
+    }
+
+    #[test]
+    fn test_authenticode_digest_ignores_signature() {
+        let signed = PeImage::parse(SIGNED_EXE).unwrap();
+        let unsigned = PeImage::parse(UNSIGNED_EXE).unwrap();
+        let sha256 = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.2.1");
+
+        assert_eq!(
+            signed.authenticode_digest(&sha256).unwrap(),
+            unsigned.authenticode_digest(&sha256).unwrap()
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use crate::hash::sha256_hex;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytesize::ByteSize;
+use dirs_next::cache_dir;
+use reqwest::blocking::Client;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+/// How long downloads are cached before they're downloaded again
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How many times a failed download is retried from each URL
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Options for downloading, and caching, the files bundled in the setup executable
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// URLs tried in order when the download URL fails
+    pub mirrors: Vec<String>,
+    /// Expected SHA-256 of the file, checked for downloaded and cached files
+    pub sha256: Option<String>,
+    /// Cached files older than this are downloaded again
+    pub cache_ttl: Duration,
+    /// Download again, even when the file is cached
+    pub refresh: bool,
+    /// Only use cached files, failing if the file hasn't been downloaded before
+    pub offline: bool,
+    /// How many times a failed download is retried from each URL
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each retry after that
+    pub retry_delay: Duration,
+    /// Directory files are cached in, defaults to `CACHE_DIR` or the user's cache directory
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            mirrors: Vec::new(),
+            sha256: None,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            refresh: false,
+            offline: false,
+            retries: DEFAULT_RETRIES,
+            retry_delay: Duration::from_secs(1),
+            cache_dir: None,
+        }
+    }
+}
+
+impl DownloadOptions {
+    fn cache_dir(&self) -> Result<PathBuf> {
+        self.cache_dir
+            .clone()
+            .or_else(|| std::env::var("CACHE_DIR").ok().map(PathBuf::from))
+            .or_else(|| cache_dir().map(|d| d.join("webview2")))
+            .ok_or_else(|| anyhow!("Failed to get cache directory"))
+    }
+}
+
+/// Path a download is cached at, keyed by the URL so different URLs are never mixed up
+pub fn cache_path(cache_dir: &Path, url: &str, filename: &str) -> PathBuf {
+    cache_dir
+        .join(&sha256_hex(url.as_bytes())[..16])
+        .join(filename)
+}
+
+/// Downloads a file in to the cache directory, or loads it if it has already been downloaded
+pub fn download_cached(
+    url: &str,
+    filename: &str,
+    description: &str,
+    options: &DownloadOptions,
+) -> Result<Vec<u8>> {
+    let path = cache_path(&options.cache_dir()?, url, filename);
+
+    if options.offline {
+        if !path.exists() {
+            bail!(
+                "{} is not cached at {}, run the bundler without --offline to download it",
+                description,
+                path.display()
+            );
+        }
+        let data = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        verify_sha256(&data, options.sha256.as_deref())
+            .with_context(|| format!("Cached {} at {:?} is invalid", description, path))?;
+        print_loaded(description, filename, &data);
+        return Ok(data);
+    }
+
+    if !options.refresh && is_fresh(&path, options.cache_ttl) {
+        let data = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        match verify_sha256(&data, options.sha256.as_deref()) {
+            Ok(()) => {
+                print_loaded(description, filename, &data);
+                return Ok(data);
+            }
+            Err(e) => println!(
+                "  Cached {} is invalid, downloading again: {}",
+                description, e
+            ),
+        }
+    }
+
+    let data = download_with_mirrors(url, description, options)?;
+
+    // Write to a temporary file first, so a partial download is never cached
+    let parent = path.parent().expect("Cache path has no parent");
+    fs::create_dir_all(parent).context("Failed to create cache directory")?;
+    let partial_path = path.with_extension("partial");
+    fs::write(&partial_path, &data)
+        .with_context(|| format!("Failed to write {:?}", partial_path))?;
+    fs::rename(&partial_path, &path).with_context(|| format!("Failed to write {:?}", path))?;
+
+    print_loaded(description, filename, &data);
+    Ok(data)
+}
+
+/// Downloads from the URL then each mirror in turn, until one succeeds
+fn download_with_mirrors(
+    url: &str,
+    description: &str,
+    options: &DownloadOptions,
+) -> Result<Vec<u8>> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10 * 60))
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let mut errors = Vec::new();
+    for url in std::iter::once(url).chain(options.mirrors.iter().map(String::as_str)) {
+        println!("  Downloading {}: {}", description, url);
+        match download_with_retries(&client, url, options)
+            .and_then(|data| verify_sha256(&data, options.sha256.as_deref()).map(|_| data))
+        {
+            Ok(data) => return Ok(data),
+            Err(e) => {
+                println!("  Failed to download {}: {:#}", url, e);
+                errors.push(format!("{}: {:#}", url, e));
+            }
+        }
+    }
+
+    bail!(
+        "Failed to download {}\n  {}",
+        description,
+        errors.join("\n  ")
+    )
+}
+
+/// Downloads the URL, retrying with backoff after network errors and server errors
+fn download_with_retries(client: &Client, url: &str, options: &DownloadOptions) -> Result<Vec<u8>> {
+    let mut delay = options.retry_delay;
+    let mut attempt = 0;
+    loop {
+        match download(client, url) {
+            Ok(data) => return Ok(data),
+            Err(DownloadError::Permanent(e)) => return Err(e),
+            Err(DownloadError::Transient(e)) if attempt >= options.retries => return Err(e),
+            Err(DownloadError::Transient(e)) => {
+                attempt += 1;
+                println!(
+                    "  Retrying in {:?} ({}/{}): {:#}",
+                    delay, attempt, options.retries, e
+                );
+                sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+}
+
+enum DownloadError {
+    /// Worth retrying, e.g. a timeout or a 503
+    Transient(anyhow::Error),
+    /// Not worth retrying, e.g. a 404
+    Permanent(anyhow::Error),
+}
+
+fn download(client: &Client, url: &str) -> Result<Vec<u8>, DownloadError> {
+    let response = client
+        .get(url)
+        .send()
+        .map_err(|e| DownloadError::Transient(e.into()))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error = anyhow!("HTTP status {}", status);
+        return Err(if status.is_server_error() || status.as_u16() == 429 {
+            DownloadError::Transient(error)
+        } else {
+            DownloadError::Permanent(error)
+        });
+    }
+
+    let bytes = response
+        .bytes()
+        .map_err(|e| DownloadError::Transient(e.into()))?;
+    Ok(bytes.to_vec())
+}
+
+fn verify_sha256(data: &[u8], expected: Option<&str>) -> Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+
+    let actual = sha256_hex(data);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("SHA-256 mismatch, expected {} but got {}", expected, actual);
+    }
+    Ok(())
+}
+
+fn is_fresh(path: &Path, ttl: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < ttl)
+}
+
+fn print_loaded(description: &str, filename: &str, data: &[u8]) {
+    println!(
+        "  Loaded {}: {} ({} bytes)",
+        description,
+        filename,
+        ByteSize(data.len().try_into().unwrap())
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Server;
+    use tempfile::tempdir;
+
+    fn test_options(cache_dir: &Path) -> DownloadOptions {
+        DownloadOptions {
+            retry_delay: Duration::ZERO,
+            cache_dir: Some(cache_dir.to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_download_not_found_is_not_cached() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("GET", "/missing")
+            .with_status(404)
+            .with_body("<html>Not Found</html>")
+            .expect(1)
+            .create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let url = format!("{}/missing", server.url());
+        let result = download_cached(
+            &url,
+            "file.exe",
+            "test file",
+            &test_options(cache_dir.path()),
+        );
+
+        assert!(format!("{:#}", result.unwrap_err()).contains("404"));
+        assert!(!cache_path(cache_dir.path(), &url, "file.exe").exists());
+        mock.assert();
+    }
+
+    #[test]
+    fn test_download_retries_server_errors() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("GET", "/flaky")
+            .with_status(503)
+            .expect(3)
+            .create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let options = DownloadOptions {
+            retries: 2,
+            ..test_options(cache_dir.path())
+        };
+        let url = format!("{}/flaky", server.url());
+
+        assert!(download_cached(&url, "file.exe", "test file", &options).is_err());
+        mock.assert();
+    }
+
+    #[test]
+    fn test_download_falls_back_to_mirror() {
+        let mut server = Server::new();
+        let primary = server.mock("GET", "/primary").with_status(404).create();
+        let mirror = server
+            .mock("GET", "/mirror")
+            .with_status(200)
+            .with_body("from mirror")
+            .create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let options = DownloadOptions {
+            mirrors: vec![format!("{}/mirror", server.url())],
+            ..test_options(cache_dir.path())
+        };
+        let url = format!("{}/primary", server.url());
+        let data = download_cached(&url, "file.exe", "test file", &options).unwrap();
+
+        assert_eq!(data, b"from mirror");
+        // Cached under the requested URL, not the mirror
+        assert!(cache_path(cache_dir.path(), &url, "file.exe").exists());
+        primary.assert();
+        mirror.assert();
+    }
+
+    #[test]
+    fn test_download_sha256_pinning() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/file")
+            .with_status(200)
+            .with_body("hello")
+            .create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let url = format!("{}/file", server.url());
+
+        let options = DownloadOptions {
+            sha256: Some("00".repeat(32)),
+            ..test_options(cache_dir.path())
+        };
+        let error = download_cached(&url, "file.exe", "test file", &options).unwrap_err();
+        assert!(format!("{:#}", error).contains("SHA-256 mismatch"));
+        assert!(!cache_path(cache_dir.path(), &url, "file.exe").exists());
+
+        let options = DownloadOptions {
+            sha256: Some(
+                "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824".to_string(),
+            ),
+            ..test_options(cache_dir.path())
+        };
+        let data = download_cached(&url, "file.exe", "test file", &options).unwrap();
+        assert_eq!(data, b"hello");
+    }
+
+    #[test]
+    fn test_download_cache_ttl_and_refresh() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("GET", "/file")
+            .with_status(200)
+            .with_body("downloaded")
+            .expect(2)
+            .create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let url = format!("{}/file", server.url());
+        let path = cache_path(cache_dir.path(), &url, "file.exe");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        // A fresh cached file is used
+        fs::write(&path, "cached").unwrap();
+        let options = test_options(cache_dir.path());
+        let data = download_cached(&url, "file.exe", "test file", &options).unwrap();
+        assert_eq!(data, b"cached");
+
+        // An expired cached file is downloaded again
+        fs::write(&path, "cached").unwrap();
+        let options = DownloadOptions {
+            cache_ttl: Duration::ZERO,
+            ..test_options(cache_dir.path())
+        };
+        let data = download_cached(&url, "file.exe", "test file", &options).unwrap();
+        assert_eq!(data, b"downloaded");
+
+        // Refreshing ignores the cache
+        fs::write(&path, "cached").unwrap();
+        let options = DownloadOptions {
+            refresh: true,
+            ..test_options(cache_dir.path())
+        };
+        let data = download_cached(&url, "file.exe", "test file", &options).unwrap();
+        assert_eq!(data, b"downloaded");
+
+        mock.assert();
+    }
+
+    #[test]
+    fn test_download_offline() {
+        let mut server = Server::new();
+        let mock = server.mock("GET", "/file").expect(0).create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let url = format!("{}/file", server.url());
+        let options = DownloadOptions {
+            offline: true,
+            cache_ttl: Duration::ZERO,
+            ..test_options(cache_dir.path())
+        };
+
+        // Fails clearly when the cache is empty
+        let error = download_cached(&url, "file.exe", "test file", &options).unwrap_err();
+        assert!(error.to_string().contains("without --offline"));
+
+        // Uses the cached file, even when it has expired
+        let path = cache_path(cache_dir.path(), &url, "file.exe");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "cached").unwrap();
+        let data = download_cached(&url, "file.exe", "test file", &options).unwrap();
+        assert_eq!(data, b"cached");
+
+        mock.assert();
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use log::info;
+use std::ffi::OsStr;
+use std::mem::size_of;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{CloseHandle, ERROR_CANCELLED, HANDLE};
+use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, GetExitCodeProcess, OpenProcessToken, WaitForSingleObject, INFINITE,
+};
+use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW};
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+/// Whether the process is running elevated, as an administrator
+pub fn is_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut size = 0;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut _),
+            size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        let _ = CloseHandle(token);
+
+        result.is_ok() && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Runs the executable again with the `runas` verb, showing the UAC prompt, and waits for it to
+/// exit. Used by setup and the uninstaller.
+///
+/// The elevated process logs to its own file next to `log_path`, as opening the same log would
+/// truncate it. Returns its exit code, or `None` if the UAC prompt was declined.
+pub fn relaunch_elevated(extra_args: &[&str], log_path: Option<&Path>) -> Result<Option<i32>> {
+    let exe_path = std::env::current_exe().context("Failed to get the path of the executable")?;
+    let log_path = log_path.map(elevated_log_path);
+    let parameters = elevated_args(std::env::args().skip(1), extra_args, log_path.as_deref())
+        .iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    info!("Relaunching {:?} elevated with: {}", exe_path, parameters);
+
+    let verb = to_wide_null("runas");
+    let file = to_wide_null(&exe_path);
+    let parameters = to_wide_null(&parameters);
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS,
+        lpVerb: PCWSTR(verb.as_ptr()),
+        lpFile: PCWSTR(file.as_ptr()),
+        lpParameters: PCWSTR(parameters.as_ptr()),
+        nShow: SW_SHOWNORMAL.0,
+        ..Default::default()
+    };
+
+    unsafe {
+        if let Err(e) = ShellExecuteExW(&mut info) {
+            if e.code() == ERROR_CANCELLED.to_hresult() {
+                return Ok(None);
+            }
+            return Err(e).context("Failed to relaunch elevated");
+        }
+
+        WaitForSingleObject(info.hProcess, INFINITE);
+        let mut exit_code = 0u32;
+        let result = GetExitCodeProcess(info.hProcess, &mut exit_code);
+        let _ = CloseHandle(info.hProcess);
+        result.context("Failed to get the exit code of the elevated process")?;
+
+        Ok(Some(exit_code as i32))
+    }
+}
+
+/// The log file of the elevated process, `{name}-elevated.log` next to the log file
+fn elevated_log_path(log_path: &Path) -> PathBuf {
+    let mut file_name = log_path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push("-elevated");
+    if let Some(extension) = log_path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    log_path.with_file_name(file_name)
+}
+
+/// The arguments of the elevated process: the current arguments, without any the extra arguments
+/// or log file replace, followed by them
+fn elevated_args(
+    mut args: impl Iterator<Item = String>,
+    extra_args: &[&str],
+    log_path: Option<&Path>,
+) -> Vec<String> {
+    let replace_log = log_path.is_some();
+    let mut elevated_args = Vec::new();
+    while let Some(arg) = args.next() {
+        if replace_log && arg == "--log" {
+            args.next();
+        } else if !(replace_log && arg.starts_with("--log=") || extra_args.contains(&arg.as_str()))
+        {
+            elevated_args.push(arg);
+        }
+    }
+
+    elevated_args.extend(extra_args.iter().map(|arg| arg.to_string()));
+    if let Some(log_path) = log_path {
+        elevated_args.push("--log".to_string());
+        elevated_args.push(log_path.to_string_lossy().to_string());
+    }
+    elevated_args
+}
+
+fn to_wide_null(value: impl AsRef<OsStr>) -> Vec<u16> {
+    value.as_ref().encode_wide().chain(Some(0)).collect()
+}
+
+/// Quotes an argument so `CommandLineToArgvW` parses it back unchanged
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote are escaped, along with the quote
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    // Backslashes before the closing quote are escaped
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("--silent"), "--silent");
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(
+            quote_arg("C:\\Program Files\\App"),
+            "\"C:\\Program Files\\App\""
+        );
+        assert_eq!(quote_arg("C:\\My Dir\\"), "\"C:\\My Dir\\\\\"");
+        assert_eq!(quote_arg("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn test_elevated_log_path() {
+        assert_eq!(
+            elevated_log_path(Path::new("C:\\Temp\\com.example.app-setup.log")),
+            PathBuf::from("C:\\Temp\\com.example.app-setup-elevated.log")
+        );
+        assert_eq!(
+            elevated_log_path(Path::new("C:\\Logs\\setup")),
+            PathBuf::from("C:\\Logs\\setup-elevated")
+        );
+    }
+
+    #[test]
+    fn test_elevated_args() {
+        let args = ["--silent", "--per-machine", "--log", "C:\\setup.log"].map(String::from);
+        assert_eq!(
+            elevated_args(
+                args.clone().into_iter(),
+                &["--per-machine", "--no-launch"],
+                Some(Path::new("C:\\setup-elevated.log"))
+            ),
+            vec![
+                "--silent",
+                "--per-machine",
+                "--no-launch",
+                "--log",
+                "C:\\setup-elevated.log"
+            ]
+        );
+        assert_eq!(
+            elevated_args(args.into_iter(), &[], None),
+            vec!["--silent", "--per-machine", "--log", "C:\\setup.log"]
+        );
+    }
+}
@@ -1,4 +1,9 @@
+use crate::file_association::FileAssociation;
 use crate::metadata::{MetadataEntry, METADATA_OFFSET_SIZE};
+use crate::prerequisite::Prerequisite;
+use crate::scope::InstallScope;
+use crate::shortcut::Shortcuts;
+use crate::webview2::Webview2Install;
 
 use serde::{Deserialize, Serialize};
 use serde_json;
@@ -26,6 +31,7 @@ use std::path::Path;
 ///         version: "1.0.0".to_string(),
 ///         identifier: "com.example.myapp".to_string(),
 ///         application: "myapp.exe".to_string(),
+///         ..Default::default()
 ///     };
 ///     packager.add_manifest(&manifest);
 ///     packager.package(Path::new("output.exe"));
@@ -39,13 +45,38 @@ pub struct ExePackager {
     manifest: Option<Vec<u8>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct SetupManifest {
     pub name: String,
     pub title: String,
     pub version: String,
     pub identifier: String,
     pub application: String,
+    /// How WebView2 is installed, packages without it embed the bootstrapper if present
+    #[serde(default)]
+    pub webview2: Option<Webview2Install>,
+    /// Prerequisites installed in order before the application
+    #[serde(default)]
+    pub prerequisites: Vec<Prerequisite>,
+    /// Whether an older version can replace a newer installed version, from Tauri's
+    /// `bundle.windows.allowDowngrades`
+    #[serde(default)]
+    pub allow_downgrades: bool,
+    /// Who the application can be installed for
+    #[serde(default)]
+    pub install_scope: InstallScope,
+    /// Installation directory template from `installDir`, instead of the default of the scope
+    #[serde(default)]
+    pub install_dir: Option<String>,
+    /// Shortcuts created to the application
+    #[serde(default)]
+    pub shortcuts: Shortcuts,
+    /// File name of the application icon in the package, installed next to the application
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// File types opened with the application, from Tauri's `bundle.fileAssociations`
+    #[serde(default)]
+    pub file_associations: Vec<FileAssociation>,
 }
 
 impl ExePackager {
@@ -162,6 +193,7 @@ mod tests {
             version: "1.0.0".to_string(),
             identifier: "com.example.testapp".to_string(),
             application: "test.exe".to_string(),
+            ..Default::default()
         };
 
         packager.add_manifest(&manifest);
@@ -183,6 +215,7 @@ mod tests {
             version: "1.0.0".to_string(),
             identifier: "com.example.testapp".to_string(),
             application: "test.exe".to_string(),
+            ..Default::default()
         };
 
         packager.add_manifest(&manifest);
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+/// Key the file associations are registered under, in the registry hive of the install scope
+pub const CLASSES_KEY: &str = "Software\\Classes";
+
+/// A file association, from Tauri's `bundle.fileAssociations`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileAssociation {
+    /// Extensions without the leading `.`
+    pub extensions: Vec<String>,
+    /// Name of the file type, defaulting to the first extension
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Shown in the `Type` column of Explorer
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Not registered, as the extension's `Content Type` is shared with other applications
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+impl FileAssociation {
+    /// The associations in Tauri's bundle config
+    pub fn from_tauri_config(tauri_conf: &tauri::Config) -> Vec<Self> {
+        tauri_conf
+            .bundle
+            .file_associations
+            .iter()
+            .flatten()
+            .map(|association| FileAssociation {
+                extensions: association.ext.iter().map(|ext| ext.0.clone()).collect(),
+                name: association.name.clone(),
+                description: association.description.clone(),
+                mime_type: association.mime_type.clone(),
+            })
+            .collect()
+    }
+
+    /// The ProgID of the file type, `{identifier}.{name}` with any characters not allowed removed
+    pub fn prog_id(&self, identifier: &str) -> String {
+        let name = self
+            .name
+            .as_deref()
+            .or(self.extensions.first().map(String::as_str))
+            .unwrap_or_default();
+        format!("{}.{}", identifier, name)
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+            .collect()
+    }
+}
+
+/// A string value of the file associations, the key is relative to the registry hive
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssociationValue {
+    pub key: String,
+    pub name: String,
+    pub value: String,
+    /// Whether the value is in an extension's `OpenWithProgids` key, shared with other
+    /// applications, so it's only removed when uninstalling if it's unchanged
+    pub shared: bool,
+}
+
+/// The registry values registering the file associations of an application
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssociationPlan {
+    /// Values to write, in order
+    pub values: Vec<AssociationValue>,
+    /// The ProgID keys, owned by the application and removed with their subkeys when uninstalling
+    pub prog_id_keys: Vec<String>,
+    /// The extension keys, shared with other applications and only removed if they're left empty
+    pub extension_keys: Vec<String>,
+}
+
+impl AssociationPlan {
+    /// Plans the values opening the associated files with the executable.
+    ///
+    /// The ProgID is only added to the `Open with` list of each extension, the extension's default
+    /// and `Content Type` belong to whichever application the user chose and aren't replaced.
+    pub fn new(associations: &[FileAssociation], identifier: &str, exe_path: &str) -> Self {
+        let mut plan = AssociationPlan::default();
+        for association in associations {
+            let prog_id = association.prog_id(identifier);
+            let prog_id_key = format!("{}\\{}", CLASSES_KEY, prog_id);
+            let description = association
+                .description
+                .clone()
+                .or_else(|| association.name.clone())
+                .unwrap_or_else(|| prog_id.clone());
+
+            plan.add(&prog_id_key, "", &description, false);
+            plan.add(
+                &format!("{}\\DefaultIcon", prog_id_key),
+                "",
+                &format!("\"{}\",0", exe_path),
+                false,
+            );
+            plan.add(
+                &format!("{}\\shell\\open\\command", prog_id_key),
+                "",
+                &format!("\"{}\" \"%1\"", exe_path),
+                false,
+            );
+            plan.prog_id_keys.push(prog_id_key);
+
+            for extension in &association.extensions {
+                let extension_key = format!("{}\\.{}", CLASSES_KEY, extension);
+                plan.add(
+                    &format!("{}\\OpenWithProgids", extension_key),
+                    &prog_id,
+                    "",
+                    true,
+                );
+                plan.extension_keys.push(extension_key);
+            }
+        }
+        plan
+    }
+
+    /// Whether the key is an extension key shared with other applications, which isn't removed
+    pub fn is_shared_key(&self, key: &str) -> bool {
+        self.values
+            .iter()
+            .filter(|value| value.shared)
+            .map(|value| &value.key)
+            .chain(&self.extension_keys)
+            .any(|shared_key| shared_key.eq_ignore_ascii_case(key))
+    }
+
+    fn add(&mut self, key: &str, name: &str, value: &str, shared: bool) {
+        self.values.push(AssociationValue {
+            key: key.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+            shared,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn association() -> FileAssociation {
+        FileAssociation {
+            extensions: vec!["demo".to_string(), "dmo".to_string()],
+            name: Some("Demo Project".to_string()),
+            description: Some("Demo App Project".to_string()),
+            mime_type: Some("application/x-demo".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_from_tauri_config() {
+        let tauri_conf: tauri::Config = serde_json::from_value(json!({
+            "identifier": "com.gussy.demo-app",
+            "bundle": {
+                "fileAssociations": [
+                    {
+                        "ext": [".demo", "dmo"],
+                        "name": "Demo Project",
+                        "description": "Demo App Project",
+                        "role": "Editor",
+                        "mimeType": "application/x-demo"
+                    },
+                    { "ext": ["txt"] }
+                ]
+            }
+        }))
+        .unwrap();
+
+        let associations = FileAssociation::from_tauri_config(&tauri_conf);
+        assert_eq!(associations.len(), 2);
+        assert_eq!(associations[0], association());
+        assert_eq!(associations[1].extensions, vec!["txt".to_string()]);
+        assert_eq!(
+            associations[1].prog_id("com.gussy.demo-app"),
+            "com.gussy.demo-app.txt"
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let plan = AssociationPlan::new(
+            &[association()],
+            "com.gussy.demo-app",
+            "C:\\Apps\\demo-app.exe",
+        );
+        let values = plan
+            .values
+            .iter()
+            .map(|value| {
+                (
+                    value.key.as_str(),
+                    value.name.as_str(),
+                    value.value.as_str(),
+                    value.shared,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let prog_id = "Software\\Classes\\com.gussy.demo-app.DemoProject";
+        assert_eq!(
+            values,
+            vec![
+                (prog_id, "", "Demo App Project", false),
+                (
+                    "Software\\Classes\\com.gussy.demo-app.DemoProject\\DefaultIcon",
+                    "",
+                    "\"C:\\Apps\\demo-app.exe\",0",
+                    false
+                ),
+                (
+                    "Software\\Classes\\com.gussy.demo-app.DemoProject\\shell\\open\\command",
+                    "",
+                    "\"C:\\Apps\\demo-app.exe\" \"%1\"",
+                    false
+                ),
+                (
+                    "Software\\Classes\\.demo\\OpenWithProgids",
+                    "com.gussy.demo-app.DemoProject",
+                    "",
+                    true
+                ),
+                (
+                    "Software\\Classes\\.dmo\\OpenWithProgids",
+                    "com.gussy.demo-app.DemoProject",
+                    "",
+                    true
+                ),
+            ]
+        );
+        assert_eq!(plan.prog_id_keys, vec![prog_id.to_string()]);
+        assert_eq!(
+            plan.extension_keys,
+            vec![
+                "Software\\Classes\\.demo".to_string(),
+                "Software\\Classes\\.dmo".to_string()
+            ]
+        );
+        assert!(plan.is_shared_key("Software\\Classes\\.DEMO"));
+        assert!(plan.is_shared_key("Software\\Classes\\.demo\\OpenWithProgids"));
+        assert!(!plan.is_shared_key(prog_id));
+    }
+}
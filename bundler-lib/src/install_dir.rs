@@ -0,0 +1,109 @@
+use crate::scope::Scope;
+
+use anyhow::{bail, Result};
+
+/// Installation directory of per-user installs, unless `installDir` is set in the plugin config
+pub const DEFAULT_PER_USER_INSTALL_DIR: &str = "{LocalAppData}\\{identifier}";
+
+/// Installation directory of per-machine installs, unless `installDir` is set in the plugin config
+pub const DEFAULT_PER_MACHINE_INSTALL_DIR: &str = "{ProgramFiles}\\{productName}";
+
+/// Registry key in `HKEY_LOCAL_MACHINE` under which admins can set the installation directory of
+/// an application, as the `InstallDir` value of its `{identifier}` subkey
+pub const INSTALL_DIR_POLICY_KEY: &str = "Software\\Policies\\TauriWindowsInstaller";
+
+/// Name of the policy value, which can use the same placeholders as `installDir`
+pub const INSTALL_DIR_POLICY_VALUE: &str = "InstallDir";
+
+/// Placeholders supported in installation directory templates
+pub const INSTALL_DIR_PLACEHOLDERS: [&str; 4] =
+    ["LocalAppData", "ProgramFiles", "productName", "identifier"];
+
+/// The default installation directory template of the install scope
+pub fn default_install_dir(scope: Scope) -> &'static str {
+    match scope {
+        Scope::PerUser => DEFAULT_PER_USER_INSTALL_DIR,
+        Scope::PerMachine => DEFAULT_PER_MACHINE_INSTALL_DIR,
+    }
+}
+
+/// Policy key of an application, see [`INSTALL_DIR_POLICY_KEY`]
+pub fn install_dir_policy_key(identifier: &str) -> String {
+    format!("{}\\{}", INSTALL_DIR_POLICY_KEY, identifier)
+}
+
+/// Expands the `{placeholder}`s of an installation directory template, failing on unknown or
+/// unclosed placeholders
+pub fn expand_install_dir(
+    template: &str,
+    lookup: impl Fn(&str) -> Result<String>,
+) -> Result<String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            bail!("Unclosed placeholder in install directory '{}'", template);
+        };
+        let name = &after[..end];
+        if !INSTALL_DIR_PLACEHOLDERS.contains(&name) {
+            bail!(
+                "Unknown placeholder {{{}}} in install directory '{}', use {}",
+                name,
+                template,
+                INSTALL_DIR_PLACEHOLDERS
+                    .map(|name| format!("{{{}}}", name))
+                    .join(", ")
+            );
+        }
+        output.push_str(&lookup(name)?);
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Checks the placeholders of an installation directory template, when bundling
+pub fn validate_install_dir(template: &str) -> Result<()> {
+    expand_install_dir(template, |_| Ok(String::new())).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Result<String> {
+        Ok(match name {
+            "LocalAppData" => "C:\\Users\\gussy\\AppData\\Local",
+            "ProgramFiles" => "C:\\Program Files",
+            "productName" => "Demo App",
+            _ => "com.gussy.demo-app",
+        }
+        .to_string())
+    }
+
+    #[test]
+    fn test_expand_install_dir() {
+        assert_eq!(
+            expand_install_dir("{LocalAppData}\\Programs\\{productName}", lookup).unwrap(),
+            "C:\\Users\\gussy\\AppData\\Local\\Programs\\Demo App"
+        );
+        assert_eq!(
+            expand_install_dir(DEFAULT_PER_USER_INSTALL_DIR, lookup).unwrap(),
+            "C:\\Users\\gussy\\AppData\\Local\\com.gussy.demo-app"
+        );
+        assert_eq!(
+            expand_install_dir(default_install_dir(Scope::PerMachine), lookup).unwrap(),
+            "C:\\Program Files\\Demo App"
+        );
+        assert_eq!(expand_install_dir("D:\\Apps", lookup).unwrap(), "D:\\Apps");
+    }
+
+    #[test]
+    fn test_validate_install_dir() {
+        assert!(validate_install_dir("{ProgramFiles}\\Gussy\\{productName}").is_ok());
+        assert!(validate_install_dir("{AppData}\\{productName}").is_err());
+        assert!(validate_install_dir("{LocalAppData}\\{productName").is_err());
+    }
+}
@@ -1,18 +1,37 @@
+pub mod authenticode;
+pub mod download;
+#[cfg(windows)]
+pub mod elevation;
 pub mod exe_packager;
+pub mod file_association;
+pub mod hash;
+pub mod install_dir;
 pub mod metadata;
+pub mod package_managers;
 pub mod plugin_config;
+pub mod portable;
+pub mod prerequisite;
+pub mod receipt;
+pub mod release;
+pub mod sbom;
+pub mod scope;
+pub mod shortcut;
+pub mod version;
 pub mod webview2;
 
 pub use crate::exe_packager::{ExePackager, SetupManifest};
 pub use crate::metadata::MetadataEntry;
 
+use anyhow::{anyhow, bail, Context, Result};
 use metadata::METADATA_OFFSET_SIZE;
+use plugin_config::Webview2Bundle;
+use prerequisite::Prerequisite;
 use serde_json;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
-use webview2::WEBVIEW2_EVERGREEN_EXE;
+use webview2::{WEBVIEW2_EVERGREEN_EXE, WEBVIEW2_FIXED_RUNTIME_DIR};
 
 /// A package containing files and a manifest
 #[derive(Debug)]
@@ -23,54 +42,71 @@ pub struct SetupPackage {
 
 /// Extracts the manifest and files from a packaged executable
 pub fn extract_package(exe_path: &Path) -> SetupPackage {
-    let mut file = File::open(exe_path).expect("Failed to open executable file");
+    try_extract_package(exe_path).expect("Failed to extract package")
+}
+
+/// Extracts the manifest and files from a packaged executable, failing if it's truncated or corrupt
+pub fn try_extract_package(exe_path: &Path) -> Result<SetupPackage> {
+    let mut file = File::open(exe_path).context("Failed to open executable file")?;
+    let file_length = file
+        .metadata()
+        .context("Failed to get file metadata")?
+        .len();
+    if file_length < METADATA_OFFSET_SIZE as u64 {
+        bail!("The executable is too small to contain a package");
+    }
 
     // Read the offset of the metadata at the end of the file
     file.seek(SeekFrom::End(-(METADATA_OFFSET_SIZE as i64)))
-        .expect("Failed to seek to metadata offset");
+        .context("Failed to seek to metadata offset")?;
     let mut offset_buf = [0; METADATA_OFFSET_SIZE];
     file.read_exact(&mut offset_buf)
-        .expect("Failed to read metadata offset");
+        .context("Failed to read metadata offset")?;
     let metadata_offset: u64 = String::from_utf8_lossy(&offset_buf)
         .trim()
         .parse()
-        .expect("Failed to parse metadata offset");
+        .context("Failed to parse metadata offset")?;
 
-    // Calculate the length of the metadata
-    let metadata_length = file.metadata().expect("Failed to get file metadata").len()
-        - metadata_offset
-        - METADATA_OFFSET_SIZE as u64;
+    // Calculate the length of the metadata, without overflowing when the offset is corrupt
+    let metadata_length = metadata_offset
+        .checked_add(METADATA_OFFSET_SIZE as u64)
+        .and_then(|metadata_end| file_length.checked_sub(metadata_end))
+        .ok_or_else(|| anyhow!("The metadata offset is out of bounds"))?;
 
     // Read the metadata
     file.seek(SeekFrom::Start(metadata_offset))
-        .expect("Failed to seek to metadata");
+        .context("Failed to seek to metadata")?;
     let mut metadata_buf = vec![0; metadata_length as usize];
     file.read_exact(&mut metadata_buf)
-        .expect("Failed to read metadata");
+        .context("Failed to read metadata")?;
 
     let metadata: Vec<MetadataEntry> =
-        serde_json::from_slice(&metadata_buf).expect("Failed to deserialize metadata");
+        serde_json::from_slice(&metadata_buf).context("Failed to deserialize metadata")?;
 
     let mut files = HashMap::new();
     let mut manifest = None;
 
     for entry in metadata {
+        if entry.offset.saturating_add(entry.size as u64) > metadata_offset {
+            bail!("File {} is out of bounds", entry.name);
+        }
         file.seek(SeekFrom::Start(entry.offset))
-            .expect("Failed to seek to file data");
+            .context("Failed to seek to file data")?;
         let mut data = vec![0; entry.size];
         file.read_exact(&mut data)
-            .expect("Failed to read file data");
+            .context("Failed to read file data")?;
 
         if entry.name == "manifest" {
-            manifest = Some(serde_json::from_slice(&data).expect("Failed to deserialize manifest"));
+            manifest =
+                Some(serde_json::from_slice(&data).context("Failed to deserialize manifest")?);
         } else {
             files.insert(entry.name, data);
         }
     }
 
-    let manifest = manifest.expect("Manifest not found in executable");
+    let manifest = manifest.ok_or_else(|| anyhow!("Manifest not found in executable"))?;
 
-    SetupPackage { manifest, files }
+    Ok(SetupPackage { manifest, files })
 }
 
 impl SetupPackage {
@@ -87,12 +123,50 @@ impl SetupPackage {
         self.get_file(&filename)
     }
 
+    /// The application icon and its file name, if one was bundled
+    pub fn get_icon(&self) -> Option<(String, Vec<u8>)> {
+        let filename = self.manifest.icon.as_ref()?;
+        let data = self.files.get(filename)?;
+        Some((filename.clone(), data.clone()))
+    }
+
+    /// The WebView2 install mode, packages created before modes were recorded embed the bootstrapper
+    pub fn webview2_mode(&self) -> Webview2Bundle {
+        self.manifest
+            .webview2
+            .as_ref()
+            .map_or(Webview2Bundle::EmbedBootstrapper, |webview2| webview2.mode)
+    }
+
     pub fn get_webview2(&self) -> Option<Vec<u8>> {
-        self.files.get(WEBVIEW2_EVERGREEN_EXE).cloned()
+        self.files.get(&self.webview2_filename()).cloned()
     }
 
     pub fn webview2_filename(&self) -> String {
-        WEBVIEW2_EVERGREEN_EXE.to_string()
+        match &self.manifest.webview2 {
+            Some(webview2) if webview2.mode == Webview2Bundle::OfflineInstaller => {
+                webview2.arch.offline_installer_exe().to_string()
+            }
+            _ => WEBVIEW2_EVERGREEN_EXE.to_string(),
+        }
+    }
+
+    /// The bundled installer of a prerequisite, if it's not downloaded at install time
+    pub fn get_prerequisite(&self, prerequisite: &Prerequisite) -> Option<Vec<u8>> {
+        let payload = prerequisite.payload.as_ref()?;
+        self.files.get(payload).cloned()
+    }
+
+    /// The files of the fixed version runtime, named relative to the runtime directory
+    pub fn get_webview2_fixed_runtime(&self) -> Vec<(String, Vec<u8>)> {
+        let prefix = format!("{}/", WEBVIEW2_FIXED_RUNTIME_DIR);
+        self.files
+            .iter()
+            .filter_map(|(name, data)| {
+                let relative_name = name.strip_prefix(&prefix)?;
+                Some((relative_name.to_string(), data.clone()))
+            })
+            .collect()
     }
 }
 
@@ -118,6 +192,7 @@ mod tests {
             version: "1.0.0".to_string(),
             identifier: "com.example.testapp".to_string(),
             application: "test.exe".to_string(),
+            ..Default::default()
         };
 
         packager.add_manifest(&manifest);
@@ -150,6 +225,97 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extract_package_webview2() {
+        let mut packager = ExePackager::new(vec![0; 1000]);
+        packager.add_file(
+            "MicrosoftEdgeWebView2RuntimeInstallerARM64.exe",
+            b"offline installer".to_vec(),
+        );
+        packager.add_file("WebView2Runtime/msedgewebview2.exe", b"runtime".to_vec());
+
+        let manifest = SetupManifest {
+            name: "TestApp".to_string(),
+            application: "test.exe".to_string(),
+            webview2: Some(webview2::Webview2Install {
+                mode: Webview2Bundle::OfflineInstaller,
+                silent: true,
+                arch: webview2::Webview2Arch::Arm64,
+                fixed_runtime_path: None,
+                min_version: Some("120.0.2210.91".to_string()),
+            }),
+            ..Default::default()
+        };
+        packager.add_manifest(&manifest);
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output_path = temp_dir.path().join("output.exe");
+        packager.package(&output_path);
+
+        let setup_package = extract_package(&output_path);
+        assert_eq!(
+            setup_package.webview2_mode(),
+            Webview2Bundle::OfflineInstaller
+        );
+        assert_eq!(
+            setup_package.webview2_filename(),
+            "MicrosoftEdgeWebView2RuntimeInstallerARM64.exe"
+        );
+        assert_eq!(
+            setup_package.get_webview2(),
+            Some(b"offline installer".to_vec())
+        );
+        assert_eq!(
+            setup_package.get_webview2_fixed_runtime(),
+            vec![("msedgewebview2.exe".to_string(), b"runtime".to_vec())]
+        );
+        assert_eq!(
+            setup_package.manifest.webview2.unwrap().min_version,
+            Some("120.0.2210.91".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_package_prerequisites() {
+        let bundled: Prerequisite = serde_json::from_value(serde_json::json!({
+            "name": "driver",
+            "path": "drivers/driver.msi",
+            "payload": "prerequisites/driver/driver.msi"
+        }))
+        .unwrap();
+        let downloaded: Prerequisite = serde_json::from_value(serde_json::json!({
+            "name": "vc-redist-x64",
+            "url": "https://aka.ms/vs/17/release/vc_redist.x64.exe"
+        }))
+        .unwrap();
+
+        let mut packager = ExePackager::new(vec![0; 1000]);
+        packager.add_file("prerequisites/driver/driver.msi", b"driver".to_vec());
+        let manifest = SetupManifest {
+            name: "TestApp".to_string(),
+            application: "test.exe".to_string(),
+            prerequisites: vec![bundled, downloaded],
+            ..Default::default()
+        };
+        packager.add_manifest(&manifest);
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output_path = temp_dir.path().join("output.exe");
+        packager.package(&output_path);
+
+        // The prerequisites are extracted in order, without the bundler's paths
+        let setup_package = extract_package(&output_path);
+        let prerequisites = &setup_package.manifest.prerequisites;
+        assert_eq!(prerequisites.len(), 2);
+        assert_eq!(prerequisites[0].name, "driver");
+        assert_eq!(prerequisites[0].path, None);
+        assert_eq!(
+            setup_package.get_prerequisite(&prerequisites[0]),
+            Some(b"driver".to_vec())
+        );
+        assert_eq!(setup_package.get_prerequisite(&prerequisites[1]), None);
+    }
+
     #[test]
     fn test_extract_package_no_manifest() {
         let exe_data = vec![0; 1000]; // Mock executable data
@@ -193,4 +359,45 @@ mod tests {
         // Check that the extraction fails because the executable is empty
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_try_extract_package_corrupt() {
+        let mut packager = ExePackager::new(vec![0; 1000]);
+        packager.add_file("test.exe", vec![1; 1000]);
+        packager.add_manifest(&SetupManifest {
+            application: "test.exe".to_string(),
+            ..Default::default()
+        });
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output_path = temp_dir.path().join("output.exe");
+        packager.package(&output_path);
+        assert!(try_extract_package(&output_path).is_ok());
+
+        // A truncated download loses the metadata at the end
+        let data = std::fs::read(&output_path).unwrap();
+        std::fs::write(&output_path, &data[..data.len() - 100]).unwrap();
+        assert!(try_extract_package(&output_path).is_err());
+
+        std::fs::write(&output_path, b"short").unwrap();
+        assert!(try_extract_package(&output_path).is_err());
+    }
+
+    #[test]
+    fn test_try_extract_package_offset_out_of_bounds() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output_path = temp_dir.path().join("output.exe");
+
+        // The largest offset that fits, and one past the end of the file
+        for metadata_offset in ["9".repeat(METADATA_OFFSET_SIZE), "2000".to_string()] {
+            let mut data = vec![0; 1000];
+            data.extend(
+                format!("{:0>width$}", metadata_offset, width = METADATA_OFFSET_SIZE).as_bytes(),
+            );
+            std::fs::write(&output_path, &data).unwrap();
+
+            let error = try_extract_package(&output_path).unwrap_err();
+            assert_eq!(error.to_string(), "The metadata offset is out of bounds");
+        }
+    }
 }
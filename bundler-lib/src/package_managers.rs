@@ -0,0 +1,255 @@
+pub mod chocolatey;
+pub mod scoop;
+pub mod winget;
+
+use crate::exe_packager::SetupManifest;
+use crate::release::ReleaseMetadata;
+use crate::scope::{InstallScope, Scope};
+
+/// Release information shared by the package manager manifest generators.
+///
+/// Combines the setup manifest, the Tauri bundle config and the release metadata
+/// of the built setup executable.
+#[derive(Debug)]
+pub struct PackageInfo<'a> {
+    pub manifest: &'a SetupManifest,
+    pub release: &'a ReleaseMetadata,
+    pub publisher: String,
+    pub homepage: Option<String>,
+    pub license: Option<String>,
+    pub short_description: Option<String>,
+    pub description: Option<String>,
+    pub copyright: Option<String>,
+    /// Download link for the setup executable
+    pub installer_url: String,
+}
+
+impl<'a> PackageInfo<'a> {
+    /// Collects the package information from the Tauri config.
+    ///
+    /// The installer URL template may contain the `{name}`, `{identifier}`, `{version}`,
+    /// `{arch}` and `{file}` placeholders, for example:
+    /// `https://github.com/user/app/releases/download/v{version}/{file}`
+    pub fn new(
+        manifest: &'a SetupManifest,
+        release: &'a ReleaseMetadata,
+        tauri_conf: &tauri::Config,
+        installer_url_template: &str,
+    ) -> Self {
+        let bundle = &tauri_conf.bundle;
+
+        // Same fallback as the Tauri bundler, the second part of the identifier
+        let publisher = bundle.publisher.clone().unwrap_or_else(|| {
+            manifest
+                .identifier
+                .split('.')
+                .nth(1)
+                .unwrap_or(&manifest.identifier)
+                .to_string()
+        });
+
+        Self {
+            manifest,
+            release,
+            publisher,
+            homepage: bundle.homepage.clone(),
+            license: bundle.license.clone(),
+            short_description: bundle.short_description.clone(),
+            description: bundle.long_description.clone(),
+            copyright: bundle.copyright.clone(),
+            installer_url: expand_installer_url(installer_url_template, release),
+        }
+    }
+}
+
+/// Expands the placeholders in an installer URL template
+pub fn expand_installer_url(template: &str, release: &ReleaseMetadata) -> String {
+    template
+        .replace("{name}", &release.manifest.name)
+        .replace("{identifier}", &release.manifest.identifier)
+        .replace("{version}", &release.manifest.version)
+        .replace("{arch}", &release.arch)
+        .replace("{file}", &release.file.name)
+}
+
+/// The scopes the application can be installed in, from `installScope`
+pub(crate) fn install_scopes(install_scope: InstallScope) -> &'static [Scope] {
+    match install_scope {
+        InstallScope::PerUser => &[Scope::PerUser],
+        InstallScope::PerMachine => &[Scope::PerMachine],
+        InstallScope::Both => &[Scope::PerUser, Scope::PerMachine],
+    }
+}
+
+/// PowerShell path of the uninstall registry key written by the installer for the scope
+pub(crate) fn uninstall_registry_key(identifier: &str, scope: Scope) -> String {
+    let hive = match scope {
+        Scope::PerUser => "HKCU:",
+        Scope::PerMachine => "HKLM:",
+    };
+    format!(
+        "{}\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\{}",
+        hive, identifier
+    )
+}
+
+/// PowerShell expression for the uninstall registry key of the installation, which is looked
+/// up in both hives when the application can be installed in either scope
+pub(crate) fn uninstall_registry_key_expr(manifest: &SetupManifest) -> String {
+    let keys = install_scopes(manifest.install_scope)
+        .iter()
+        .map(|scope| powershell_str(&uninstall_registry_key(&manifest.identifier, *scope)))
+        .collect::<Vec<_>>();
+    match keys.as_slice() {
+        [key] => key.clone(),
+        keys => format!(
+            "@({}) | Where-Object {{ Test-Path $_ }} | Select-Object -First 1",
+            keys.join(", ")
+        ),
+    }
+}
+
+/// Formats a string as a single quoted PowerShell string literal
+pub(crate) fn powershell_str(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::release::{ReleaseFile, ReleaseManifest, RELEASE_METADATA_VERSION};
+    use serde_json::json;
+
+    pub(crate) fn test_manifest() -> SetupManifest {
+        SetupManifest {
+            name: "test-app".to_string(),
+            title: "Test App".to_string(),
+            version: "1.2.3".to_string(),
+            identifier: "com.example.test-app".to_string(),
+            application: "test-app.exe".to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn test_release() -> ReleaseMetadata {
+        ReleaseMetadata {
+            schema_version: RELEASE_METADATA_VERSION,
+            manifest: ReleaseManifest {
+                name: "test-app".to_string(),
+                identifier: "com.example.test-app".to_string(),
+                version: "1.2.3".to_string(),
+            },
+            file: ReleaseFile {
+                name: "test-app-setup.exe".to_string(),
+                size: 5,
+                sha256: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                    .to_string(),
+            },
+            arch: "x64".to_string(),
+            prerequisites: vec![],
+        }
+    }
+
+    pub(crate) fn test_tauri_config() -> tauri::Config {
+        serde_json::from_value(json!({
+            "productName": "test-app",
+            "version": "1.2.3",
+            "identifier": "com.example.test-app",
+            "bundle": {
+                "publisher": "Example Corp",
+                "homepage": "https://example.com/test-app",
+                "license": "MIT",
+                "copyright": "Copyright (c) Example Corp",
+                "shortDescription": "A test application",
+                "longDescription": "A test application: used to test the package manager manifests"
+            }
+        }))
+        .expect("Failed to parse tauri config")
+    }
+
+    pub(crate) const TEST_INSTALLER_URL: &str =
+        "https://example.com/releases/download/v{version}/{file}";
+
+    #[test]
+    fn test_package_info_new() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+
+        assert_eq!(info.publisher, "Example Corp");
+        assert_eq!(
+            info.homepage.as_deref(),
+            Some("https://example.com/test-app")
+        );
+        assert_eq!(info.license.as_deref(), Some("MIT"));
+        assert_eq!(
+            info.short_description.as_deref(),
+            Some("A test application")
+        );
+        assert_eq!(
+            info.installer_url,
+            "https://example.com/releases/download/v1.2.3/test-app-setup.exe"
+        );
+    }
+
+    #[test]
+    fn test_package_info_default_publisher() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let tauri_conf: tauri::Config = serde_json::from_value(json!({
+            "identifier": "com.example.test-app",
+        }))
+        .expect("Failed to parse tauri config");
+
+        let info = PackageInfo::new(&manifest, &release, &tauri_conf, TEST_INSTALLER_URL);
+        assert_eq!(info.publisher, "example");
+        assert_eq!(info.license, None);
+    }
+
+    #[test]
+    fn test_powershell_str() {
+        assert_eq!(powershell_str("it's"), "'it''s'");
+        assert_eq!(
+            uninstall_registry_key("com.example.test-app", Scope::PerUser),
+            "HKCU:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\com.example.test-app"
+        );
+        assert_eq!(
+            uninstall_registry_key("com.example.test-app", Scope::PerMachine),
+            "HKLM:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\com.example.test-app"
+        );
+    }
+
+    #[test]
+    fn test_uninstall_registry_key_expr() {
+        let mut manifest = test_manifest();
+        assert_eq!(
+            uninstall_registry_key_expr(&manifest),
+            "'HKCU:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\com.example.test-app'"
+        );
+
+        manifest.install_scope = InstallScope::Both;
+        assert_eq!(
+            uninstall_registry_key_expr(&manifest),
+            "@('HKCU:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\com.example.test-app', \
+             'HKLM:\\Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\com.example.test-app') \
+             | Where-Object { Test-Path $_ } | Select-Object -First 1"
+        );
+    }
+
+    #[test]
+    fn test_expand_installer_url() {
+        let url = expand_installer_url(
+            "https://example.com/{identifier}/{name}-{version}-{arch}/{file}",
+            &test_release(),
+        );
+        assert_eq!(
+            url,
+            "https://example.com/com.example.test-app/test-app-1.2.3-x64/test-app-setup.exe"
+        );
+    }
+}
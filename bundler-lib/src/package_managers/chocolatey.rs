@@ -0,0 +1,278 @@
+use crate::package_managers::{powershell_str, uninstall_registry_key_expr, PackageInfo};
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Arguments passed to `setup.exe` for silent installs
+const SILENT_ARGS: &str = "--silent";
+
+/// Arguments passed to the application for silent uninstalls
+const UNINSTALL_SILENT_ARGS: &str = "--uninstall --silent";
+
+/// A Chocolatey package, the `.nuspec` and the install/uninstall scripts
+#[derive(Debug)]
+pub struct ChocolateyPackage {
+    pub id: String,
+    pub nuspec: String,
+    pub install_script: String,
+    pub uninstall_script: String,
+}
+
+impl ChocolateyPackage {
+    /// Generates the package, using the lowercase product name as the package id.
+    ///
+    /// The setup executable is downloaded from the installer URL at install time.
+    pub fn generate(info: &PackageInfo) -> Self {
+        let id = info.manifest.name.to_lowercase();
+        let description = info
+            .description
+            .as_deref()
+            .or(info.short_description.as_deref())
+            .unwrap_or(&info.manifest.title);
+
+        let mut nuspec = String::new();
+        writeln!(nuspec, r#"<?xml version="1.0" encoding="utf-8"?>"#).unwrap();
+        writeln!(
+            nuspec,
+            "<!-- Created with the Tauri Windows Installer bundler -->"
+        )
+        .unwrap();
+        writeln!(
+            nuspec,
+            r#"<package xmlns="http://schemas.microsoft.com/packaging/2015/06/nuspec.xsd">"#
+        )
+        .unwrap();
+        writeln!(nuspec, "  <metadata>").unwrap();
+        let elements = [
+            ("id", Some(id.as_str())),
+            ("version", Some(info.manifest.version.as_str())),
+            ("title", Some(info.manifest.title.as_str())),
+            ("authors", Some(info.publisher.as_str())),
+            ("projectUrl", info.homepage.as_deref()),
+            ("copyright", info.copyright.as_deref()),
+            ("tags", Some(id.as_str())),
+            ("summary", info.short_description.as_deref()),
+            ("description", Some(description)),
+        ];
+        for (element, value) in elements {
+            if let Some(value) = value {
+                writeln!(nuspec, "    <{0}>{1}</{0}>", element, xml_escape(value)).unwrap();
+            }
+        }
+        writeln!(nuspec, "  </metadata>").unwrap();
+        writeln!(nuspec, "  <files>").unwrap();
+        writeln!(nuspec, r#"    <file src="tools\**" target="tools" />"#).unwrap();
+        writeln!(nuspec, "  </files>").unwrap();
+        writeln!(nuspec, "</package>").unwrap();
+
+        let mut install_script = String::new();
+        writeln!(install_script, "$ErrorActionPreference = 'Stop'").unwrap();
+        writeln!(install_script).unwrap();
+        writeln!(install_script, "$packageArgs = @{{").unwrap();
+        writeln!(
+            install_script,
+            "  packageName    = $env:ChocolateyPackageName"
+        )
+        .unwrap();
+        writeln!(install_script, "  fileType       = 'exe'").unwrap();
+        writeln!(
+            install_script,
+            "  url64bit       = {}",
+            powershell_str(&info.installer_url)
+        )
+        .unwrap();
+        writeln!(
+            install_script,
+            "  checksum64     = {}",
+            powershell_str(&info.release.file.sha256)
+        )
+        .unwrap();
+        writeln!(install_script, "  checksumType64 = 'sha256'").unwrap();
+        writeln!(
+            install_script,
+            "  silentArgs     = {}",
+            powershell_str(SILENT_ARGS)
+        )
+        .unwrap();
+        writeln!(install_script, "  validExitCodes = @(0, 3010)").unwrap();
+        writeln!(install_script, "}}").unwrap();
+        writeln!(install_script).unwrap();
+        writeln!(install_script, "Install-ChocolateyPackage @packageArgs").unwrap();
+
+        let mut uninstall_script = String::new();
+        writeln!(uninstall_script, "$ErrorActionPreference = 'Stop'").unwrap();
+        writeln!(uninstall_script).unwrap();
+        writeln!(
+            uninstall_script,
+            "$key = {}",
+            uninstall_registry_key_expr(info.manifest)
+        )
+        .unwrap();
+        writeln!(
+            uninstall_script,
+            "$installLocation = (Get-ItemProperty -Path $key).InstallLocation"
+        )
+        .unwrap();
+        writeln!(uninstall_script).unwrap();
+        writeln!(uninstall_script, "$packageArgs = @{{").unwrap();
+        writeln!(
+            uninstall_script,
+            "  packageName    = $env:ChocolateyPackageName"
+        )
+        .unwrap();
+        writeln!(uninstall_script, "  fileType       = 'exe'").unwrap();
+        writeln!(
+            uninstall_script,
+            "  file           = Join-Path $installLocation {}",
+            powershell_str(&info.manifest.application)
+        )
+        .unwrap();
+        writeln!(
+            uninstall_script,
+            "  silentArgs     = {}",
+            powershell_str(UNINSTALL_SILENT_ARGS)
+        )
+        .unwrap();
+        writeln!(uninstall_script, "  validExitCodes = @(0)").unwrap();
+        writeln!(uninstall_script, "}}").unwrap();
+        writeln!(uninstall_script).unwrap();
+        writeln!(uninstall_script, "Uninstall-ChocolateyPackage @packageArgs").unwrap();
+
+        Self {
+            id,
+            nuspec,
+            install_script,
+            uninstall_script,
+        }
+    }
+
+    /// Writes the package sources to the output directory, returning the written paths
+    pub fn write(&self, output_dir: &Path) -> Vec<PathBuf> {
+        let tools_dir = output_dir.join("tools");
+        fs::create_dir_all(&tools_dir).expect("Failed to create chocolatey output directory");
+
+        let files = [
+            (output_dir.join(format!("{}.nuspec", self.id)), &self.nuspec),
+            (
+                tools_dir.join("chocolateyInstall.ps1"),
+                &self.install_script,
+            ),
+            (
+                tools_dir.join("chocolateyUninstall.ps1"),
+                &self.uninstall_script,
+            ),
+        ];
+
+        files
+            .into_iter()
+            .map(|(path, contents)| {
+                fs::write(&path, contents).expect("Failed to write chocolatey package file");
+                path
+            })
+            .collect()
+    }
+}
+
+/// Escapes the XML special characters in element text
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package_managers::tests::{
+        test_manifest, test_release, test_tauri_config, TEST_INSTALLER_URL,
+    };
+    use crate::scope::InstallScope;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_generate_golden() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let package = ChocolateyPackage::generate(&info);
+
+        assert_eq!(package.id, "test-app");
+        assert_eq!(
+            package.nuspec,
+            include_str!("../../testdata/chocolatey/test-app.nuspec")
+        );
+        assert_eq!(
+            package.install_script,
+            include_str!("../../testdata/chocolatey/tools/chocolateyInstall.ps1")
+        );
+        assert_eq!(
+            package.uninstall_script,
+            include_str!("../../testdata/chocolatey/tools/chocolateyUninstall.ps1")
+        );
+    }
+
+    #[test]
+    fn test_generate_per_machine() {
+        let mut manifest = test_manifest();
+        manifest.install_scope = InstallScope::PerMachine;
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let package = ChocolateyPackage::generate(&info);
+
+        assert_eq!(
+            package.uninstall_script,
+            include_str!("../../testdata/chocolatey/per-machine/tools/chocolateyUninstall.ps1")
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let package = ChocolateyPackage::generate(&info);
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let paths = package.write(temp_dir.path());
+
+        assert_eq!(
+            paths,
+            vec![
+                temp_dir.path().join("test-app.nuspec"),
+                temp_dir.path().join("tools").join("chocolateyInstall.ps1"),
+                temp_dir
+                    .path()
+                    .join("tools")
+                    .join("chocolateyUninstall.ps1"),
+            ]
+        );
+        assert_eq!(fs::read_to_string(&paths[0]).unwrap(), package.nuspec);
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("Tom & Jerry's <app>"),
+            "Tom &amp; Jerry&apos;s &lt;app&gt;"
+        );
+    }
+}
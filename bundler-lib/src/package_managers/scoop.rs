@@ -0,0 +1,175 @@
+use crate::package_managers::{powershell_str, uninstall_registry_key_expr, PackageInfo};
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A Scoop app manifest
+#[derive(Serialize, Debug)]
+pub struct ScoopManifest {
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    pub architecture: ScoopArchitecture,
+    pub installer: ScoopInstaller,
+    pub uninstaller: ScoopUninstaller,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScoopArchitecture {
+    #[serde(rename = "64bit")]
+    pub x64: ScoopDownload,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScoopDownload {
+    pub url: String,
+    pub hash: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScoopInstaller {
+    pub file: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScoopUninstaller {
+    pub script: Vec<String>,
+}
+
+impl ScoopManifest {
+    /// Generates the manifest, the downloaded setup executable is run with `--silent`
+    /// and the application is uninstalled with `--uninstall --silent`
+    pub fn generate(info: &PackageInfo) -> Self {
+        let script = vec![
+            format!("$key = {}", uninstall_registry_key_expr(info.manifest)),
+            format!(
+                "$app = Join-Path (Get-ItemProperty -Path $key).InstallLocation {}",
+                powershell_str(&info.manifest.application)
+            ),
+            "Start-Process -Wait -FilePath $app -ArgumentList '--uninstall', '--silent'"
+                .to_string(),
+        ];
+
+        Self {
+            version: info.manifest.version.clone(),
+            description: info.short_description.clone(),
+            homepage: info.homepage.clone(),
+            license: info.license.clone(),
+            architecture: ScoopArchitecture {
+                x64: ScoopDownload {
+                    url: info.installer_url.clone(),
+                    hash: info.release.file.sha256.clone(),
+                },
+            },
+            installer: ScoopInstaller {
+                file: info.release.file.name.clone(),
+                args: vec!["--silent".to_string()],
+            },
+            uninstaller: ScoopUninstaller { script },
+        }
+    }
+
+    /// Serializes the manifest as pretty printed JSON
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("Failed to serialize manifest");
+        json.push('\n');
+        json
+    }
+
+    /// Writes the manifest as `{name}.json` to the output directory, returning the written path
+    pub fn write(&self, output_dir: &Path, name: &str) -> PathBuf {
+        fs::create_dir_all(output_dir).expect("Failed to create scoop output directory");
+
+        let path = output_dir.join(format!("{}.json", name.to_lowercase()));
+        fs::write(&path, self.to_json()).expect("Failed to write scoop manifest");
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package_managers::tests::{
+        test_manifest, test_release, test_tauri_config, TEST_INSTALLER_URL,
+    };
+    use crate::scope::InstallScope;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_generate_golden() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let scoop = ScoopManifest::generate(&info);
+
+        assert_eq!(
+            scoop.to_json(),
+            include_str!("../../testdata/scoop/test-app.json")
+        );
+    }
+
+    #[test]
+    fn test_generate_per_machine() {
+        let mut manifest = test_manifest();
+        manifest.install_scope = InstallScope::PerMachine;
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let scoop = ScoopManifest::generate(&info);
+
+        assert_eq!(
+            scoop.to_json(),
+            include_str!("../../testdata/scoop/per-machine/test-app.json")
+        );
+    }
+
+    #[test]
+    fn test_generate_without_optional_fields() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let tauri_conf: tauri::Config =
+            serde_json::from_value(serde_json::json!({ "identifier": "com.example.test-app" }))
+                .expect("Failed to parse tauri config");
+        let info = PackageInfo::new(&manifest, &release, &tauri_conf, TEST_INSTALLER_URL);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&ScoopManifest::generate(&info).to_json()).unwrap();
+        assert!(json.get("description").is_none());
+        assert!(json.get("license").is_none());
+        assert_eq!(json["version"], "1.2.3");
+    }
+
+    #[test]
+    fn test_write() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let scoop = ScoopManifest::generate(&info);
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let path = scoop.write(temp_dir.path(), "Test-App");
+
+        assert_eq!(path, temp_dir.path().join("test-app.json"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), scoop.to_json());
+    }
+}
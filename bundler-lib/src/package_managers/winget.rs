@@ -0,0 +1,335 @@
+use crate::package_managers::{install_scopes, PackageInfo};
+use crate::scope::{InstallScope, Scope};
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the winget manifest schema the manifests are written for
+pub const WINGET_MANIFEST_VERSION: &str = "1.6.0";
+
+/// Locale used for the default locale manifest
+pub const WINGET_DEFAULT_LOCALE: &str = "en-US";
+
+/// Switch passed to `setup.exe` for silent installs
+const SILENT_SWITCH: &str = "--silent";
+
+/// The multi-file winget manifest for a single package version
+#[derive(Debug)]
+pub struct WingetManifests {
+    pub identifier: String,
+    pub version: String,
+    pub version_manifest: String,
+    pub installer: String,
+    pub default_locale: String,
+}
+
+impl WingetManifests {
+    /// Generates the version, installer and default locale manifests.
+    ///
+    /// The tauri `bundle.license` and `bundle.shortDescription` are required by winget.
+    pub fn generate(info: &PackageInfo) -> Self {
+        let identifier = &info.manifest.identifier;
+        let version = &info.manifest.version;
+
+        let version_manifest = manifest(
+            "version",
+            &[
+                ("PackageIdentifier", Some(identifier.as_str())),
+                ("PackageVersion", Some(version.as_str())),
+                ("DefaultLocale", Some(WINGET_DEFAULT_LOCALE)),
+            ],
+            "",
+        );
+
+        let mut installers = String::new();
+        writeln!(installers, "InstallModes:").unwrap();
+        writeln!(installers, "  - interactive").unwrap();
+        writeln!(installers, "  - silent").unwrap();
+        writeln!(installers, "  - silentWithProgress").unwrap();
+        writeln!(installers, "InstallerSwitches:").unwrap();
+        writeln!(installers, "  Silent: {}", yaml_scalar(SILENT_SWITCH)).unwrap();
+        writeln!(
+            installers,
+            "  SilentWithProgress: {}",
+            yaml_scalar(SILENT_SWITCH)
+        )
+        .unwrap();
+        writeln!(installers, "UpgradeBehavior: install").unwrap();
+        // The uninstall entry is written using the identifier as the key name
+        writeln!(installers, "ProductCode: {}", yaml_scalar(identifier)).unwrap();
+        writeln!(installers, "Installers:").unwrap();
+        // An installer for each scope, when the scope is chosen with a setup flag
+        for scope in install_scopes(info.manifest.install_scope) {
+            writeln!(
+                installers,
+                "  - Architecture: {}",
+                yaml_scalar(&info.release.arch)
+            )
+            .unwrap();
+            writeln!(installers, "    Scope: {}", winget_scope(*scope)).unwrap();
+            if info.manifest.install_scope == InstallScope::Both {
+                writeln!(installers, "    InstallerSwitches:").unwrap();
+                writeln!(installers, "      Custom: {}", yaml_scalar(scope.flag())).unwrap();
+            }
+            writeln!(
+                installers,
+                "    InstallerUrl: {}",
+                yaml_scalar(&info.installer_url)
+            )
+            .unwrap();
+            writeln!(
+                installers,
+                "    InstallerSha256: {}",
+                info.release.file.sha256.to_uppercase()
+            )
+            .unwrap();
+        }
+        let installer_manifest = manifest(
+            "installer",
+            &[
+                ("PackageIdentifier", Some(identifier.as_str())),
+                ("PackageVersion", Some(version.as_str())),
+                ("InstallerType", Some("exe")),
+            ],
+            &installers,
+        );
+
+        let license = info
+            .license
+            .as_deref()
+            .expect("bundle.license must be set in the tauri config for winget manifests");
+        let short_description = info
+            .short_description
+            .as_deref()
+            .expect("bundle.shortDescription must be set in the tauri config for winget manifests");
+        let default_locale_manifest = manifest(
+            "defaultLocale",
+            &[
+                ("PackageIdentifier", Some(identifier.as_str())),
+                ("PackageVersion", Some(version.as_str())),
+                ("PackageLocale", Some(WINGET_DEFAULT_LOCALE)),
+                ("Publisher", Some(info.publisher.as_str())),
+                ("PackageName", Some(info.manifest.title.as_str())),
+                ("PackageUrl", info.homepage.as_deref()),
+                ("License", Some(license)),
+                ("Copyright", info.copyright.as_deref()),
+                ("ShortDescription", Some(short_description)),
+                ("Description", info.description.as_deref()),
+            ],
+            "",
+        );
+
+        Self {
+            identifier: identifier.clone(),
+            version: version.clone(),
+            installer: installer_manifest,
+            default_locale: default_locale_manifest,
+            version_manifest,
+        }
+    }
+
+    /// Writes the manifests to the output directory, returning the written paths
+    pub fn write(&self, output_dir: &Path) -> Vec<PathBuf> {
+        fs::create_dir_all(output_dir).expect("Failed to create winget output directory");
+
+        let files = [
+            (format!("{}.yaml", self.identifier), &self.version_manifest),
+            (
+                format!("{}.installer.yaml", self.identifier),
+                &self.installer,
+            ),
+            (
+                format!("{}.locale.{}.yaml", self.identifier, WINGET_DEFAULT_LOCALE),
+                &self.default_locale,
+            ),
+        ];
+
+        files
+            .into_iter()
+            .map(|(filename, contents)| {
+                let path = output_dir.join(filename);
+                fs::write(&path, contents).expect("Failed to write winget manifest");
+                path
+            })
+            .collect()
+    }
+}
+
+/// The winget name of an install scope
+fn winget_scope(scope: Scope) -> &'static str {
+    match scope {
+        Scope::PerUser => "user",
+        Scope::PerMachine => "machine",
+    }
+}
+
+/// Formats a single winget manifest, `body` is inserted after the fields
+fn manifest(manifest_type: &str, fields: &[(&str, Option<&str>)], body: &str) -> String {
+    let mut output = String::new();
+    writeln!(output, "# Created with the Tauri Windows Installer bundler").unwrap();
+    writeln!(
+        output,
+        "# yaml-language-server: $schema=https://aka.ms/winget-manifest.{}.{}.schema.json",
+        manifest_type, WINGET_MANIFEST_VERSION
+    )
+    .unwrap();
+    writeln!(output).unwrap();
+
+    for (key, value) in fields {
+        if let Some(value) = value {
+            writeln!(output, "{}: {}", key, yaml_scalar(value)).unwrap();
+        }
+    }
+    output.push_str(body);
+
+    writeln!(output, "ManifestType: {}", manifest_type).unwrap();
+    writeln!(output, "ManifestVersion: {}", WINGET_MANIFEST_VERSION).unwrap();
+    output
+}
+
+/// Formats a string as a YAML scalar, quoting it only when required
+pub(crate) fn yaml_scalar(value: &str) -> String {
+    let reserved = matches!(
+        value.to_lowercase().as_str(),
+        "true" | "false" | "yes" | "no" | "on" | "off" | "null" | "~"
+    );
+    let needs_quotes = value.is_empty()
+        || reserved
+        || value.trim() != value
+        || value.parse::<f64>().is_ok()
+        || value.starts_with(|c| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        || value.ends_with(':')
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.contains(['\n', '\t', '\\', '"']);
+
+    if !needs_quotes {
+        return value.to_string();
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package_managers::tests::{
+        test_manifest, test_release, test_tauri_config, TEST_INSTALLER_URL,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_generate_golden() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let manifests = WingetManifests::generate(&info);
+
+        assert_eq!(
+            manifests.version_manifest,
+            include_str!("../../testdata/winget/com.example.test-app.yaml")
+        );
+        assert_eq!(
+            manifests.installer,
+            include_str!("../../testdata/winget/com.example.test-app.installer.yaml")
+        );
+        assert_eq!(
+            manifests.default_locale,
+            include_str!("../../testdata/winget/com.example.test-app.locale.en-US.yaml")
+        );
+    }
+
+    #[test]
+    fn test_generate_install_scope() {
+        let mut manifest = test_manifest();
+        let release = test_release();
+        let tauri_conf = test_tauri_config();
+
+        manifest.install_scope = InstallScope::PerMachine;
+        let info = PackageInfo::new(&manifest, &release, &tauri_conf, TEST_INSTALLER_URL);
+        assert_eq!(
+            WingetManifests::generate(&info).installer,
+            include_str!("../../testdata/winget/per-machine/com.example.test-app.installer.yaml")
+        );
+
+        manifest.install_scope = InstallScope::Both;
+        let info = PackageInfo::new(&manifest, &release, &tauri_conf, TEST_INSTALLER_URL);
+        assert_eq!(
+            WingetManifests::generate(&info).installer,
+            include_str!("../../testdata/winget/both/com.example.test-app.installer.yaml")
+        );
+    }
+
+    #[test]
+    fn test_generate_missing_license() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let mut info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        info.license = None;
+
+        let result = std::panic::catch_unwind(|| WingetManifests::generate(&info));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let manifest = test_manifest();
+        let release = test_release();
+        let info = PackageInfo::new(
+            &manifest,
+            &release,
+            &test_tauri_config(),
+            TEST_INSTALLER_URL,
+        );
+        let manifests = WingetManifests::generate(&info);
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let paths = manifests.write(temp_dir.path());
+
+        let filenames: Vec<_> = paths
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            filenames,
+            vec![
+                "com.example.test-app.yaml",
+                "com.example.test-app.installer.yaml",
+                "com.example.test-app.locale.en-US.yaml"
+            ]
+        );
+        assert_eq!(fs::read_to_string(&paths[1]).unwrap(), manifests.installer);
+    }
+
+    #[test]
+    fn test_yaml_scalar() {
+        assert_eq!(yaml_scalar("demo-app"), "demo-app");
+        assert_eq!(yaml_scalar("1.2.3"), "1.2.3");
+        assert_eq!(
+            yaml_scalar("https://example.com/a"),
+            "https://example.com/a"
+        );
+        assert_eq!(yaml_scalar("1.0"), "\"1.0\"");
+        assert_eq!(yaml_scalar("yes"), "\"yes\"");
+        assert_eq!(yaml_scalar(""), "\"\"");
+        assert_eq!(yaml_scalar("--silent"), "\"--silent\"");
+        assert_eq!(yaml_scalar("a: b"), "\"a: b\"");
+        assert_eq!(yaml_scalar("say \"hi\"\n"), "\"say \\\"hi\\\"\\n\"");
+    }
+}
@@ -1,22 +1,116 @@
+use crate::prerequisite::Prerequisite;
+use crate::scope::InstallScope;
+use crate::shortcut::Shortcuts;
+use crate::webview2::{Webview2Arch, Webview2Install};
+
 use std::fs;
+use std::path::PathBuf;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use tauri::utils::config::WebviewInstallMode;
 
 #[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct TauriWindowsInstaller {
     pub icon: Option<String>,
     pub webview2: Webview2Config,
+    /// Prerequisites installed in order before the application
+    #[serde(default)]
+    pub prerequisites: Vec<Prerequisite>,
+    /// Who the application can be installed for, per-user by default
+    #[serde(default)]
+    pub install_scope: InstallScope,
+    /// Installation directory template, e.g. `{LocalAppData}\Programs\{productName}`
+    pub install_dir: Option<String>,
+    /// Start menu and desktop shortcuts, only a Start menu shortcut by default
+    #[serde(default)]
+    pub shortcuts: Shortcuts,
 }
 
 #[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct Webview2Config {
     pub bundle: Option<Webview2Bundle>,
+    /// Run the bootstrapper or offline installer without its UI, defaults to true
+    pub silent: Option<bool>,
+    /// Fixed version runtime `.cab` or folder, relative to the tauri config
+    pub fixed_runtime_path: Option<PathBuf>,
+    /// Architecture of the offline installer, detected from the application when not specified
+    pub arch: Option<Webview2Arch>,
+    /// URL to download the offline installer from, instead of Microsoft's URL for the architecture
+    pub offline_installer_url: Option<String>,
+    /// Expected SHA-256 of the downloaded bootstrapper or offline installer
+    pub sha256: Option<String>,
+    /// Minimum WebView2 runtime version, an older installed runtime is upgraded
+    pub min_version: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
+/// How WebView2 is installed, matching Tauri's `webviewInstallMode`
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
 pub enum Webview2Bundle {
-    Evergreen,
+    /// Don't install WebView2
+    Skip,
+    /// Download and run the bootstrapper at install time
+    DownloadBootstrapper,
+    /// Bundle the bootstrapper in the setup executable
+    #[serde(alias = "evergreen")]
+    EmbedBootstrapper,
+    /// Bundle the offline standalone installer in the setup executable
+    OfflineInstaller,
+    /// Bundle a fixed version runtime, installed next to the application
+    FixedRuntime,
+}
+
+impl TauriWindowsInstaller {
+    /// Resolves how WebView2 is installed from the plugin config, or Tauri's
+    /// `bundle.windows.webviewInstallMode` when no bundle is specified
+    pub fn webview2_install(&self, tauri_conf: &tauri::Config) -> Webview2Install {
+        if let Some(mode) = self.webview2.bundle {
+            return Webview2Install {
+                mode,
+                silent: self.webview2.silent.unwrap_or(true),
+                arch: self.webview2.arch.unwrap_or_default(),
+                fixed_runtime_path: self.webview2.fixed_runtime_path.clone(),
+                min_version: self.webview2.min_version.clone(),
+            };
+        }
+
+        let windows = &tauri_conf.bundle.windows;
+        if let Some(path) = &windows.webview_fixed_runtime_path {
+            return Webview2Install {
+                mode: Webview2Bundle::FixedRuntime,
+                silent: true,
+                arch: self.webview2.arch.unwrap_or_default(),
+                fixed_runtime_path: Some(path.clone()),
+                min_version: self.webview2.min_version.clone(),
+            };
+        }
+
+        let (mode, silent, fixed_runtime_path) = match &windows.webview_install_mode {
+            WebviewInstallMode::Skip => (Webview2Bundle::Skip, true, None),
+            WebviewInstallMode::DownloadBootstrapper { silent } => {
+                (Webview2Bundle::DownloadBootstrapper, *silent, None)
+            }
+            WebviewInstallMode::EmbedBootstrapper { silent } => {
+                (Webview2Bundle::EmbedBootstrapper, *silent, None)
+            }
+            WebviewInstallMode::OfflineInstaller { silent } => {
+                (Webview2Bundle::OfflineInstaller, *silent, None)
+            }
+            WebviewInstallMode::FixedRuntime { path } => {
+                (Webview2Bundle::FixedRuntime, true, Some(path.clone()))
+            }
+        };
+
+        Webview2Install {
+            mode,
+            silent,
+            arch: self.webview2.arch.unwrap_or_default(),
+            fixed_runtime_path,
+            min_version: self.webview2.min_version.clone(),
+        }
+    }
 }
 
 pub fn load_tauri_config(tauri_conf_path: &str) -> (tauri::Config, TauriWindowsInstaller) {
@@ -58,7 +152,10 @@ mod tests {
                     "icon": "icons/icon.ico",
                     "webview2": {
                         "bundle": "evergreen"
-                    }
+                    },
+                    "prerequisites": [
+                        { "name": "vc-redist-x64", "url": "https://aka.ms/vs/17/release/vc_redist.x64.exe" }
+                    ]
                 }
             }
         });
@@ -75,8 +172,10 @@ mod tests {
         assert_eq!(plugin_config.icon, Some("icons/icon.ico".to_string()));
         assert_eq!(
             plugin_config.webview2.bundle,
-            Some(Webview2Bundle::Evergreen)
+            Some(Webview2Bundle::EmbedBootstrapper)
         );
+        assert_eq!(plugin_config.prerequisites.len(), 1);
+        assert_eq!(plugin_config.prerequisites[0].name, "vc-redist-x64");
     }
 
     #[test]
@@ -104,4 +203,111 @@ mod tests {
         assert_eq!(plugin_config.icon, None);
         assert_eq!(plugin_config.webview2.bundle, None);
     }
+
+    fn tauri_config(bundle: serde_json::Value, plugin: serde_json::Value) -> tauri::Config {
+        serde_json::from_value(json!({
+            "identifier": "com.example.test",
+            "bundle": bundle,
+            "plugins": { "tauri-windows-installer": plugin }
+        }))
+        .expect("Failed to parse tauri config")
+    }
+
+    fn plugin_config(tauri_conf: &tauri::Config) -> TauriWindowsInstaller {
+        serde_json::from_value(tauri_conf.plugins.0["tauri-windows-installer"].clone())
+            .expect("Failed to parse plugin config")
+    }
+
+    #[test]
+    fn test_webview2_install_from_plugin_config() {
+        let tauri_conf = tauri_config(
+            json!({ "windows": { "webviewInstallMode": { "type": "skip" } } }),
+            json!({ "webview2": { "bundle": "offlineInstaller", "silent": false, "arch": "arm64" } }),
+        );
+        let install = plugin_config(&tauri_conf).webview2_install(&tauri_conf);
+
+        assert_eq!(install.mode, Webview2Bundle::OfflineInstaller);
+        assert!(!install.silent);
+        assert_eq!(install.arch, Webview2Arch::Arm64);
+        assert_eq!(install.fixed_runtime_path, None);
+    }
+
+    #[test]
+    fn test_webview2_install_from_tauri_config() {
+        let tauri_conf = tauri_config(
+            json!({ "windows": { "webviewInstallMode": { "type": "embedBootstrapper", "silent": false } } }),
+            json!({ "webview2": {} }),
+        );
+        let install = plugin_config(&tauri_conf).webview2_install(&tauri_conf);
+        assert_eq!(install.mode, Webview2Bundle::EmbedBootstrapper);
+        assert!(!install.silent);
+
+        let tauri_conf = tauri_config(
+            json!({ "windows": { "webviewInstallMode": { "type": "fixedRuntime", "path": "./Runtime/" } } }),
+            json!({ "webview2": {} }),
+        );
+        let install = plugin_config(&tauri_conf).webview2_install(&tauri_conf);
+        assert_eq!(install.mode, Webview2Bundle::FixedRuntime);
+        assert_eq!(
+            install.fixed_runtime_path,
+            Some(PathBuf::from("./Runtime/"))
+        );
+    }
+
+    #[test]
+    fn test_webview2_install_default() {
+        // Tauri defaults to downloading the bootstrapper silently
+        let tauri_conf = tauri_config(json!({}), json!({ "webview2": {} }));
+        let install = plugin_config(&tauri_conf).webview2_install(&tauri_conf);
+
+        assert_eq!(install.mode, Webview2Bundle::DownloadBootstrapper);
+        assert!(install.silent);
+        assert_eq!(install.min_version, None);
+    }
+
+    #[test]
+    fn test_webview2_install_min_version() {
+        // The minimum version applies to the mode from Tauri's config too
+        let tauri_conf = tauri_config(
+            json!({ "windows": { "webviewInstallMode": { "type": "offlineInstaller" } } }),
+            json!({ "webview2": { "minVersion": "120.0.2210.91" } }),
+        );
+        let install = plugin_config(&tauri_conf).webview2_install(&tauri_conf);
+
+        assert_eq!(install.mode, Webview2Bundle::OfflineInstaller);
+        assert_eq!(install.min_version, Some("120.0.2210.91".to_string()));
+    }
+
+    #[test]
+    fn test_install_scope() {
+        let tauri_conf = tauri_config(json!({}), json!({ "webview2": {} }));
+        assert_eq!(
+            plugin_config(&tauri_conf).install_scope,
+            InstallScope::PerUser
+        );
+
+        let tauri_conf = tauri_config(
+            json!({}),
+            json!({ "webview2": {}, "installScope": "perMachine" }),
+        );
+        assert_eq!(
+            plugin_config(&tauri_conf).install_scope,
+            InstallScope::PerMachine
+        );
+    }
+
+    #[test]
+    fn test_install_dir() {
+        let tauri_conf = tauri_config(json!({}), json!({ "webview2": {} }));
+        assert_eq!(plugin_config(&tauri_conf).install_dir, None);
+
+        let tauri_conf = tauri_config(
+            json!({}),
+            json!({ "webview2": {}, "installDir": "{LocalAppData}\\Programs\\{productName}" }),
+        );
+        assert_eq!(
+            plugin_config(&tauri_conf).install_dir.as_deref(),
+            Some("{LocalAppData}\\Programs\\{productName}")
+        );
+    }
 }
@@ -0,0 +1,171 @@
+use crate::exe_packager::SetupManifest;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Marker file placed next to the application executable in portable builds.
+///
+/// Contains the setup manifest as JSON, re-exported as `twi_uninstall::PORTABLE_MARKER`.
+pub const PORTABLE_MARKER: &str = ".twi-portable";
+
+/// A packager for creating a portable ZIP of the application, as an alternative to setup.exe
+///
+/// Example usage:
+/// ```no_run
+/// use bundler::exe_packager::SetupManifest;
+/// use bundler::portable::PortablePackager;
+/// use std::path::Path;
+///
+/// let mut packager = PortablePackager::new();
+/// packager.add_file("myapp.exe", std::fs::read("myapp.exe").unwrap());
+///
+/// let manifest = SetupManifest {
+///     name: "MyApp".to_string(),
+///     title: "My App".to_string(),
+///     version: "1.0.0".to_string(),
+///     identifier: "com.example.myapp".to_string(),
+///     application: "myapp.exe".to_string(),
+///     ..Default::default()
+/// };
+/// packager.package(&manifest, Path::new("MyApp-portable.zip"));
+/// ```
+#[derive(Default)]
+pub struct PortablePackager {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl PortablePackager {
+    /// Creates a new, empty PortablePackager
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file to the ZIP, by its `/` separated path relative to the application
+    pub fn add_file(&mut self, filename: &str, file_data: Vec<u8>) {
+        self.files.insert(filename.to_string(), file_data);
+    }
+
+    /// Writes the ZIP with the added files and the portable marker
+    pub fn package(self, manifest: &SetupManifest, output_path: &Path) {
+        let output_file = File::create(output_path).expect("Failed to create output file");
+        let mut zip = ZipWriter::new(output_file);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        for (filename, file_data) in self.files {
+            zip.start_file(filename, options)
+                .expect("Failed to add file to zip");
+            zip.write_all(&file_data)
+                .expect("Failed to write file data to zip");
+        }
+
+        let marker_data =
+            serde_json::to_vec_pretty(manifest).expect("Failed to serialize manifest");
+        zip.start_file(PORTABLE_MARKER, options)
+            .expect("Failed to add portable marker to zip");
+        zip.write_all(&marker_data)
+            .expect("Failed to write portable marker to zip");
+
+        zip.finish().expect("Failed to finish zip");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webview2::load_fixed_runtime;
+    use std::io::Read;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_package() {
+        let mut packager = PortablePackager::new();
+        packager.add_file("test.exe", b"Hello, world!".to_vec());
+        packager.add_file("icon.png", b"icon".to_vec());
+
+        let manifest = SetupManifest {
+            name: "TestApp".to_string(),
+            title: "Test App".to_string(),
+            version: "1.0.0".to_string(),
+            identifier: "com.example.testapp".to_string(),
+            application: "test.exe".to_string(),
+            ..Default::default()
+        };
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output_path = temp_dir.path().join("TestApp-portable.zip");
+        packager.package(&manifest, &output_path);
+
+        let mut archive =
+            ZipArchive::new(File::open(&output_path).unwrap()).expect("Failed to open zip");
+        let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names, vec![PORTABLE_MARKER, "icon.png", "test.exe"]);
+
+        let mut app_data = Vec::new();
+        archive
+            .by_name("test.exe")
+            .unwrap()
+            .read_to_end(&mut app_data)
+            .unwrap();
+        assert_eq!(app_data, b"Hello, world!");
+
+        let mut marker_data = Vec::new();
+        archive
+            .by_name(PORTABLE_MARKER)
+            .unwrap()
+            .read_to_end(&mut marker_data)
+            .unwrap();
+        let marker: SetupManifest = serde_json::from_slice(&marker_data).unwrap();
+        assert_eq!(marker.identifier, "com.example.testapp");
+        assert_eq!(marker.application, "test.exe");
+    }
+
+    #[test]
+    fn test_package_fixed_runtime() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let runtime_path = temp_dir.path().join("runtime");
+        std::fs::create_dir_all(runtime_path.join("EBWebView")).unwrap();
+        std::fs::write(runtime_path.join("msedgewebview2.exe"), b"webview").unwrap();
+        std::fs::write(runtime_path.join("EBWebView/msedge.dll"), b"dll").unwrap();
+
+        let mut packager = PortablePackager::new();
+        packager.add_file("test.exe", b"Hello, world!".to_vec());
+        for (filename, data) in load_fixed_runtime(&runtime_path) {
+            packager.add_file(&filename, data);
+        }
+
+        let manifest = SetupManifest {
+            application: "test.exe".to_string(),
+            ..Default::default()
+        };
+        let output_path = temp_dir.path().join("TestApp-portable.zip");
+        packager.package(&manifest, &output_path);
+
+        let mut archive =
+            ZipArchive::new(File::open(&output_path).unwrap()).expect("Failed to open zip");
+        let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                PORTABLE_MARKER,
+                "WebView2Runtime/EBWebView/msedge.dll",
+                "WebView2Runtime/msedgewebview2.exe",
+                "test.exe"
+            ]
+        );
+
+        let mut runtime_data = Vec::new();
+        archive
+            .by_name("WebView2Runtime/msedgewebview2.exe")
+            .unwrap()
+            .read_to_end(&mut runtime_data)
+            .unwrap();
+        assert_eq!(runtime_data, b"webview");
+    }
+}
@@ -0,0 +1,558 @@
+use crate::version::meets_min_version;
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Directory bundled prerequisite installers are packaged under
+pub const PREREQUISITES_DIR: &str = "prerequisites";
+
+/// A prerequisite installed before the application, e.g. the Visual C++ redistributable.
+///
+/// Declared in the plugin config and recorded in the setup manifest:
+/// ```json
+/// "prerequisites": [
+///   {
+///     "name": "vc-redist-x64",
+///     "title": "Microsoft Visual C++ Redistributable",
+///     "detect": {
+///       "type": "registry",
+///       "key": "HKLM\\SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64",
+///       "value": "Version",
+///       "minVersion": "14.40"
+///     },
+///     "url": "https://aka.ms/vs/17/release/vc_redist.x64.exe",
+///     "args": ["/install", "/quiet", "/norestart"],
+///     "exitCodes": [0, 1638]
+///   }
+/// ]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Prerequisite {
+    /// Unique name, used for the packaged installer and in the release metadata
+    pub name: String,
+    /// Display name, defaults to the name
+    #[serde(default)]
+    pub title: Option<String>,
+    /// How to detect the prerequisite is already installed, the installer is always run without one
+    #[serde(default)]
+    pub detect: Option<DetectionRule>,
+    /// Installer bundled in the setup executable, relative to the tauri config, only used by the bundler
+    #[serde(default, skip_serializing)]
+    pub path: Option<PathBuf>,
+    /// Filename of the bundled installer, set by the bundler
+    #[serde(default)]
+    pub payload: Option<String>,
+    /// URL to download the installer from at install time, when it isn't bundled
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Arguments passed to the installer, after `/i <installer>` for `.msi` packages
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Exit codes meaning the installer succeeded
+    #[serde(default = "default_exit_codes")]
+    pub exit_codes: Vec<i32>,
+    /// Exit codes meaning the installer succeeded, but a reboot is required
+    #[serde(default = "default_reboot_exit_codes")]
+    pub reboot_exit_codes: Vec<i32>,
+}
+
+fn default_exit_codes() -> Vec<i32> {
+    vec![0]
+}
+
+/// `ERROR_SUCCESS_REBOOT_REQUIRED` and `ERROR_SUCCESS_REBOOT_INITIATED`
+fn default_reboot_exit_codes() -> Vec<i32> {
+    vec![3010, 1641]
+}
+
+/// How to detect a prerequisite is already installed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DetectionRule {
+    /// A registry key exists, or a value in it, e.g. `HKLM\SOFTWARE\Vendor\Product`
+    #[serde(rename_all = "camelCase")]
+    Registry {
+        key: String,
+        value: Option<String>,
+        /// The value must equal this
+        equals: Option<String>,
+        /// The value is a version, which must be at least this
+        min_version: Option<String>,
+    },
+    /// A file exists, environment variables like `%SystemRoot%` are expanded
+    #[serde(rename_all = "camelCase")]
+    File {
+        path: String,
+        /// The file version must be at least this
+        min_version: Option<String>,
+    },
+    /// A Windows Installer product is installed, e.g. `{GUID}`
+    #[serde(rename_all = "camelCase")]
+    ProductCode { product_code: String },
+}
+
+/// Registry hive of a registry key
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RegistryRoot {
+    LocalMachine,
+    CurrentUser,
+    ClassesRoot,
+}
+
+/// Queries the system for detection rules, implemented by the installer
+pub trait SystemProbe {
+    /// Whether the registry key exists
+    fn registry_key_exists(&self, root: RegistryRoot, key: &str) -> bool;
+    /// A registry value, with numbers formatted in decimal
+    fn registry_value(&self, root: RegistryRoot, key: &str, value: &str) -> Option<String>;
+    /// The value of an environment variable
+    fn env_var(&self, name: &str) -> Option<String>;
+    /// Whether the file exists
+    fn file_exists(&self, path: &Path) -> bool;
+    /// The file version from the file's version resource
+    fn file_version(&self, path: &Path) -> Option<String>;
+    /// Whether the Windows Installer product is installed
+    fn product_installed(&self, product_code: &str) -> bool;
+}
+
+/// Result of a successful prerequisite install
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallOutcome {
+    Installed,
+    RebootRequired,
+}
+
+impl Prerequisite {
+    /// Display name of the prerequisite
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Checks the prerequisite can be installed, before it's bundled
+    pub fn validate(&self) -> Result<()> {
+        let valid_name = !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            && !self.name.chars().all(|c| c == '.');
+        if !valid_name {
+            bail!(
+                "Invalid prerequisite name '{}', use letters, numbers, '-', '_' and '.'",
+                self.name
+            );
+        }
+
+        if self.path.is_none() && self.url.is_none() {
+            bail!("Prerequisite '{}' needs a path or url", self.name);
+        }
+
+        if let Some(DetectionRule::Registry { key, .. }) = &self.detect {
+            parse_registry_key(key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Name of the bundled installer in the setup executable, for an installer filename
+    pub fn payload_name(&self, filename: &str) -> String {
+        format!("{}/{}/{}", PREREQUISITES_DIR, self.name, filename)
+    }
+
+    /// Filename the installer is written to before it's run
+    pub fn installer_filename(&self) -> String {
+        let name = self
+            .payload
+            .as_deref()
+            .or(self.url.as_deref())
+            .and_then(|name| name.split(['?', '#']).next())
+            .and_then(|name| name.rsplit('/').next())
+            .filter(|name| name.contains('.'));
+        match name {
+            Some(name) => name.to_string(),
+            None => format!("{}.exe", self.name),
+        }
+    }
+
+    /// Program and arguments to run the installer with, `.msi` packages are run by `msiexec`
+    pub fn command(&self, installer_path: &Path) -> (PathBuf, Vec<String>) {
+        let is_msi = installer_path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("msi"));
+        if is_msi {
+            let mut args = vec!["/i".to_string(), installer_path.display().to_string()];
+            args.extend(self.args.iter().cloned());
+            (PathBuf::from("msiexec.exe"), args)
+        } else {
+            (installer_path.to_path_buf(), self.args.clone())
+        }
+    }
+
+    /// Whether the prerequisite is already installed
+    pub fn is_installed(&self, system: &dyn SystemProbe) -> bool {
+        self.detect
+            .as_ref()
+            .is_some_and(|detect| detect.is_satisfied(system))
+    }
+
+    /// Maps the installer's exit code to the install outcome
+    pub fn check_exit_code(&self, exit_code: i32) -> Result<InstallOutcome> {
+        if self.exit_codes.contains(&exit_code) {
+            Ok(InstallOutcome::Installed)
+        } else if self.reboot_exit_codes.contains(&exit_code) {
+            Ok(InstallOutcome::RebootRequired)
+        } else {
+            Err(anyhow!(
+                "{} installer failed with exit code: {}",
+                self.title(),
+                exit_code
+            ))
+        }
+    }
+}
+
+impl DetectionRule {
+    /// Whether the rule matches the system
+    pub fn is_satisfied(&self, system: &dyn SystemProbe) -> bool {
+        match self {
+            DetectionRule::Registry {
+                key,
+                value,
+                equals,
+                min_version,
+            } => {
+                let Ok((root, key)) = parse_registry_key(key) else {
+                    return false;
+                };
+                let Some(value) = value else {
+                    return system.registry_key_exists(root, key);
+                };
+                let Some(data) = system.registry_value(root, key, value) else {
+                    return false;
+                };
+
+                equals.iter().all(|equals| data == *equals)
+                    && min_version.iter().all(|min_version| {
+                        // Versions are often stored with a prefix, e.g. `v14.40.33810.00`
+                        meets_min_version(data.trim_start_matches(['v', 'V']), min_version)
+                    })
+            }
+            DetectionRule::File { path, min_version } => {
+                let path = PathBuf::from(expand_env_vars(path, |name| system.env_var(name)));
+                if !system.file_exists(&path) {
+                    return false;
+                }
+
+                min_version.iter().all(|min_version| {
+                    system
+                        .file_version(&path)
+                        .is_some_and(|version| meets_min_version(&version, min_version))
+                })
+            }
+            DetectionRule::ProductCode { product_code } => system.product_installed(product_code),
+        }
+    }
+}
+
+/// Splits a registry key into its root and the path below it, e.g. `HKLM\SOFTWARE\Vendor`
+pub fn parse_registry_key(key: &str) -> Result<(RegistryRoot, &str)> {
+    let (root, path) = key.split_once('\\').unwrap_or((key, ""));
+    let root = match root.to_ascii_uppercase().as_str() {
+        "HKLM" | "HKEY_LOCAL_MACHINE" => RegistryRoot::LocalMachine,
+        "HKCU" | "HKEY_CURRENT_USER" => RegistryRoot::CurrentUser,
+        "HKCR" | "HKEY_CLASSES_ROOT" => RegistryRoot::ClassesRoot,
+        _ => bail!("Unknown registry root in '{}', use HKLM, HKCU or HKCR", key),
+    };
+    Ok((root, path.trim_matches('\\')))
+}
+
+/// Expands `%NAME%` environment variables, unknown variables are left as they are
+pub fn expand_env_vars(input: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::new();
+    let mut rest = input;
+    while let Some(start) = rest.find('%') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('%') {
+            Some(end) => {
+                let name = &after[..end];
+                match lookup(name) {
+                    Some(value) if !name.is_empty() => output.push_str(&value),
+                    _ => output.push_str(&rest[start..start + end + 2]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// A system with the given registry values, environment variables, files and products
+    #[derive(Default)]
+    struct FakeSystem {
+        registry: HashMap<(String, String), String>,
+        env: HashMap<String, String>,
+        files: HashMap<PathBuf, Option<String>>,
+        products: Vec<String>,
+    }
+
+    impl SystemProbe for FakeSystem {
+        fn registry_key_exists(&self, root: RegistryRoot, key: &str) -> bool {
+            assert_eq!(root, RegistryRoot::LocalMachine);
+            self.registry.keys().any(|(k, _)| k == key)
+        }
+
+        fn registry_value(&self, root: RegistryRoot, key: &str, value: &str) -> Option<String> {
+            assert_eq!(root, RegistryRoot::LocalMachine);
+            self.registry
+                .get(&(key.to_string(), value.to_string()))
+                .cloned()
+        }
+
+        fn env_var(&self, name: &str) -> Option<String> {
+            self.env.get(name).cloned()
+        }
+
+        fn file_exists(&self, path: &Path) -> bool {
+            self.files.contains_key(path)
+        }
+
+        fn file_version(&self, path: &Path) -> Option<String> {
+            self.files.get(path).cloned().flatten()
+        }
+
+        fn product_installed(&self, product_code: &str) -> bool {
+            self.products.iter().any(|p| p == product_code)
+        }
+    }
+
+    fn vc_redist() -> Prerequisite {
+        serde_json::from_value(json!({
+            "name": "vc-redist-x64",
+            "title": "Microsoft Visual C++ Redistributable",
+            "detect": {
+                "type": "registry",
+                "key": "HKLM\\SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64",
+                "value": "Version",
+                "minVersion": "14.40"
+            },
+            "url": "https://aka.ms/vs/17/release/vc_redist.x64.exe",
+            "args": ["/install", "/quiet", "/norestart"],
+            "exitCodes": [0, 1638]
+        }))
+        .expect("Failed to parse prerequisite")
+    }
+
+    fn vc_redist_system(version: &str) -> FakeSystem {
+        let mut system = FakeSystem::default();
+        system.registry.insert(
+            (
+                "SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64".to_string(),
+                "Version".to_string(),
+            ),
+            version.to_string(),
+        );
+        system
+    }
+
+    #[test]
+    fn test_prerequisite_defaults() {
+        let prerequisite: Prerequisite =
+            serde_json::from_value(json!({ "name": "driver", "path": "driver.msi" })).unwrap();
+
+        assert_eq!(prerequisite.title(), "driver");
+        assert_eq!(prerequisite.exit_codes, vec![0]);
+        assert_eq!(prerequisite.reboot_exit_codes, vec![3010, 1641]);
+        assert!(prerequisite.validate().is_ok());
+
+        // Without a detection rule the installer is always run
+        assert!(!prerequisite.is_installed(&FakeSystem::default()));
+
+        // The bundler's path isn't recorded in the setup manifest
+        let manifest = serde_json::to_value(&prerequisite).unwrap();
+        assert!(manifest.get("path").is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(vc_redist().validate().is_ok());
+
+        let mut prerequisite = vc_redist();
+        prerequisite.name = "../vc redist".to_string();
+        assert!(prerequisite.validate().is_err());
+
+        for name in [".", ".."] {
+            let mut prerequisite = vc_redist();
+            prerequisite.name = name.to_string();
+            assert!(prerequisite.validate().is_err());
+        }
+
+        let mut prerequisite = vc_redist();
+        prerequisite.url = None;
+        assert!(prerequisite.validate().is_err());
+
+        let mut prerequisite = vc_redist();
+        prerequisite.detect = Some(DetectionRule::Registry {
+            key: "HKXX\\SOFTWARE".to_string(),
+            value: None,
+            equals: None,
+            min_version: None,
+        });
+        assert!(prerequisite.validate().is_err());
+    }
+
+    #[test]
+    fn test_detect_registry() {
+        let prerequisite = vc_redist();
+        assert!(prerequisite.is_installed(&vc_redist_system("v14.40.33810.00")));
+        assert!(prerequisite.is_installed(&vc_redist_system("v14.42.34433.00")));
+        assert!(!prerequisite.is_installed(&vc_redist_system("v14.29.30139.00")));
+        assert!(!prerequisite.is_installed(&FakeSystem::default()));
+
+        let key_only = DetectionRule::Registry {
+            key: "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64"
+                .to_string(),
+            value: None,
+            equals: None,
+            min_version: None,
+        };
+        assert!(key_only.is_satisfied(&vc_redist_system("v14.40.33810.00")));
+
+        let equals = DetectionRule::Registry {
+            key: "HKLM\\SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64".to_string(),
+            value: Some("Version".to_string()),
+            equals: Some("1".to_string()),
+            min_version: None,
+        };
+        assert!(equals.is_satisfied(&vc_redist_system("1")));
+        assert!(!equals.is_satisfied(&vc_redist_system("0")));
+    }
+
+    #[test]
+    fn test_detect_file() {
+        let mut system = FakeSystem::default();
+        system
+            .env
+            .insert("SystemRoot".to_string(), "C:\\Windows".to_string());
+        system.files.insert(
+            PathBuf::from("C:\\Windows\\System32\\drivers\\example.sys"),
+            Some("2.1.0.0".to_string()),
+        );
+
+        let rule = |min_version: Option<&str>| DetectionRule::File {
+            path: "%SystemRoot%\\System32\\drivers\\example.sys".to_string(),
+            min_version: min_version.map(str::to_string),
+        };
+        assert!(rule(None).is_satisfied(&system));
+        assert!(rule(Some("2.0")).is_satisfied(&system));
+        assert!(!rule(Some("2.2")).is_satisfied(&system));
+        assert!(!rule(None).is_satisfied(&FakeSystem::default()));
+    }
+
+    #[test]
+    fn test_detect_product_code() {
+        let mut system = FakeSystem::default();
+        system
+            .products
+            .push("{00000000-0000-0000-0000-000000000001}".to_string());
+
+        let rule: DetectionRule = serde_json::from_value(json!({
+            "type": "productCode",
+            "productCode": "{00000000-0000-0000-0000-000000000001}"
+        }))
+        .unwrap();
+        assert!(rule.is_satisfied(&system));
+        assert!(!rule.is_satisfied(&FakeSystem::default()));
+    }
+
+    #[test]
+    fn test_check_exit_code() {
+        let prerequisite = vc_redist();
+        assert_eq!(
+            prerequisite.check_exit_code(0).unwrap(),
+            InstallOutcome::Installed
+        );
+        assert_eq!(
+            prerequisite.check_exit_code(1638).unwrap(),
+            InstallOutcome::Installed
+        );
+        assert_eq!(
+            prerequisite.check_exit_code(3010).unwrap(),
+            InstallOutcome::RebootRequired
+        );
+        let error = prerequisite.check_exit_code(1603).unwrap_err();
+        assert!(error.to_string().contains("exit code: 1603"));
+    }
+
+    #[test]
+    fn test_command() {
+        let prerequisite = vc_redist();
+        let (program, args) = prerequisite.command(Path::new("C:\\Temp\\vc_redist.x64.exe"));
+        assert_eq!(program, PathBuf::from("C:\\Temp\\vc_redist.x64.exe"));
+        assert_eq!(args, vec!["/install", "/quiet", "/norestart"]);
+
+        let mut prerequisite = vc_redist();
+        prerequisite.args = vec!["/qn".to_string()];
+        let (program, args) = prerequisite.command(Path::new("C:\\Temp\\driver.MSI"));
+        assert_eq!(program, PathBuf::from("msiexec.exe"));
+        assert_eq!(args, vec!["/i", "C:\\Temp\\driver.MSI", "/qn"]);
+    }
+
+    #[test]
+    fn test_installer_filename() {
+        let mut prerequisite = vc_redist();
+        assert_eq!(prerequisite.installer_filename(), "vc_redist.x64.exe");
+
+        prerequisite.payload = Some(prerequisite.payload_name("driver.msi"));
+        assert_eq!(
+            prerequisite.payload.as_deref(),
+            Some("prerequisites/vc-redist-x64/driver.msi")
+        );
+        assert_eq!(prerequisite.installer_filename(), "driver.msi");
+
+        prerequisite.payload = None;
+        prerequisite.url = Some("https://go.microsoft.com/fwlink/?linkid=123".to_string());
+        assert_eq!(prerequisite.installer_filename(), "vc-redist-x64.exe");
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        let lookup = |name: &str| match name {
+            "SystemRoot" => Some("C:\\Windows".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand_env_vars("%SystemRoot%\\System32", lookup),
+            "C:\\Windows\\System32"
+        );
+        assert_eq!(expand_env_vars("%Unknown%\\a", lookup), "%Unknown%\\a");
+        assert_eq!(expand_env_vars("100%", lookup), "100%");
+        assert_eq!(expand_env_vars("%%", lookup), "%%");
+    }
+
+    #[test]
+    fn test_parse_registry_key() {
+        assert_eq!(
+            parse_registry_key("HKCU\\Software\\Example\\").unwrap(),
+            (RegistryRoot::CurrentUser, "Software\\Example")
+        );
+        assert_eq!(
+            parse_registry_key("hkey_classes_root\\.txt").unwrap(),
+            (RegistryRoot::ClassesRoot, ".txt")
+        );
+        assert!(parse_registry_key("Software\\Example").is_err());
+    }
+}
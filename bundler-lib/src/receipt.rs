@@ -0,0 +1,206 @@
+use crate::exe_packager::SetupManifest;
+use crate::hash::sha256_hex;
+use crate::prerequisite::RegistryRoot;
+use crate::scope::Scope;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the `install.json` schema, bumped on any breaking change to [`InstallReceipt`]
+pub const INSTALL_RECEIPT_VERSION: u32 = 1;
+
+/// Path of the install receipt, relative to the installation directory
+pub const INSTALL_RECEIPT_PATH: &str = ".twi/install.json";
+
+/// What the installer installed, written by the installer and read by the uninstaller.
+///
+/// Written as `.twi/install.json` in the installation directory, for example:
+/// ```json
+/// {
+///   "schemaVersion": 1,
+///   "manifest": { "name": "demo-app", "version": "0.1.0", ... },
+///   "installedAt": "2024-08-20T10:15:30Z",
+///   "installerVersion": "0.1.0",
+///   "scope": "perUser",
+///   "files": [
+///     {
+///       "path": "demo-app.exe",
+///       "size": 10584064,
+///       "sha256": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+///     }
+///   ],
+///   "shortcuts": [],
+///   "directories": [],
+///   "registry": [
+///     {
+///       "root": "CurrentUser",
+///       "key": "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\com.gussy.demo-app",
+///       "name": "DisplayVersion"
+///     }
+///   ],
+///   "prerequisites": ["webview2-evergreen"]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallReceipt {
+    /// Always [`INSTALL_RECEIPT_VERSION`] when written by this version of the installer
+    pub schema_version: u32,
+    /// The manifest of the installed package
+    pub manifest: SetupManifest,
+    /// When the application was installed, in RFC 3339 format
+    pub installed_at: String,
+    /// Version of the installer that installed the application
+    pub installer_version: String,
+    /// Who the application is installed for
+    #[serde(default)]
+    pub scope: Scope,
+    /// Files installed in the installation directory
+    pub files: Vec<ReceiptFile>,
+    /// Shortcuts created outside the installation directory
+    #[serde(default)]
+    pub shortcuts: Vec<PathBuf>,
+    /// Directories created outside the installation directory for the shortcuts, removed if empty
+    #[serde(default)]
+    pub directories: Vec<PathBuf>,
+    /// Registry values written
+    #[serde(default)]
+    pub registry: Vec<ReceiptRegistryValue>,
+    /// Names of the prerequisites setup installed, as in the manifest, excluding those already
+    /// installed. The WebView2 runtime is `webview2-evergreen`
+    #[serde(default)]
+    pub prerequisites: Vec<String>,
+}
+
+/// An installed file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReceiptFile {
+    /// Path relative to the installation directory, separated by `/`
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A written registry value
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReceiptRegistryValue {
+    pub root: RegistryRoot,
+    pub key: String,
+    pub name: String,
+}
+
+impl ReceiptFile {
+    /// Records a file by its relative path and contents
+    pub fn new(path: &str, data: &[u8]) -> Self {
+        ReceiptFile {
+            path: path.to_string(),
+            size: data.len() as u64,
+            sha256: sha256_hex(data),
+        }
+    }
+}
+
+impl InstallReceipt {
+    /// Path of the receipt in the installation directory
+    pub fn path(root_path: &Path) -> PathBuf {
+        root_path.join(INSTALL_RECEIPT_PATH)
+    }
+
+    /// Parses a receipt
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).context("Failed to parse install receipt")
+    }
+
+    /// Reads the receipt from the installation directory
+    pub fn load(root_path: &Path) -> Result<Self> {
+        let path = Self::path(root_path);
+        let data = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        Self::from_slice(&data)
+    }
+
+    /// The registry keys written, in the order they were first written
+    pub fn registry_keys(&self) -> Vec<(RegistryRoot, &str)> {
+        let mut keys: Vec<(RegistryRoot, &str)> = Vec::new();
+        for value in &self.registry {
+            let key = (value.root, value.key.as_str());
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt() -> InstallReceipt {
+        let value = |key: &str, name: &str| ReceiptRegistryValue {
+            root: RegistryRoot::CurrentUser,
+            key: key.to_string(),
+            name: name.to_string(),
+        };
+
+        InstallReceipt {
+            schema_version: INSTALL_RECEIPT_VERSION,
+            manifest: SetupManifest {
+                name: "demo-app".to_string(),
+                version: "0.1.0".to_string(),
+                identifier: "com.gussy.demo-app".to_string(),
+                ..Default::default()
+            },
+            installed_at: "2024-08-20T10:15:30Z".to_string(),
+            installer_version: "0.1.0".to_string(),
+            scope: Scope::PerUser,
+            files: vec![ReceiptFile::new("demo-app.exe", b"hello")],
+            shortcuts: Vec::new(),
+            directories: Vec::new(),
+            registry: vec![
+                value("Uninstall\\demo-app", "DisplayName"),
+                value("Uninstall\\demo-app", "DisplayVersion"),
+                value("Classes\\.demo", ""),
+            ],
+            prerequisites: vec!["webview2-evergreen".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let receipt = receipt();
+        let path = InstallReceipt::path(temp_dir.path());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, serde_json::to_vec_pretty(&receipt).unwrap()).unwrap();
+
+        assert_eq!(InstallReceipt::load(temp_dir.path()).unwrap(), receipt);
+        assert!(InstallReceipt::load(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_schema() {
+        let json = serde_json::to_value(receipt()).unwrap();
+        assert_eq!(json["schemaVersion"], 1);
+        assert_eq!(json["installedAt"], "2024-08-20T10:15:30Z");
+        assert_eq!(json["files"][0]["path"], "demo-app.exe");
+        assert_eq!(
+            json["files"][0]["sha256"],
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(json["registry"][0]["root"], "CurrentUser");
+        assert_eq!(json["manifest"]["identifier"], "com.gussy.demo-app");
+    }
+
+    #[test]
+    fn test_registry_keys() {
+        assert_eq!(
+            receipt().registry_keys(),
+            vec![
+                (RegistryRoot::CurrentUser, "Uninstall\\demo-app"),
+                (RegistryRoot::CurrentUser, "Classes\\.demo"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use crate::exe_packager::SetupManifest;
+use crate::hash::sha256_hex;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the `release.json` schema, bumped on any breaking change to [`ReleaseMetadata`]
+pub const RELEASE_METADATA_VERSION: u32 = 1;
+
+/// Filename of the release metadata written next to the setup executable
+pub const RELEASE_METADATA_FILE: &str = "release.json";
+
+/// Release metadata describing a packaged setup executable.
+///
+/// Written as `release.json` next to the setup executable, for example:
+/// ```json
+/// {
+///   "schemaVersion": 1,
+///   "manifest": {
+///     "name": "demo-app",
+///     "identifier": "com.gussy.demo-app",
+///     "version": "0.0.0"
+///   },
+///   "file": {
+///     "name": "demo-app-setup.exe",
+///     "size": 13002145,
+///     "sha256": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+///   },
+///   "arch": "x64",
+///   "prerequisites": ["webview2-evergreen"]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseMetadata {
+    /// Always [`RELEASE_METADATA_VERSION`] when written by this version of the bundler
+    pub schema_version: u32,
+    pub manifest: ReleaseManifest,
+    pub file: ReleaseFile,
+    /// Target architecture of the setup executable (e.g. `x64`)
+    pub arch: String,
+    /// Prerequisites bundled in to the setup executable
+    pub prerequisites: Vec<String>,
+}
+
+/// The subset of the [`SetupManifest`] recorded in the release metadata
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ReleaseManifest {
+    pub name: String,
+    pub identifier: String,
+    pub version: String,
+}
+
+/// The packaged setup executable
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ReleaseFile {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl ReleaseMetadata {
+    /// Creates the release metadata for a packaged setup executable
+    pub fn new(
+        manifest: &SetupManifest,
+        setup_path: &Path,
+        arch: &str,
+        prerequisites: Vec<String>,
+    ) -> Self {
+        let setup_data = fs::read(setup_path).expect("Failed to read setup executable");
+
+        ReleaseMetadata {
+            schema_version: RELEASE_METADATA_VERSION,
+            manifest: ReleaseManifest {
+                name: manifest.name.clone(),
+                identifier: manifest.identifier.clone(),
+                version: manifest.version.clone(),
+            },
+            file: ReleaseFile {
+                name: setup_path
+                    .file_name()
+                    .expect("Setup path has no filename")
+                    .to_string_lossy()
+                    .to_string(),
+                size: setup_data.len() as u64,
+                sha256: sha256_hex(&setup_data),
+            },
+            arch: arch.to_string(),
+            prerequisites,
+        }
+    }
+
+    /// Writes `{setup}.sha256` and `release.json` next to the setup executable,
+    /// returning the paths of both files
+    pub fn write(&self, setup_path: &Path) -> (PathBuf, PathBuf) {
+        let output_dir = setup_path.parent().unwrap_or(Path::new(""));
+
+        // Use the same format as `sha256sum` so it can be checked with `sha256sum -c`
+        let mut sha256_path = setup_path.as_os_str().to_owned();
+        sha256_path.push(".sha256");
+        let sha256_path = PathBuf::from(sha256_path);
+        let sha256_line = format!("{}  {}\n", self.file.sha256, self.file.name);
+        fs::write(&sha256_path, sha256_line).expect("Failed to write sha256 file");
+
+        let release_path = output_dir.join(RELEASE_METADATA_FILE);
+        let release_data =
+            serde_json::to_vec_pretty(self).expect("Failed to serialize release metadata");
+        fs::write(&release_path, release_data).expect("Failed to write release metadata");
+
+        (sha256_path, release_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn test_manifest() -> SetupManifest {
+        SetupManifest {
+            name: "TestApp".to_string(),
+            title: "Test App".to_string(),
+            version: "1.0.0".to_string(),
+            identifier: "com.example.testapp".to_string(),
+            application: "test.exe".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_release_metadata_new() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let setup_path = temp_dir.path().join("TestApp-setup.exe");
+        fs::write(&setup_path, b"hello").expect("Failed to write setup file");
+
+        let release = ReleaseMetadata::new(
+            &test_manifest(),
+            &setup_path,
+            "x64",
+            vec!["webview2-evergreen".to_string()],
+        );
+
+        assert_eq!(release.schema_version, RELEASE_METADATA_VERSION);
+        assert_eq!(release.manifest.name, "TestApp");
+        assert_eq!(release.manifest.identifier, "com.example.testapp");
+        assert_eq!(release.manifest.version, "1.0.0");
+        assert_eq!(release.file.name, "TestApp-setup.exe");
+        assert_eq!(release.file.size, 5);
+        assert_eq!(
+            release.file.sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(release.arch, "x64");
+        assert_eq!(release.prerequisites, vec!["webview2-evergreen"]);
+    }
+
+    #[test]
+    fn test_release_metadata_write() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let setup_path = temp_dir.path().join("TestApp-setup.exe");
+        fs::write(&setup_path, b"hello").expect("Failed to write setup file");
+
+        let release = ReleaseMetadata::new(&test_manifest(), &setup_path, "x64", vec![]);
+        let (sha256_path, release_path) = release.write(&setup_path);
+
+        assert_eq!(
+            sha256_path,
+            temp_dir.path().join("TestApp-setup.exe.sha256")
+        );
+        assert_eq!(
+            fs::read_to_string(&sha256_path).unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  TestApp-setup.exe\n"
+        );
+
+        assert_eq!(release_path, temp_dir.path().join(RELEASE_METADATA_FILE));
+        let written: ReleaseMetadata =
+            serde_json::from_slice(&fs::read(&release_path).unwrap()).unwrap();
+        assert_eq!(written, release);
+
+        // The JSON layout is part of the documented schema
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&release_path).unwrap())
+            .expect("Failed to parse release metadata");
+        assert_eq!(json["schemaVersion"], 1);
+        assert_eq!(json["manifest"]["identifier"], "com.example.testapp");
+        assert_eq!(json["file"]["size"], 5);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::exe_packager::SetupManifest;
+use crate::hash::sha256_hex;
+
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Version of the CycloneDX specification the SBOM is written for
+pub const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+
+/// Marker preceding the stub dependency list embedded in `setup.exe` at build time.
+///
+/// The marker is followed by the length of the JSON list as a little endian `u32`,
+/// then the JSON list of [`LockPackage`]. Must match `STUB_DEPENDENCIES_MAGIC` in `installer/build.rs`.
+pub const STUB_DEPENDENCIES_MAGIC: &[u8] = b"TWI-STUB-DEPENDENCIES:";
+
+/// A package entry from a `Cargo.lock` file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockPackage {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<LockPackage>,
+}
+
+/// Parses the packages from the contents of a `Cargo.lock` file
+pub fn parse_cargo_lock(contents: &str) -> Vec<LockPackage> {
+    let lock: CargoLock = toml::from_str(contents).expect("Failed to parse Cargo.lock");
+    lock.package
+}
+
+/// Finds the dependency list embedded in the setup stub, if it was built with one
+pub fn find_stub_dependencies(stub_data: &[u8]) -> Option<Vec<LockPackage>> {
+    let start = stub_data
+        .windows(STUB_DEPENDENCIES_MAGIC.len())
+        .position(|window| window == STUB_DEPENDENCIES_MAGIC)?
+        + STUB_DEPENDENCIES_MAGIC.len();
+
+    let length_bytes: [u8; 4] = stub_data.get(start..start + 4)?.try_into().ok()?;
+    let length = u32::from_le_bytes(length_bytes) as usize;
+    let json = stub_data.get(start + 4..start + 4 + length)?;
+
+    serde_json::from_slice(json).ok()
+}
+
+/// A CycloneDX JSON software bill of materials
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Bom {
+    pub bom_format: String,
+    pub spec_version: String,
+    pub serial_number: String,
+    pub version: u32,
+    pub metadata: BomMetadata,
+    pub components: Vec<Component>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BomMetadata {
+    pub timestamp: String,
+    pub component: Component,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Component {
+    #[serde(rename = "type")]
+    pub component_type: String,
+    #[serde(rename = "bom-ref")]
+    pub bom_ref: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<Hash>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<Property>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<Component>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Hash {
+    pub alg: String,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub value: String,
+}
+
+impl Component {
+    /// Creates a component for a file packaged in the setup executable
+    pub fn file(component_type: &str, name: &str, version: Option<&str>, data: &[u8]) -> Self {
+        Self {
+            component_type: component_type.to_string(),
+            bom_ref: format!("file:{}", name),
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            purl: None,
+            hashes: vec![Hash {
+                alg: "SHA-256".to_string(),
+                content: sha256_hex(data),
+            }],
+            properties: vec![Property {
+                name: "twi:size".to_string(),
+                value: data.len().to_string(),
+            }],
+            components: Vec::new(),
+        }
+    }
+
+    /// Adds the packages from a `Cargo.lock` as nested library components
+    pub fn with_dependencies(mut self, packages: &[LockPackage]) -> Self {
+        self.components
+            .extend(packages.iter().map(Component::from_lock_package));
+        self
+    }
+
+    fn from_lock_package(package: &LockPackage) -> Self {
+        let purl = format!("pkg:cargo/{}@{}", package.name, package.version);
+        Self {
+            component_type: "library".to_string(),
+            bom_ref: purl.clone(),
+            name: package.name.clone(),
+            version: Some(package.version.clone()),
+            purl: Some(purl),
+            // The checksum is the SHA-256 of the crate file, only present for registry packages
+            hashes: package
+                .checksum
+                .iter()
+                .map(|checksum| Hash {
+                    alg: "SHA-256".to_string(),
+                    content: checksum.clone(),
+                })
+                .collect(),
+            properties: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+}
+
+/// Collects the components of a setup executable in to a CycloneDX SBOM
+#[derive(Default)]
+pub struct SbomBuilder {
+    components: Vec<Component>,
+}
+
+impl SbomBuilder {
+    /// Creates a new, empty SbomBuilder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a component packaged in the setup executable
+    pub fn add_component(&mut self, component: Component) {
+        self.components.push(component);
+    }
+
+    /// Creates the SBOM, described by the setup manifest and the packaged setup executable
+    pub fn build(self, manifest: &SetupManifest, setup_filename: &str, setup_data: &[u8]) -> Bom {
+        let mut setup = Component::file(
+            "application",
+            setup_filename,
+            Some(&manifest.version),
+            setup_data,
+        );
+        setup.properties.push(Property {
+            name: "twi:identifier".to_string(),
+            value: manifest.identifier.clone(),
+        });
+
+        Bom {
+            bom_format: "CycloneDX".to_string(),
+            spec_version: CYCLONEDX_SPEC_VERSION.to_string(),
+            serial_number: format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+            version: 1,
+            metadata: BomMetadata {
+                timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                component: setup,
+            },
+            components: self.components,
+        }
+    }
+}
+
+impl Bom {
+    /// Writes the SBOM as pretty printed JSON
+    pub fn write(&self, output_path: &Path) {
+        let data = serde_json::to_vec_pretty(self).expect("Failed to serialize SBOM");
+        fs::write(output_path, data).expect("Failed to write SBOM");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const CARGO_LOCK: &str = r#"
+# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "demo-app"
+version = "0.1.0"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.204"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "bc76f558e0cbb2a839d37354c575f1dc3fdc6546b5be373ba43d95f231bf7c12"
+"#;
+
+    #[test]
+    fn test_parse_cargo_lock() {
+        let packages = parse_cargo_lock(CARGO_LOCK);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "demo-app");
+        assert_eq!(packages[0].source, None);
+        assert_eq!(packages[1].name, "serde");
+        assert_eq!(packages[1].version, "1.0.204");
+        assert_eq!(
+            packages[1].checksum.as_deref(),
+            Some("bc76f558e0cbb2a839d37354c575f1dc3fdc6546b5be373ba43d95f231bf7c12")
+        );
+    }
+
+    #[test]
+    fn test_find_stub_dependencies() {
+        let packages = parse_cargo_lock(CARGO_LOCK);
+        let json = serde_json::to_vec(&packages).unwrap();
+
+        let mut stub_data = vec![0; 100];
+        stub_data.extend_from_slice(STUB_DEPENDENCIES_MAGIC);
+        stub_data.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        stub_data.extend_from_slice(&json);
+        stub_data.extend_from_slice(&[0; 100]);
+
+        assert_eq!(find_stub_dependencies(&stub_data), Some(packages));
+    }
+
+    #[test]
+    fn test_find_stub_dependencies_missing() {
+        assert_eq!(find_stub_dependencies(&[0; 100]), None);
+
+        // Truncated after the marker
+        let mut stub_data = STUB_DEPENDENCIES_MAGIC.to_vec();
+        stub_data.extend_from_slice(&100u32.to_le_bytes());
+        assert_eq!(find_stub_dependencies(&stub_data), None);
+    }
+
+    #[test]
+    fn test_component_file() {
+        let component = Component::file("application", "test.exe", Some("1.0.0"), b"hello");
+
+        assert_eq!(component.bom_ref, "file:test.exe");
+        assert_eq!(component.version.as_deref(), Some("1.0.0"));
+        assert_eq!(
+            component.hashes[0].content,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(component.properties[0].name, "twi:size");
+        assert_eq!(component.properties[0].value, "5");
+    }
+
+    #[test]
+    fn test_build_and_write() {
+        let manifest = SetupManifest {
+            name: "TestApp".to_string(),
+            title: "Test App".to_string(),
+            version: "1.0.0".to_string(),
+            identifier: "com.example.testapp".to_string(),
+            application: "test.exe".to_string(),
+            ..Default::default()
+        };
+
+        let mut sbom = SbomBuilder::new();
+        sbom.add_component(
+            Component::file("application", "test.exe", Some("1.0.0"), b"hello")
+                .with_dependencies(&parse_cargo_lock(CARGO_LOCK)),
+        );
+        sbom.add_component(Component::file("file", "icon.png", None, b"icon"));
+        let bom = sbom.build(&manifest, "TestApp-setup.exe", b"setup");
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output_path = temp_dir.path().join("TestApp-setup.cdx.json");
+        bom.write(&output_path);
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&fs::read(&output_path).unwrap()).unwrap();
+        assert_eq!(json["bomFormat"], "CycloneDX");
+        assert_eq!(json["specVersion"], CYCLONEDX_SPEC_VERSION);
+        assert!(json["serialNumber"]
+            .as_str()
+            .unwrap()
+            .starts_with("urn:uuid:"));
+        assert_eq!(json["metadata"]["component"]["name"], "TestApp-setup.exe");
+        assert_eq!(json["components"][0]["name"], "test.exe");
+        assert_eq!(
+            json["components"][0]["components"][1]["purl"],
+            "pkg:cargo/serde@1.0.204"
+        );
+        assert_eq!(json["components"][1]["type"], "file");
+        assert!(json["components"][1].get("version").is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::prerequisite::RegistryRoot;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Who the application can be installed for, set with `installScope` in the plugin config
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum InstallScope {
+    /// Installed for the current user in `%LOCALAPPDATA%`, without elevation
+    #[default]
+    PerUser,
+    /// Installed for every user in `Program Files`, setup runs elevated
+    PerMachine,
+    /// Per-user unless `--per-machine` is passed, or the application is already installed per-machine
+    Both,
+}
+
+/// Who an installation is for
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum Scope {
+    #[default]
+    PerUser,
+    PerMachine,
+}
+
+impl Scope {
+    /// Registry hive of the uninstall entry
+    pub fn registry_root(self) -> RegistryRoot {
+        match self {
+            Scope::PerUser => RegistryRoot::CurrentUser,
+            Scope::PerMachine => RegistryRoot::LocalMachine,
+        }
+    }
+
+    /// Command line flag selecting the scope
+    pub fn flag(self) -> &'static str {
+        match self {
+            Scope::PerUser => "--per-user",
+            Scope::PerMachine => "--per-machine",
+        }
+    }
+}
+
+impl InstallScope {
+    /// Resolves the scope to install in, from the scope requested on the command line and the
+    /// scope of an existing installation
+    pub fn resolve(self, requested: Option<Scope>, installed: Option<Scope>) -> Result<Scope> {
+        match (self, requested) {
+            (InstallScope::PerUser, Some(Scope::PerMachine)) => {
+                bail!("This application can only be installed per-user")
+            }
+            (InstallScope::PerMachine, Some(Scope::PerUser)) => {
+                bail!("This application can only be installed per-machine")
+            }
+            (InstallScope::PerUser, _) => Ok(Scope::PerUser),
+            (InstallScope::PerMachine, _) => Ok(Scope::PerMachine),
+            (InstallScope::Both, Some(requested)) => Ok(requested),
+            (InstallScope::Both, None) => Ok(installed.unwrap_or(Scope::PerUser)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_per_user() {
+        let scope = InstallScope::PerUser;
+        assert_eq!(scope.resolve(None, None).unwrap(), Scope::PerUser);
+        assert_eq!(
+            scope.resolve(Some(Scope::PerUser), None).unwrap(),
+            Scope::PerUser
+        );
+        assert_eq!(
+            scope.resolve(None, Some(Scope::PerMachine)).unwrap(),
+            Scope::PerUser
+        );
+        assert!(scope.resolve(Some(Scope::PerMachine), None).is_err());
+    }
+
+    #[test]
+    fn test_resolve_per_machine() {
+        let scope = InstallScope::PerMachine;
+        assert_eq!(scope.resolve(None, None).unwrap(), Scope::PerMachine);
+        assert_eq!(
+            scope.resolve(None, Some(Scope::PerUser)).unwrap(),
+            Scope::PerMachine
+        );
+        assert!(scope.resolve(Some(Scope::PerUser), None).is_err());
+    }
+
+    #[test]
+    fn test_resolve_both() {
+        let scope = InstallScope::Both;
+        assert_eq!(scope.resolve(None, None).unwrap(), Scope::PerUser);
+        assert_eq!(
+            scope.resolve(None, Some(Scope::PerMachine)).unwrap(),
+            Scope::PerMachine
+        );
+        assert_eq!(
+            scope
+                .resolve(Some(Scope::PerUser), Some(Scope::PerMachine))
+                .unwrap(),
+            Scope::PerUser
+        );
+        assert_eq!(
+            scope.resolve(Some(Scope::PerMachine), None).unwrap(),
+            Scope::PerMachine
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let scope: InstallScope = serde_json::from_str("\"perMachine\"").unwrap();
+        assert_eq!(scope, InstallScope::PerMachine);
+        let scope: InstallScope = serde_json::from_str("\"both\"").unwrap();
+        assert_eq!(scope, InstallScope::Both);
+        assert_eq!(
+            Scope::PerMachine.registry_root(),
+            RegistryRoot::LocalMachine
+        );
+    }
+}
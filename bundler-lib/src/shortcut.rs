@@ -0,0 +1,88 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Parent key of the AppUserModelIDs registered for the shortcuts, in the registry hive of the
+/// install scope
+pub const APP_USER_MODEL_ID_KEY: &str = "Software\\Classes\\AppUserModelId";
+
+/// Characters Windows doesn't allow in file names
+const INVALID_FILE_NAME_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Which shortcuts to the application are created, set with `shortcuts` in the plugin config
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Shortcuts {
+    /// Create a Start menu shortcut, true by default
+    pub start_menu: bool,
+    /// Create a desktop shortcut
+    pub desktop: bool,
+    /// Start menu folder to put the shortcut in, instead of directly in `Programs`
+    pub folder: Option<String>,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Shortcuts {
+            start_menu: true,
+            desktop: false,
+            folder: None,
+        }
+    }
+}
+
+impl Shortcuts {
+    /// Checks the Start menu folder is a valid folder name, when bundling
+    pub fn validate(&self) -> Result<()> {
+        if let Some(folder) = &self.folder {
+            if folder.trim().is_empty()
+                || folder.contains(INVALID_FILE_NAME_CHARS)
+                || folder.ends_with('.')
+            {
+                bail!("Invalid shortcut folder '{}'", folder);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// File name of the shortcuts to the application, replacing characters not allowed in file names
+pub fn shortcut_file_name(title: &str) -> String {
+    format!("{}.lnk", title.replace(INVALID_FILE_NAME_CHARS, "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let shortcuts: Shortcuts = serde_json::from_str("{}").unwrap();
+        assert_eq!(shortcuts, Shortcuts::default());
+        assert!(shortcuts.start_menu);
+
+        let shortcuts: Shortcuts =
+            serde_json::from_str(r#"{ "startMenu": false, "desktop": true, "folder": "Gussy" }"#)
+                .unwrap();
+        assert!(!shortcuts.start_menu);
+        assert!(shortcuts.desktop);
+        assert_eq!(shortcuts.folder.as_deref(), Some("Gussy"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let shortcuts = |folder: &str| Shortcuts {
+            folder: Some(folder.to_string()),
+            ..Default::default()
+        };
+        assert!(Shortcuts::default().validate().is_ok());
+        assert!(shortcuts("Gussy Apps").validate().is_ok());
+        assert!(shortcuts("..\\Startup").validate().is_err());
+        assert!(shortcuts(" ").validate().is_err());
+    }
+
+    #[test]
+    fn test_shortcut_file_name() {
+        assert_eq!(shortcut_file_name("Demo App"), "Demo App.lnk");
+        assert_eq!(shortcut_file_name("Demo: App?"), "Demo_ App_.lnk");
+    }
+}
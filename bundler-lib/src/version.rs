@@ -0,0 +1,87 @@
+use std::cmp::Ordering;
+
+/// Parses a dotted numeric version, e.g. `128.0.2739.42`.
+///
+/// Anything after the first whitespace is ignored, as WebView2 reports channel versions like
+/// `130.0.2849.5 beta`.
+pub fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let version = version.split_whitespace().next()?;
+    version
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+/// Compares two dotted numeric versions, with missing parts treated as zero so `128.0` equals
+/// `128.0.0.0`. Returns `None` if either isn't a valid version.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let a = parse_version(a)?;
+    let b = parse_version(b)?;
+
+    let len = a.len().max(b.len());
+    let part = |parts: &[u64], i: usize| parts.get(i).copied().unwrap_or(0);
+    Some(
+        (0..len)
+            .map(|i| part(&a, i).cmp(&part(&b, i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal),
+    )
+}
+
+/// Whether the version is at least the minimum version, false if either isn't a valid version
+pub fn meets_min_version(version: &str, min_version: &str) -> bool {
+    compare_versions(version, min_version).is_some_and(|ordering| ordering.is_ge())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("128.0.2739.42"), Some(vec![128, 0, 2739, 42]));
+        assert_eq!(
+            parse_version("130.0.2849.5 beta"),
+            Some(vec![130, 0, 2849, 5])
+        );
+        assert_eq!(parse_version("1"), Some(vec![1]));
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("1.x.3"), None);
+        assert_eq!(parse_version("1..3"), None);
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(
+            compare_versions("128.0.2739.42", "128.0.2739.42"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare_versions("128.0.2739.42", "128.0.2739.5"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare_versions("99.0.1150.55", "128.0"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare_versions("128.0", "128.0.0.0"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare_versions("128.0.0.1", "128"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(compare_versions("invalid", "128"), None);
+    }
+
+    #[test]
+    fn test_meets_min_version() {
+        assert!(meets_min_version("128.0.2739.42", "120.0.2210.91"));
+        assert!(meets_min_version("120.0.2210.91", "120.0.2210.91"));
+        assert!(meets_min_version("130.0.2849.5 beta", "130"));
+        assert!(!meets_min_version("109.0.1518.140", "120.0.2210.91"));
+        assert!(!meets_min_version("", "120"));
+        assert!(!meets_min_version("128.0", "latest"));
+    }
+}
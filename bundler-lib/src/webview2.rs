@@ -1,62 +1,220 @@
-use bytesize::ByteSize;
-use dirs_next::cache_dir;
-use reqwest::blocking::get;
+use crate::download::{download_cached, DownloadOptions};
+use crate::plugin_config::Webview2Bundle;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::tempdir;
 
-const WEBVIEW2_EVERGREEN_URL: &str = "https://go.microsoft.com/fwlink/p/?LinkId=2124703";
+pub const WEBVIEW2_EVERGREEN_URL: &str = "https://go.microsoft.com/fwlink/p/?LinkId=2124703";
 pub const WEBVIEW2_EVERGREEN_EXE: &str = "MicrosoftEdgeWebview2Setup.exe";
 
+/// Name of the WebView2 runtime among the prerequisites of the release metadata and install receipt
+pub const WEBVIEW2_PREREQUISITE: &str = "webview2-evergreen";
+
+/// Directory the fixed version runtime is packaged under, and extracted to next to the application
+pub const WEBVIEW2_FIXED_RUNTIME_DIR: &str = "WebView2Runtime";
+
+/// How the setup executable installs WebView2, recorded in the setup manifest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Webview2Install {
+    pub mode: Webview2Bundle,
+    /// Run the bootstrapper or offline installer without its UI
+    pub silent: bool,
+    /// Architecture of the bundled offline installer
+    #[serde(default)]
+    pub arch: Webview2Arch,
+    /// Fixed version runtime `.cab` or folder, only used by the bundler
+    #[serde(skip)]
+    pub fixed_runtime_path: Option<PathBuf>,
+    /// Minimum runtime version, an older installed runtime is upgraded
+    #[serde(default)]
+    pub min_version: Option<String>,
+}
+
+/// Architecture of the WebView2 offline standalone installer
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Webview2Arch {
+    #[default]
+    X64,
+    X86,
+    Arm64,
+}
+
+impl Webview2Arch {
+    /// Microsoft's download URL for the offline installer
+    pub fn offline_installer_url(self) -> &'static str {
+        match self {
+            Webview2Arch::X64 => "https://go.microsoft.com/fwlink/?linkid=2124701",
+            Webview2Arch::X86 => "https://go.microsoft.com/fwlink/?linkid=2099617",
+            Webview2Arch::Arm64 => "https://go.microsoft.com/fwlink/?linkid=2099616",
+        }
+    }
+
+    /// Filename of the offline installer, as named by Microsoft
+    pub fn offline_installer_exe(self) -> &'static str {
+        match self {
+            Webview2Arch::X64 => "MicrosoftEdgeWebView2RuntimeInstallerX64.exe",
+            Webview2Arch::X86 => "MicrosoftEdgeWebView2RuntimeInstallerX86.exe",
+            Webview2Arch::Arm64 => "MicrosoftEdgeWebView2RuntimeInstallerARM64.exe",
+        }
+    }
+
+    /// Detects the architecture of a Windows executable from its PE header
+    pub fn from_pe(exe_data: &[u8]) -> Option<Self> {
+        let pe_offset_bytes: [u8; 4] = exe_data.get(0x3c..0x40)?.try_into().ok()?;
+        let pe_offset = u32::from_le_bytes(pe_offset_bytes) as usize;
+        if exe_data.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+            return None;
+        }
+
+        let machine_bytes: [u8; 2] = exe_data
+            .get(pe_offset + 4..pe_offset + 6)?
+            .try_into()
+            .ok()?;
+        match u16::from_le_bytes(machine_bytes) {
+            0x8664 => Some(Webview2Arch::X64),
+            0x014c => Some(Webview2Arch::X86),
+            0xaa64 => Some(Webview2Arch::Arm64),
+            _ => None,
+        }
+    }
+}
+
 /// Download the WebView2 evergreen bootstrapper
-pub fn download_webview2_evergreen() -> Vec<u8> {
-    download_webview2_evergreen_impl(WEBVIEW2_EVERGREEN_URL)
+pub fn download_webview2_evergreen(options: &DownloadOptions) -> Result<Vec<u8>> {
+    download_webview2_evergreen_impl(WEBVIEW2_EVERGREEN_URL, options)
 }
 
 /// Download the WebView2 evergreen bootstrapper
-pub fn download_webview2_evergreen_impl(url: &str) -> Vec<u8> {
-    // Get the cache directory
-    let cache_dir = std::env::var("CACHE_DIR")
-        .ok()
-        .map(PathBuf::from)
-        .or_else(|| cache_dir().map(|d| d.join("webview2")))
-        .expect("Failed to get cache directory");
-
-    let webview2_path = cache_dir.join(WEBVIEW2_EVERGREEN_EXE);
-
-    println!("  Downloading WebView2 Evergreen: {}", url);
-
-    if !webview2_path.exists() {
-        // Ensure the cache directory exists
-        fs::create_dir_all(&cache_dir).expect("Failed to create cache directory");
-
-        // Download the file
-        let response = get(url).expect("Failed to download file");
-        let mut file = fs::File::create(&webview2_path).expect("Failed to create file");
-        let bytes = response.bytes().expect("Failed to read response bytes");
-        file.write_all(&bytes).expect("Failed to write to file");
+pub fn download_webview2_evergreen_impl(url: &str, options: &DownloadOptions) -> Result<Vec<u8>> {
+    download_cached(url, WEBVIEW2_EVERGREEN_EXE, "WebView2 Evergreen", options)
+}
+
+/// Download the WebView2 offline standalone installer for the architecture
+pub fn download_webview2_offline_installer(
+    arch: Webview2Arch,
+    options: &DownloadOptions,
+) -> Result<Vec<u8>> {
+    download_webview2_offline_installer_impl(arch.offline_installer_url(), arch, options)
+}
+
+/// Download the WebView2 offline standalone installer for the architecture
+pub fn download_webview2_offline_installer_impl(
+    url: &str,
+    arch: Webview2Arch,
+    options: &DownloadOptions,
+) -> Result<Vec<u8>> {
+    download_cached(
+        url,
+        arch.offline_installer_exe(),
+        "WebView2 offline installer",
+        options,
+    )
+}
+
+/// Reads the files of a fixed version runtime `.cab` or folder, named relative to [`WEBVIEW2_FIXED_RUNTIME_DIR`]
+pub fn load_fixed_runtime(runtime_path: &Path) -> Vec<(String, Vec<u8>)> {
+    let is_cab = runtime_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("cab"));
+    if !is_cab {
+        return load_fixed_runtime_dir(&fixed_runtime_root(runtime_path));
     }
 
-    // Read the cached or newly downloaded file
-    let webview2_data = fs::read(&webview2_path).expect("Failed to read file");
+    let extract_dir = tempdir().expect("Failed to create temp dir");
+    extract_cab(runtime_path, extract_dir.path());
+    load_fixed_runtime_dir(&fixed_runtime_root(extract_dir.path()))
+}
+
+/// Extracts a `.cab` with `expand.exe` on Windows, or `cabextract` elsewhere
+fn extract_cab(cab_path: &Path, output_dir: &Path) {
     println!(
-        "  Loaded WebView2 Evergreen: {} ({} bytes)",
-        WEBVIEW2_EVERGREEN_EXE,
-        ByteSize(webview2_data.len().try_into().unwrap())
+        "  Extracting WebView2 fixed runtime: {}",
+        cab_path.display()
     );
+    let status = if cfg!(windows) {
+        Command::new("expand")
+            .arg(cab_path)
+            .arg("-F:*")
+            .arg(output_dir)
+            .status()
+    } else {
+        Command::new("cabextract")
+            .arg("-q")
+            .arg("-d")
+            .arg(output_dir)
+            .arg(cab_path)
+            .status()
+    }
+    .expect("Failed to run the cab extractor");
+
+    if !status.success() {
+        panic!("Failed to extract {}", cab_path.display());
+    }
+}
+
+/// Microsoft's runtimes are in a single versioned folder, e.g.
+/// `Microsoft.WebView2.FixedVersionRuntime.128.0.2739.42.x64`, which isn't packaged
+fn fixed_runtime_root(runtime_path: &Path) -> PathBuf {
+    let entries: Vec<_> = fs::read_dir(runtime_path)
+        .expect("Failed to read fixed runtime directory")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+
+    match entries.as_slice() {
+        [single] if single.is_dir() => single.clone(),
+        _ => runtime_path.to_path_buf(),
+    }
+}
+
+fn load_fixed_runtime_dir(runtime_path: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut pending = vec![runtime_path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).expect("Failed to read fixed runtime directory") {
+            let path = entry.expect("Failed to read directory entry").path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let relative_path = path
+                .strip_prefix(runtime_path)
+                .expect("Failed to get relative path");
+            let name = relative_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let data = fs::read(&path).expect("Failed to read fixed runtime file");
+            files.push((format!("{}/{}", WEBVIEW2_FIXED_RUNTIME_DIR, name), data));
+        }
+    }
 
-    webview2_data
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::download::cache_path;
     use mockito::Server;
     use std::io::Write;
+    use std::sync::Mutex;
     use tempfile::tempdir;
 
+    /// The tests share the `CACHE_DIR` environment variable, so can't run in parallel
+    static CACHE_DIR_LOCK: Mutex<()> = Mutex::new(());
+
     #[test]
     fn test_download_webview2_evergreen_download() {
+        let _lock = CACHE_DIR_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         // Create a new mock server
         let mut server = Server::new();
 
@@ -70,7 +228,7 @@ mod tests {
 
         let mock_url = format!("{}/webview2", server.url());
         let cache_dir = tempdir().expect("Failed to create temp dir");
-        let webview2_path = cache_dir.path().join(WEBVIEW2_EVERGREEN_EXE);
+        let webview2_path = cache_path(cache_dir.path(), &mock_url, WEBVIEW2_EVERGREEN_EXE);
 
         // Ensure the cache directory is empty
         assert!(!webview2_path.exists());
@@ -79,7 +237,8 @@ mod tests {
         std::env::set_var("CACHE_DIR", cache_dir.path());
 
         let result = std::panic::catch_unwind(|| {
-            let bytes = download_webview2_evergreen_impl(&mock_url);
+            let bytes = download_webview2_evergreen_impl(&mock_url, &DownloadOptions::default())
+                .expect("Failed to download");
             assert_eq!(bytes, b"mock webview2 installer");
         });
 
@@ -93,10 +252,16 @@ mod tests {
 
     #[test]
     fn test_download_webview2_evergreen_cached() {
+        let _lock = CACHE_DIR_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // Create a new mock server
+        let mut server = Server::new();
+        let mock_url = server.url();
+
         let cache_dir = tempdir().expect("Failed to create temp dir");
-        let webview2_path = cache_dir.path().join(WEBVIEW2_EVERGREEN_EXE);
+        let webview2_path = cache_path(cache_dir.path(), &mock_url, WEBVIEW2_EVERGREEN_EXE);
 
         // Write a mock installer to the cache
+        fs::create_dir_all(webview2_path.parent().unwrap()).unwrap();
         {
             let mut file = fs::File::create(&webview2_path).expect("Failed to create file");
             file.write_all(b"mock cached installer")
@@ -106,9 +271,6 @@ mod tests {
         // Ensure the cache directory is not empty
         assert!(webview2_path.exists());
 
-        // Create a new mock server
-        let mut server = Server::new();
-
         // Create a mock for the WebView2 download URL (it should not be called)
         let mock = server
             .mock("GET", "/")
@@ -118,14 +280,134 @@ mod tests {
             .expect(0)
             .create();
 
-        let mock_url = server.url();
         std::env::set_var("MOCK_WEBVIEW2_URL", &mock_url);
         std::env::set_var("CACHE_DIR", cache_dir.path());
 
-        let bytes = download_webview2_evergreen_impl(&mock_url);
+        let bytes = download_webview2_evergreen_impl(&mock_url, &DownloadOptions::default())
+            .expect("Failed to load cached installer");
         assert_eq!(bytes, b"mock cached installer");
 
         // Verify that the mock was not called
         mock.assert();
     }
+
+    #[test]
+    fn test_load_fixed_runtime() {
+        let runtime_dir = tempdir().expect("Failed to create temp dir");
+        fs::create_dir_all(runtime_dir.path().join("EBWebView/x64")).unwrap();
+        fs::write(runtime_dir.path().join("msedgewebview2.exe"), b"exe").unwrap();
+        fs::write(
+            runtime_dir
+                .path()
+                .join("EBWebView/x64/EmbeddedBrowserWebView.dll"),
+            b"dll",
+        )
+        .unwrap();
+
+        let files = load_fixed_runtime(runtime_dir.path());
+
+        assert_eq!(
+            files,
+            vec![
+                (
+                    "WebView2Runtime/EBWebView/x64/EmbeddedBrowserWebView.dll".to_string(),
+                    b"dll".to_vec()
+                ),
+                (
+                    "WebView2Runtime/msedgewebview2.exe".to_string(),
+                    b"exe".to_vec()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_download_webview2_offline_installer() {
+        let mut server = Server::new();
+        let x64_mock = server
+            .mock("GET", "/offline/x64")
+            .with_status(200)
+            .with_body(b"mock x64 installer")
+            .create();
+        let arm64_mock = server
+            .mock("GET", "/offline/arm64")
+            .with_status(200)
+            .with_body(b"mock arm64 installer")
+            .create();
+
+        let cache_dir = tempdir().expect("Failed to create temp dir");
+        let options = DownloadOptions {
+            cache_dir: Some(cache_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let x64_url = format!("{}/offline/x64", server.url());
+        let bytes = download_webview2_offline_installer_impl(&x64_url, Webview2Arch::X64, &options)
+            .expect("Failed to download");
+        assert_eq!(bytes, b"mock x64 installer");
+
+        let arm64_url = format!("{}/offline/arm64", server.url());
+        let bytes =
+            download_webview2_offline_installer_impl(&arm64_url, Webview2Arch::Arm64, &options)
+                .expect("Failed to download");
+        assert_eq!(bytes, b"mock arm64 installer");
+
+        // Each architecture is cached separately
+        assert!(cache_path(
+            cache_dir.path(),
+            &x64_url,
+            "MicrosoftEdgeWebView2RuntimeInstallerX64.exe"
+        )
+        .exists());
+        assert!(cache_path(
+            cache_dir.path(),
+            &arm64_url,
+            "MicrosoftEdgeWebView2RuntimeInstallerARM64.exe"
+        )
+        .exists());
+
+        x64_mock.assert();
+        arm64_mock.assert();
+    }
+
+    #[test]
+    fn test_webview2_arch_from_pe() {
+        let pe = |machine: u16| {
+            let mut data = vec![0; 0x100];
+            data[0..2].copy_from_slice(b"MZ");
+            data[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+            data[0x80..0x84].copy_from_slice(b"PE\0\0");
+            data[0x84..0x86].copy_from_slice(&machine.to_le_bytes());
+            data
+        };
+
+        assert_eq!(Webview2Arch::from_pe(&pe(0x8664)), Some(Webview2Arch::X64));
+        assert_eq!(Webview2Arch::from_pe(&pe(0x014c)), Some(Webview2Arch::X86));
+        assert_eq!(
+            Webview2Arch::from_pe(&pe(0xaa64)),
+            Some(Webview2Arch::Arm64)
+        );
+        assert_eq!(Webview2Arch::from_pe(&pe(0x0200)), None);
+        assert_eq!(Webview2Arch::from_pe(b"not an executable"), None);
+    }
+
+    #[test]
+    fn test_load_fixed_runtime_versioned_folder() {
+        let runtime_dir = tempdir().expect("Failed to create temp dir");
+        let versioned_dir = runtime_dir
+            .path()
+            .join("Microsoft.WebView2.FixedVersionRuntime.128.0.2739.42.x64");
+        fs::create_dir_all(&versioned_dir).unwrap();
+        fs::write(versioned_dir.join("msedgewebview2.exe"), b"exe").unwrap();
+
+        let files = load_fixed_runtime(runtime_dir.path());
+
+        assert_eq!(
+            files,
+            vec![(
+                "WebView2Runtime/msedgewebview2.exe".to_string(),
+                b"exe".to_vec()
+            )]
+        );
+    }
 }
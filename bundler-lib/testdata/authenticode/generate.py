@@ -0,0 +1,232 @@
+#!/usr/bin/env python3
+"""Generates the Authenticode test fixtures.
+
+Creates a test root, intermediate and code signing certificate, a minimal PE32+
+executable and a copy of it with an Authenticode signature, like Microsoft's.
+
+Requires the `cryptography` package: python3 generate.py
+"""
+
+import datetime
+import hashlib
+import os
+import struct
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import padding, rsa
+from cryptography.x509.oid import ExtendedKeyUsageOID, NameOID
+
+OUT_DIR = os.path.dirname(os.path.abspath(__file__))
+
+
+def der(tag, content):
+    length = len(content)
+    if length < 0x80:
+        encoded_length = bytes([length])
+    else:
+        length_bytes = length.to_bytes((length.bit_length() + 7) // 8, "big")
+        encoded_length = bytes([0x80 | len(length_bytes)]) + length_bytes
+    return bytes([tag]) + encoded_length + content
+
+
+def sequence(*items):
+    return der(0x30, b"".join(items))
+
+
+def set_of(*items):
+    return der(0x31, b"".join(sorted(items)))
+
+
+def oid(dotted):
+    parts = [int(part) for part in dotted.split(".")]
+    body = bytes([parts[0] * 40 + parts[1]])
+    for part in parts[2:]:
+        chunk = [part & 0x7F]
+        part >>= 7
+        while part:
+            chunk.insert(0, 0x80 | (part & 0x7F))
+            part >>= 7
+        body += bytes(chunk)
+    return der(0x06, body)
+
+
+def integer(value):
+    return der(0x02, value.to_bytes(value.bit_length() // 8 + 1, "big"))
+
+
+NULL = der(0x05, b"")
+SHA256 = sequence(oid("2.16.840.1.101.3.4.2.1"), NULL)
+RSA_ENCRYPTION = sequence(oid("1.2.840.113549.1.1.1"), NULL)
+SPC_INDIRECT_DATA = "1.3.6.1.4.1.311.2.1.4"
+SPC_PE_IMAGE_DATA = "1.3.6.1.4.1.311.2.1.15"
+
+
+def name(common_name):
+    return x509.Name(
+        [
+            x509.NameAttribute(NameOID.COMMON_NAME, common_name),
+            x509.NameAttribute(NameOID.ORGANIZATION_NAME, "Test Corporation"),
+        ]
+    )
+
+
+def certificate(subject, key, issuer=None, issuer_key=None, ca=True, authority_key=None):
+    issuer = issuer or subject
+    issuer_key = issuer_key or key
+    authority_key = authority_key or issuer_key
+    now = datetime.datetime(2024, 1, 1, tzinfo=datetime.timezone.utc)
+    builder = (
+        x509.CertificateBuilder()
+        .subject_name(subject)
+        .issuer_name(issuer)
+        .public_key(key.public_key())
+        .serial_number(x509.random_serial_number())
+        .not_valid_before(now)
+        .not_valid_after(now + datetime.timedelta(days=36500))
+        .add_extension(x509.BasicConstraints(ca=ca, path_length=None), critical=True)
+        .add_extension(
+            x509.SubjectKeyIdentifier.from_public_key(key.public_key()), critical=False
+        )
+        .add_extension(
+            x509.AuthorityKeyIdentifier.from_issuer_public_key(authority_key.public_key()),
+            critical=False,
+        )
+    )
+    if not ca:
+        builder = builder.add_extension(
+            x509.ExtendedKeyUsage([ExtendedKeyUsageOID.CODE_SIGNING]), critical=False
+        )
+    return builder.sign(issuer_key, hashes.SHA256())
+
+
+def key():
+    return rsa.generate_private_key(public_exponent=65537, key_size=2048)
+
+
+def unsigned_pe():
+    """A PE32+ image with a single section of 0x400 bytes"""
+    dos_header = b"MZ" + b"\0" * 0x3A + struct.pack("<I", 0x40)
+    coff_header = struct.pack("<HHIIIHH", 0x8664, 1, 0, 0, 0, 240, 0x22)
+    optional_header = struct.pack(
+        "<HBBIIIII", 0x20B, 14, 0, 0x400, 0, 0, 0x1000, 0x1000
+    )
+    optional_header += struct.pack("<QII", 0x140000000, 0x1000, 0x200)
+    optional_header += struct.pack("<HHHHHHI", 6, 0, 0, 0, 6, 0, 0)
+    optional_header += struct.pack("<IIIHH", 0x2000, 0x200, 0, 3, 0x8160)
+    optional_header += struct.pack("<QQQQII", 0x100000, 0x1000, 0x100000, 0x1000, 0, 16)
+    optional_header += b"\0" * 16 * 8
+    section = b".text\0\0\0" + struct.pack(
+        "<IIIIIIHHI", 0x400, 0x1000, 0x400, 0x200, 0, 0, 0, 0, 0x60000020
+    )
+    headers = dos_header + b"PE\0\0" + coff_header + optional_header + section
+    headers += b"\0" * (0x200 - len(headers))
+    code = bytes([0xC3]) + bytes(range(256)) * 4
+    return headers + code[:0x400]
+
+
+def authenticode_digest(image, security_directory):
+    checksum = 0x40 + 24 + 64
+    return hashlib.sha256(
+        image[:checksum]
+        + image[checksum + 4 : security_directory]
+        + image[security_directory + 8 :]
+    ).digest()
+
+
+def sign(image, signer, signer_key, certificates):
+    security_directory = 0x40 + 24 + 112 + 4 * 8
+
+    # The message digest covers the content of the SpcIndirectDataContent, without its header
+    content = sequence(
+        oid(SPC_PE_IMAGE_DATA), sequence(der(0x03, b"\0"), bytes.fromhex("a004a2028000"))
+    ) + sequence(SHA256, der(0x04, authenticode_digest(image, security_directory)))
+    spc_indirect_data = der(0x30, content)
+    signed_attributes = [
+        sequence(oid("1.2.840.113549.1.9.3"), set_of(oid(SPC_INDIRECT_DATA))),
+        sequence(
+            oid("1.2.840.113549.1.9.4"),
+            set_of(der(0x04, hashlib.sha256(content).digest())),
+        ),
+    ]
+    signature = signer_key.sign(
+        set_of(*signed_attributes), padding.PKCS1v15(), hashes.SHA256()
+    )
+
+    signer_info = sequence(
+        integer(1),
+        sequence(signer.issuer.public_bytes(), integer(signer.serial_number)),
+        SHA256,
+        der(0xA0, b"".join(sorted(signed_attributes))),
+        RSA_ENCRYPTION,
+        der(0x04, signature),
+    )
+    signed_data = sequence(
+        integer(1),
+        set_of(SHA256),
+        sequence(oid(SPC_INDIRECT_DATA), der(0xA0, spc_indirect_data)),
+        der(
+            0xA0,
+            b"".join(
+                sorted(c.public_bytes(serialization.Encoding.DER) for c in certificates)
+            ),
+        ),
+        set_of(signer_info),
+    )
+    content_info = sequence(oid("1.2.840.113549.1.7.2"), der(0xA0, signed_data))
+
+    # WIN_CERTIFICATE, padded to 8 bytes
+    win_certificate = struct.pack("<IHH", 8 + len(content_info), 0x0200, 0x0002) + content_info
+    win_certificate += b"\0" * (-len(win_certificate) % 8)
+
+    signed = bytearray(image)
+    struct.pack_into("<II", signed, security_directory, len(image), len(win_certificate))
+    return bytes(signed) + win_certificate
+
+
+def write(filename, data):
+    with open(os.path.join(OUT_DIR, filename), "wb") as file:
+        file.write(data)
+
+
+def main():
+    root_key = key()
+    root = certificate(name("Test Root Certificate Authority"), root_key)
+
+    pca_key = key()
+    pca = certificate(name("Test Code Signing PCA"), pca_key, root.subject, root_key)
+
+    signer_key = key()
+    signer = certificate(
+        name("Test Corporation"), signer_key, pca.subject, pca_key, ca=False
+    )
+
+    # A root with the same name as the test root, but a different key
+    other_root_key = key()
+    other_root = certificate(name("Test Root Certificate Authority"), other_root_key)
+
+    # A chain claiming to be issued by the test root, with its name and key identifier, but
+    # signed by another key
+    forged_pca_key = key()
+    forged_pca = certificate(
+        name("Test Code Signing PCA"),
+        forged_pca_key,
+        root.subject,
+        other_root_key,
+        authority_key=root_key,
+    )
+    forged_signer_key = key()
+    forged_signer = certificate(
+        name("Test Corporation"), forged_signer_key, forged_pca.subject, forged_pca_key, ca=False
+    )
+
+    image = unsigned_pe()
+    write("unsigned.exe", image)
+    write("signed.exe", sign(image, signer, signer_key, [signer, pca]))
+    write("forged.exe", sign(image, forged_signer, forged_signer_key, [forged_signer, forged_pca]))
+    write("root.cer", root.public_bytes(serialization.Encoding.DER))
+    write("other-root.cer", other_root.public_bytes(serialization.Encoding.DER))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,13 @@
+$ErrorActionPreference = 'Stop'
+
+$packageArgs = @{
+  packageName    = $env:ChocolateyPackageName
+  fileType       = 'exe'
+  url64bit       = 'https://example.com/releases/download/v1.2.3/test-app-setup.exe'
+  checksum64     = '2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824'
+  checksumType64 = 'sha256'
+  silentArgs     = '--silent'
+  validExitCodes = @(0, 3010)
+}
+
+Install-ChocolateyPackage @packageArgs
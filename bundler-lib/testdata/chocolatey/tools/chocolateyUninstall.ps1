@@ -0,0 +1,14 @@
+$ErrorActionPreference = 'Stop'
+
+$key = 'HKCU:\Software\Microsoft\Windows\CurrentVersion\Uninstall\com.example.test-app'
+$installLocation = (Get-ItemProperty -Path $key).InstallLocation
+
+$packageArgs = @{
+  packageName    = $env:ChocolateyPackageName
+  fileType       = 'exe'
+  file           = Join-Path $installLocation 'test-app.exe'
+  silentArgs     = '--uninstall --silent'
+  validExitCodes = @(0)
+}
+
+Uninstall-ChocolateyPackage @packageArgs
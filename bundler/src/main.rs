@@ -1,7 +1,23 @@
+use anyhow::anyhow;
 use bundler::{
+    authenticode::AuthenticodeVerifier,
+    download::DownloadOptions,
     exe_packager::{ExePackager, SetupManifest},
+    file_association::FileAssociation,
+    install_dir::validate_install_dir,
+    package_managers::{
+        chocolatey::ChocolateyPackage, scoop::ScoopManifest, winget::WingetManifests, PackageInfo,
+    },
     plugin_config::{load_tauri_config, Webview2Bundle},
-    webview2::{download_webview2_evergreen, WEBVIEW2_EVERGREEN_EXE},
+    portable::PortablePackager,
+    release::ReleaseMetadata,
+    sbom::{find_stub_dependencies, parse_cargo_lock, Component, SbomBuilder},
+    version::parse_version,
+    webview2::{
+        download_webview2_evergreen_impl, download_webview2_offline_installer_impl,
+        load_fixed_runtime, Webview2Arch, WEBVIEW2_EVERGREEN_EXE, WEBVIEW2_EVERGREEN_URL,
+        WEBVIEW2_PREREQUISITE,
+    },
 };
 use bytesize::ByteSize;
 use clap::Parser;
@@ -9,6 +25,9 @@ use colored::*;
 use editpe::Image;
 use std::{env, path::Path};
 
+/// Architecture of the embedded setup executable, only 64-bit is supported
+const SETUP_ARCH: &str = "x64";
+
 /// Tauri Windows Installer Bundler
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -24,6 +43,59 @@ struct Args {
     /// Title of the bundled application
     #[arg(short, long)]
     title: String,
+
+    /// Download URL of the setup executable used by package manager manifests,
+    /// supports the {name}, {identifier}, {version}, {arch} and {file} placeholders
+    #[arg(long)]
+    installer_url: Option<String>,
+
+    /// Generate winget manifests in the `winget` directory
+    #[arg(long, requires = "installer_url")]
+    winget: bool,
+
+    /// Generate a Chocolatey package in the `chocolatey` directory
+    #[arg(long, requires = "installer_url")]
+    chocolatey: bool,
+
+    /// Generate a Scoop manifest in the `scoop` directory
+    #[arg(long, requires = "installer_url")]
+    scoop: bool,
+
+    /// Also create a portable ZIP of the application
+    #[arg(long)]
+    portable: bool,
+
+    /// Write a CycloneDX SBOM of the setup executable contents
+    #[arg(long)]
+    sbom: bool,
+
+    /// Path to the application's Cargo.lock, merged in to the SBOM
+    #[arg(long, requires = "sbom")]
+    cargo_lock: Option<String>,
+
+    /// URL to download the WebView2 bootstrapper or offline installer from
+    #[arg(long)]
+    webview2_url: Option<String>,
+
+    /// Mirror URL tried when the WebView2 download fails, can be repeated
+    #[arg(long)]
+    webview2_mirror: Vec<String>,
+
+    /// Expected SHA-256 of the WebView2 bootstrapper or offline installer
+    #[arg(long)]
+    webview2_sha256: Option<String>,
+
+    /// DER encoded root certificate trusted to sign the WebView2 installers, can be repeated
+    #[arg(long)]
+    webview2_trusted_root: Vec<String>,
+
+    /// Download the WebView2 installers again, even when cached
+    #[arg(long, conflicts_with = "offline")]
+    refresh: bool,
+
+    /// Only use cached WebView2 installers, without downloading
+    #[arg(long)]
+    offline: bool,
 }
 
 fn main() {
@@ -37,12 +109,15 @@ fn main() {
     // Load the setup.exe file
     let mut setup_data = load_embedded_setup();
 
+    // The portable ZIP is created from the same inputs as the setup executable
+    let mut portable = args.portable.then(PortablePackager::new);
+
     // Add an icon to the output executable
     let mut image = Image::parse(&setup_data).expect("Failed to parse exe data");
     let mut resources = image.resource_directory().cloned().unwrap_or_default();
 
     // Use the icon specified in the plugin config, or the first png icon in the bundle config
-    let icon = plugin_config.icon.or_else(|| {
+    let icon = plugin_config.icon.clone().or_else(|| {
         tauri_conf
             .bundle
             .icon
@@ -50,14 +125,18 @@ fn main() {
             .find(|i| i.ends_with(".png"))
             .cloned()
     });
+    // The icon is also installed next to the application, for its notifications
+    let mut icon_file = None;
     if let Some(icon) = icon {
         let icon_path = Path::new(&args.tauri_conf).parent().unwrap().join(icon);
         let icon_data = std::fs::read(&icon_path).expect("Failed to read icon data");
         resources.set_icon(&icon_data).expect("Failed to set icon");
-        println!(
-            "  Added icon: {}",
-            &icon_path.file_name().unwrap().to_str().unwrap()
-        );
+        let icon_filename = icon_path.file_name().unwrap().to_str().unwrap();
+        println!("  Added icon: {}", icon_filename);
+        if let Some(portable) = &mut portable {
+            portable.add_file(icon_filename, icon_data.clone());
+        }
+        icon_file = Some((icon_filename.to_owned(), icon_data));
     } else {
         println!("  No icon specified, skipping icon addition");
     }
@@ -69,25 +148,206 @@ fn main() {
     setup_data = image.data().into();
     println!("  Added resources to the setup file");
 
+    // Record the setup stub, and the dependencies embedded in it at build time
+    let mut sbom = args.sbom.then(SbomBuilder::new);
+    if let Some(sbom) = &mut sbom {
+        let stub_dependencies = find_stub_dependencies(&setup_data).unwrap_or_default();
+        sbom.add_component(
+            Component::file(
+                "application",
+                env!("SETUP_EXE"),
+                Some(env!("CARGO_PKG_VERSION")),
+                &setup_data,
+            )
+            .with_dependencies(&stub_dependencies),
+        );
+    }
+
     // Create the packager
     let mut packager = ExePackager::new(setup_data);
+    let mut prerequisites = Vec::new();
 
     // Handle the webview2 bundling
-    match &plugin_config.webview2.bundle {
-        Some(Webview2Bundle::Evergreen) => {
+    let mut webview2 = plugin_config.webview2_install(&tauri_conf);
+    if let Some(min_version) = &webview2.min_version {
+        if parse_version(min_version).is_none() {
+            exit_with_error(anyhow!("Invalid webview2 minVersion: {}", min_version));
+        }
+        println!("  Minimum WebView2 runtime version: {}", min_version);
+    }
+    let download_options = DownloadOptions {
+        mirrors: args.webview2_mirror.clone(),
+        sha256: args
+            .webview2_sha256
+            .clone()
+            .or(plugin_config.webview2.sha256.clone()),
+        refresh: args.refresh,
+        offline: args.offline,
+        ..Default::default()
+    };
+
+    // Downloaded installers must be signed by Microsoft, or by one of the trusted roots
+    let mut verifier = AuthenticodeVerifier::microsoft();
+    for root in &args.webview2_trusted_root {
+        let root_data = std::fs::read(root).expect("Failed to read trusted root certificate");
+        verifier
+            .add_root_certificate(&root_data)
+            .unwrap_or_else(|e| exit_with_error(e));
+    }
+
+    match webview2.mode {
+        Webview2Bundle::Skip => {
+            println!("  {}", "Skipping webview2 installation".blue());
+        }
+        Webview2Bundle::DownloadBootstrapper => {
+            println!(
+                "  {}",
+                "The webview2 bootstrapper will be downloaded at install time".blue()
+            );
+            prerequisites.push(WEBVIEW2_PREREQUISITE.to_string());
+        }
+        Webview2Bundle::EmbedBootstrapper => {
             println!(
                 "  {}",
                 "Bundling the webview2 evergreen bootstrapper...".green()
             );
 
-            let webview_data = download_webview2_evergreen();
+            let url = args
+                .webview2_url
+                .as_deref()
+                .unwrap_or(WEBVIEW2_EVERGREEN_URL);
+            let webview_data = download_webview2_evergreen_impl(url, &download_options)
+                .unwrap_or_else(|e| exit_with_error(e));
+            verify_signature(&verifier, WEBVIEW2_EVERGREEN_EXE, &webview_data);
+            if let Some(sbom) = &mut sbom {
+                sbom.add_component(Component::file(
+                    "application",
+                    WEBVIEW2_EVERGREEN_EXE,
+                    None,
+                    &webview_data,
+                ));
+            }
             packager.add_file(WEBVIEW2_EVERGREEN_EXE, webview_data.to_vec());
+            prerequisites.push(WEBVIEW2_PREREQUISITE.to_string());
         }
-        None => {
-            println!("  {}", "No webview2 bundle specified".blue());
+        Webview2Bundle::OfflineInstaller => {
+            println!("  {}", "Bundling the webview2 offline installer...".green());
+
+            // Bundle the installer matching the application, unless specified in the plugin config
+            if plugin_config.webview2.arch.is_none() {
+                let app_data =
+                    std::fs::read(&args.app).expect("Failed to read application executable");
+                webview2.arch = Webview2Arch::from_pe(&app_data).unwrap_or_default();
+            }
+            println!(
+                "  WebView2 offline installer architecture: {:?}",
+                webview2.arch
+            );
+
+            let url = args
+                .webview2_url
+                .as_deref()
+                .or(plugin_config.webview2.offline_installer_url.as_deref())
+                .unwrap_or(webview2.arch.offline_installer_url());
+            let webview_data =
+                download_webview2_offline_installer_impl(url, webview2.arch, &download_options)
+                    .unwrap_or_else(|e| exit_with_error(e));
+            let webview_exe = webview2.arch.offline_installer_exe();
+            verify_signature(&verifier, webview_exe, &webview_data);
+            if let Some(sbom) = &mut sbom {
+                sbom.add_component(Component::file(
+                    "application",
+                    webview_exe,
+                    None,
+                    &webview_data,
+                ));
+            }
+            packager.add_file(webview_exe, webview_data);
+            prerequisites.push(WEBVIEW2_PREREQUISITE.to_string());
+        }
+        Webview2Bundle::FixedRuntime => {
+            println!(
+                "  {}",
+                "Bundling the webview2 fixed version runtime...".green()
+            );
+
+            let runtime_path = Path::new(&args.tauri_conf).parent().unwrap().join(
+                webview2
+                    .fixed_runtime_path
+                    .as_ref()
+                    .expect("No webview2 fixed runtime path specified"),
+            );
+            let runtime_files = load_fixed_runtime(&runtime_path);
+            println!(
+                "  Loaded WebView2 fixed runtime: {} ({} files)",
+                runtime_path.display(),
+                runtime_files.len()
+            );
+            for (filename, data) in runtime_files {
+                if let Some(sbom) = &mut sbom {
+                    sbom.add_component(Component::file("file", &filename, None, &data));
+                }
+                // The portable application finds the runtime next to it, like an installed one
+                if let Some(portable) = &mut portable {
+                    portable.add_file(&filename, data.clone());
+                }
+                packager.add_file(&filename, data);
+            }
         }
     }
 
+    if let Some(install_dir) = &plugin_config.install_dir {
+        validate_install_dir(install_dir).unwrap_or_else(|e| exit_with_error(e));
+    }
+    plugin_config
+        .shortcuts
+        .validate()
+        .unwrap_or_else(|e| exit_with_error(e));
+
+    // Bundle the prerequisites, those without a path are downloaded at install time
+    let mut setup_prerequisites = plugin_config.prerequisites.clone();
+    for prerequisite in &mut setup_prerequisites {
+        prerequisite
+            .validate()
+            .unwrap_or_else(|e| exit_with_error(e));
+        if prerequisites.contains(&prerequisite.name) {
+            exit_with_error(anyhow!("Duplicate prerequisite: {}", prerequisite.name));
+        }
+
+        if let Some(path) = &prerequisite.path {
+            let installer_path = Path::new(&args.tauri_conf).parent().unwrap().join(path);
+            let installer_data =
+                std::fs::read(&installer_path).expect("Failed to read prerequisite installer");
+            let filename = installer_path.file_name().unwrap().to_str().unwrap();
+            let payload = prerequisite.payload_name(filename);
+            println!(
+                "  Bundled prerequisite: {} ({})",
+                prerequisite.title(),
+                ByteSize(installer_data.len() as u64)
+            );
+            if let Some(sbom) = &mut sbom {
+                sbom.add_component(Component::file(
+                    "application",
+                    filename,
+                    None,
+                    &installer_data,
+                ));
+            }
+            packager.add_file(&payload, installer_data);
+            prerequisite.payload = Some(payload);
+        } else {
+            println!(
+                "  {}",
+                format!(
+                    "Prerequisite {} will be downloaded at install time",
+                    prerequisite.title()
+                )
+                .blue()
+            );
+        }
+        prerequisites.push(prerequisite.name.clone());
+    }
+
     // Add the application executable to the package
     let app_exe = Path::new(&args.app).file_name().unwrap().to_str().unwrap();
     let app_data = std::fs::read(&args.app).expect("Failed to read application executable");
@@ -95,7 +355,25 @@ fn main() {
         .len()
         .try_into()
         .expect("Failed to convert app data length");
+    if let Some(portable) = &mut portable {
+        portable.add_file(app_exe, app_data.clone());
+    }
+    if let Some(sbom) = &mut sbom {
+        let app_version = tauri_conf.version.as_deref().unwrap_or("0.0.0");
+        let mut app_component =
+            Component::file("application", app_exe, Some(app_version), &app_data);
+        if let Some(cargo_lock) = &args.cargo_lock {
+            let lock_contents =
+                std::fs::read_to_string(cargo_lock).expect("Failed to read Cargo.lock");
+            app_component = app_component.with_dependencies(&parse_cargo_lock(&lock_contents));
+        }
+        sbom.add_component(app_component);
+    }
     packager.add_file(app_exe, app_data);
+    let icon = icon_file.map(|(icon_filename, icon_data)| {
+        packager.add_file(&icon_filename, icon_data);
+        icon_filename
+    });
     println!(
         "  Loaded application executable: {} ({} bytes)",
         app_exe,
@@ -109,23 +387,93 @@ fn main() {
         version: tauri_conf.version.clone().unwrap_or("0.0.0".to_owned()),
         identifier: tauri_conf.identifier.clone(),
         application: app_exe.to_owned(),
+        webview2: Some(webview2),
+        prerequisites: setup_prerequisites,
+        allow_downgrades: tauri_conf.bundle.windows.allow_downgrades,
+        install_scope: plugin_config.install_scope,
+        install_dir: plugin_config.install_dir.clone(),
+        shortcuts: plugin_config.shortcuts.clone(),
+        icon,
+        file_associations: FileAssociation::from_tauri_config(&tauri_conf),
     };
     packager.add_manifest(&manifest);
 
     // Package the executable with the added files and manifest
     let output_filename = format!("{}-setup.exe", manifest.name);
-    packager.package(Path::new(&output_filename));
+    let output_path = Path::new(&output_filename);
+    packager.package(output_path);
 
-    // Print the output filename and size
-    let output_size = std::fs::metadata(&output_filename)
-        .expect("Failed to get output file metadata")
-        .len();
+    // Write the checksum and release metadata next to the setup executable
+    let release = ReleaseMetadata::new(&manifest, output_path, SETUP_ARCH, prerequisites);
+    let (sha256_path, release_path) = release.write(output_path);
 
     println!("{}", "Packaging complete.".green().bold());
     println!(
         "{}",
-        format!("Created {} ({})", output_filename, ByteSize(output_size)).green()
+        format!(
+            "Created {} ({})",
+            output_filename,
+            ByteSize(release.file.size)
+        )
+        .green()
     );
+    println!("{}", format!("Created {}", sha256_path.display()).green());
+    println!("{}", format!("Created {}", release_path.display()).green());
+
+    // Write the SBOM of the setup executable contents
+    if let Some(sbom) = sbom {
+        let sbom_filename = format!("{}-setup.cdx.json", manifest.name);
+        let setup_data = std::fs::read(output_path).expect("Failed to read setup executable");
+        sbom.build(&manifest, &output_filename, &setup_data)
+            .write(Path::new(&sbom_filename));
+        println!("{}", format!("Created {}", sbom_filename).green());
+    }
+
+    // Create the portable ZIP
+    if let Some(portable) = portable {
+        let portable_filename = format!("{}-portable.zip", manifest.name);
+        portable.package(&manifest, Path::new(&portable_filename));
+        println!("{}", format!("Created {}", portable_filename).green());
+    }
+
+    // Generate the package manager manifests
+    if let Some(installer_url) = &args.installer_url {
+        let info = PackageInfo::new(&manifest, &release, &tauri_conf, installer_url);
+
+        if args.winget {
+            let paths = WingetManifests::generate(&info).write(Path::new("winget"));
+            for path in paths {
+                println!("{}", format!("Created {}", path.display()).green());
+            }
+        }
+
+        if args.chocolatey {
+            let paths = ChocolateyPackage::generate(&info).write(Path::new("chocolatey"));
+            for path in paths {
+                println!("{}", format!("Created {}", path.display()).green());
+            }
+        }
+
+        if args.scoop {
+            let path = ScoopManifest::generate(&info).write(Path::new("scoop"), &manifest.name);
+            println!("{}", format!("Created {}", path.display()).green());
+        }
+    }
+}
+
+/// Prints the error and exits, for failures the user can fix such as a failed download
+fn exit_with_error(error: anyhow::Error) -> ! {
+    eprintln!("{}", format!("Error: {:#}", error).red().bold());
+    std::process::exit(1);
+}
+
+/// Checks the Authenticode signature of a downloaded installer before it's bundled
+fn verify_signature(verifier: &AuthenticodeVerifier, filename: &str, data: &[u8]) {
+    let info = verifier
+        .verify(data)
+        .map_err(|e| e.context(format!("Failed to verify the signature of {}", filename)))
+        .unwrap_or_else(|e| exit_with_error(e));
+    println!("  Verified signature of {}: {}", filename, info.signer);
 }
 
 fn load_embedded_setup() -> Vec<u8> {
@@ -6,5 +6,9 @@ fn main() {
     #[cfg(target_os = "windows")]
     twi_uninstall::handle_uninstall(&"Demo App", &"com.gussy.demo-app"); // TODO: Get this at build time
 
+    // Use the fixed WebView2 runtime, when installed with the fixedRuntime mode
+    #[cfg(target_os = "windows")]
+    twi_uninstall::use_webview2_fixed_runtime();
+
     demo_app_lib::run()
 }
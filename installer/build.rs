@@ -1,4 +1,12 @@
 use embed_manifest::{embed_manifest, new_manifest};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Must match `bundler::sbom::STUB_DEPENDENCIES_MAGIC`
+const STUB_DEPENDENCIES_MAGIC: &[u8] = b"TWI-STUB-DEPENDENCIES:";
+const STUB_DEPENDENCIES_FILE: &str = "stub-dependencies.bin";
 
 fn main() {
     // Embed the app.manifest file
@@ -6,4 +14,91 @@ fn main() {
         embed_manifest(new_manifest("app.manifest")).expect("unable to embed manifest file");
         println!("cargo:rerun-if-changed=app.manifest");
     }
+
+    // Embed the dependency list of the setup stub, reported in the bundler's SBOM
+    write_stub_dependencies();
+}
+
+fn write_stub_dependencies() {
+    let manifest_dir_str = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    let lock_path = PathBuf::from(manifest_dir_str)
+        .parent()
+        .expect("Failed to get workspace directory")
+        .join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+
+    let package_name = env::var("CARGO_PKG_NAME").expect("CARGO_PKG_NAME is not set");
+    let packages = fs::read_to_string(&lock_path)
+        .map(|contents| resolve_dependencies(&contents, &package_name))
+        .unwrap_or_default();
+    let packages_json = serde_json::to_vec(&packages).expect("Failed to serialize dependencies");
+
+    // The marker and length let the bundler find the list in the compiled executable
+    let mut data = STUB_DEPENDENCIES_MAGIC.to_vec();
+    data.extend_from_slice(&(packages_json.len() as u32).to_le_bytes());
+    data.extend_from_slice(&packages_json);
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR is not set"));
+    fs::write(out_dir.join(STUB_DEPENDENCIES_FILE), data)
+        .expect("Failed to write stub dependencies");
+}
+
+/// Resolves all the packages the root package depends on from the contents of a `Cargo.lock`
+fn resolve_dependencies(lock_contents: &str, root: &str) -> Vec<serde_json::Value> {
+    let lock: toml::Table = toml::from_str(lock_contents).expect("Failed to parse Cargo.lock");
+    let packages = lock
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let field = |package: &toml::Value, key: &str| -> Option<String> {
+        package.get(key)?.as_str().map(str::to_string)
+    };
+
+    // Dependencies are listed as "name", "name version" or "name version (source)"
+    let find = |spec: &str| {
+        let mut parts = spec.split(' ');
+        let name = parts.next();
+        let version = parts.next();
+        packages.iter().find(|package| {
+            field(package, "name").as_deref() == name
+                && version.is_none_or(|v| field(package, "version").as_deref() == Some(v))
+        })
+    };
+
+    let mut visited = BTreeSet::new();
+    let mut resolved = Vec::new();
+    let mut pending = vec![root.to_string()];
+    while let Some(spec) = pending.pop() {
+        let Some(package) = find(&spec) else {
+            continue;
+        };
+        let name = field(package, "name").unwrap_or_default();
+        let version = field(package, "version").unwrap_or_default();
+        if !visited.insert((name.clone(), version.clone())) {
+            continue;
+        }
+
+        if let Some(dependencies) = package.get("dependencies").and_then(|d| d.as_array()) {
+            pending.extend(
+                dependencies
+                    .iter()
+                    .filter_map(|d| d.as_str().map(str::to_string)),
+            );
+        }
+
+        if name != root {
+            let mut entry = serde_json::json!({ "name": name, "version": version });
+            for key in ["source", "checksum"] {
+                if let Some(value) = field(package, key) {
+                    entry[key] = value.into();
+                }
+            }
+            resolved.push(entry);
+        }
+    }
+
+    resolved.sort_by_key(|entry| (entry["name"].to_string(), entry["version"].to_string()));
+    resolved
 }
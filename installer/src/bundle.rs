@@ -1,14 +1,68 @@
 mod application;
+mod prerequisites;
 mod webview2;
 
 pub(crate) use application::Application;
+pub(crate) use prerequisites::Prerequisites;
 pub(crate) use webview2::WebView2;
 
+use crate::install::generate_random_string;
+use crate::transaction::Transaction;
+
+use anyhow::{Context, Result};
 use bundler::SetupPackage;
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub(crate) trait Bundle {
     fn load(package: &SetupPackage) -> Self;
-    fn is_installed() -> bool;
-    fn install(&self, quiet: bool, path: &PathBuf) -> Result<(), anyhow::Error>;
+    fn is_installed(&self) -> bool;
+    /// Installs into the installation directory, as part of the install transaction
+    fn install(&self, transaction: &mut Transaction, path: &Path) -> Result<(), anyhow::Error>;
+    /// Files installed in the installation directory, by their `/` separated path relative to it
+    fn files(&self) -> Vec<(String, &[u8])>;
+}
+
+/// A new directory in the temp directory for an installer, removed with its contents when dropped
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates `{prefix}-XXXXXXXX`, a new directory so only what the installer created is removed
+    pub fn new(prefix: &str) -> Result<Self> {
+        let path = env::temp_dir().join(format!("{}-{}", prefix, generate_random_string(8)));
+        fs::create_dir(&path).with_context(|| format!("Failed to create directory {:?}", path))?;
+        Ok(TempDir { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_dir_removed_when_dropped() {
+        let temp_dir = TempDir::new("twi-test").unwrap();
+        let path = temp_dir.path().to_path_buf();
+        fs::write(path.join("installer.exe"), b"installer").unwrap();
+
+        let other = TempDir::new("twi-test").unwrap();
+        assert_ne!(other.path(), path);
+
+        drop(temp_dir);
+        assert!(!path.exists());
+        assert!(other.path().exists());
+    }
 }
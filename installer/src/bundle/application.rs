@@ -1,53 +1,55 @@
 use crate::bundle::Bundle;
-use crate::process::spawn_detached_process;
+use crate::transaction::Transaction;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use bundler::SetupPackage;
-use std::{fs, io::Write, path::PathBuf};
+use log::info;
+use std::path::Path;
 
 pub(crate) struct Application {
     pub exe: String,
     pub data: Vec<u8>,
+    /// The icon file name and data, installed next to the executable
+    pub icon: Option<(String, Vec<u8>)>,
     pub size: u64,
 }
 
 impl Bundle for Application {
     fn load(package: &SetupPackage) -> Self {
         let data = package.get_application();
+        let icon = package.get_icon();
+        let icon_size = icon.as_ref().map_or(0, |(_, icon_data)| icon_data.len());
 
         Self {
             exe: package.manifest.application.clone(),
             data: data.clone(),
-            size: data.clone().len() as u64,
+            size: (data.len() + icon_size) as u64,
+            icon,
         }
     }
 
-    fn is_installed() -> bool {
+    fn is_installed(&self) -> bool {
         false
     }
 
-    fn install(&self, _quiet: bool, path: &PathBuf) -> Result<()> {
-        println!("Starting installation!");
-
-        println!("Extracting application to installation directory...");
-        let application_path = path.join(&self.exe);
-
-        // Create and write to the application file
-        let mut app_file = fs::File::create(&application_path).with_context(|| {
-            format!(
-                "Failed to create application file at {:?}",
-                application_path
-            )
-        })?;
-        app_file.write_all(&self.data).with_context(|| {
-            format!("Failed to write application data to {:?}", application_path)
-        })?;
-        drop(app_file);
-
-        // Start the application
-        spawn_detached_process(application_path.clone())
-            .with_context(|| format!("Failed to start application at {:?}", application_path))?;
+    fn install(&self, transaction: &mut Transaction, path: &Path) -> Result<()> {
+        info!("Starting installation!");
 
+        info!("Extracting application to installation directory...");
+        transaction.write_file(&path.join(&self.exe), &self.data)?;
+        if let Some((icon, icon_data)) = &self.icon {
+            transaction.write_file(&path.join(icon), icon_data)?;
+        }
         Ok(())
     }
+
+    fn files(&self) -> Vec<(String, &[u8])> {
+        let icon = self
+            .icon
+            .iter()
+            .map(|(icon, icon_data)| (icon.clone(), icon_data.as_slice()));
+        std::iter::once((self.exe.clone(), self.data.as_slice()))
+            .chain(icon)
+            .collect()
+    }
 }
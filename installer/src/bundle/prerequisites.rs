@@ -0,0 +1,113 @@
+use crate::bundle::TempDir;
+use crate::platform::Installers;
+
+use anyhow::{anyhow, Context, Result};
+use bundler::prerequisite::{InstallOutcome, Prerequisite, SystemProbe};
+use bundler::SetupPackage;
+use log::info;
+use std::fs;
+
+/// The prerequisites from the plugin config, installed in order before the application
+pub(crate) struct Prerequisites {
+    pub items: Vec<(Prerequisite, Option<Vec<u8>>)>,
+}
+
+impl Prerequisites {
+    pub fn load(package: &SetupPackage) -> Self {
+        let items = package
+            .manifest
+            .prerequisites
+            .iter()
+            .map(|prerequisite| {
+                let data = package.get_prerequisite(prerequisite);
+                (prerequisite.clone(), data)
+            })
+            .collect();
+
+        Self { items }
+    }
+
+    /// The prerequisites that aren't installed
+    pub fn missing(&self, system: &dyn SystemProbe) -> Vec<&Prerequisite> {
+        self.items
+            .iter()
+            .filter(|(prerequisite, _)| {
+                let installed = prerequisite.is_installed(system);
+                info!(
+                    "Prerequisite {} installed: {}",
+                    prerequisite.name, installed
+                );
+                !installed
+            })
+            .map(|(prerequisite, _)| prerequisite)
+            .collect()
+    }
+
+    /// Installs the prerequisites that aren't installed, in order, reporting if any need a reboot
+    pub fn install_missing(
+        &self,
+        system: &dyn SystemProbe,
+        installers: &dyn Installers,
+    ) -> Result<InstallOutcome> {
+        let mut outcome = InstallOutcome::Installed;
+        for (prerequisite, data) in &self.items {
+            if prerequisite.is_installed(system) {
+                info!("{} is already installed", prerequisite.title());
+                continue;
+            }
+
+            info!("Installing {}...", prerequisite.title());
+            if install_prerequisite(prerequisite, data.as_deref(), installers)?
+                == InstallOutcome::RebootRequired
+            {
+                info!(
+                    "{} requires a reboot to complete installation",
+                    prerequisite.title()
+                );
+                outcome = InstallOutcome::RebootRequired;
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
+/// Writes or downloads the installer to a new temp directory and runs it
+fn install_prerequisite(
+    prerequisite: &Prerequisite,
+    data: Option<&[u8]>,
+    installers: &dyn Installers,
+) -> Result<InstallOutcome> {
+    prerequisite.validate()?;
+
+    // Removed when returning, whether or not the installer ran
+    let installer_dir = TempDir::new(&prerequisite.name)?;
+    let installer_path = installer_dir.path().join(prerequisite.installer_filename());
+
+    match (data, &prerequisite.url) {
+        (Some(data), _) => fs::write(&installer_path, data)
+            .with_context(|| format!("Failed to write installer {:?}", installer_path))?,
+        (None, Some(url)) => {
+            info!("Downloading installer: {}", url);
+            installers.download(url, &installer_path)?;
+        }
+        (None, None) => {
+            return Err(anyhow!(
+                "{} installer is not bundled.",
+                prerequisite.title()
+            ))
+        }
+    }
+
+    // Run the installer
+    let (program, args) = prerequisite.command(&installer_path);
+    info!("Running installer: '{:?}', args={:?}", program, args);
+    let exit_code = installers.run(&program, &args)?;
+    info!(
+        "{} installer exited with code {}",
+        prerequisite.title(),
+        exit_code
+    );
+
+    prerequisite.check_exit_code(exit_code)
+}
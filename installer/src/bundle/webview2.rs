@@ -1,74 +1,164 @@
-use crate::bundle::Bundle;
+use crate::bundle::{Bundle, TempDir};
+use crate::platform::Installers;
+use crate::transaction::Transaction;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use bundler::plugin_config::Webview2Bundle;
+use bundler::version::meets_min_version;
+use bundler::webview2::{WEBVIEW2_EVERGREEN_URL, WEBVIEW2_FIXED_RUNTIME_DIR};
 use bundler::SetupPackage;
-use std::env;
+use log::info;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command as Process;
+use std::path::Path;
 
 pub(crate) struct WebView2 {
+    pub mode: Webview2Bundle,
+    pub silent: bool,
     pub bundled: bool,
     pub exe: String,
     pub data: Option<Vec<u8>>,
+    pub fixed_runtime: Vec<(String, Vec<u8>)>,
+    /// Minimum runtime version, an older runtime is upgraded
+    pub min_version: Option<String>,
+    /// Version of the installed runtime, once detected
+    pub version: Option<String>,
+    /// Whether a runtime of at least the minimum version is installed, once detected
     pub installed: bool,
 }
 
 impl Bundle for WebView2 {
     fn load(package: &SetupPackage) -> Self {
+        let mode = package.webview2_mode();
+        let install = package.manifest.webview2.as_ref();
+        let silent = install.is_some_and(|webview2| webview2.silent);
+        let min_version = install.and_then(|webview2| webview2.min_version.clone());
         let data = package.get_webview2();
-        let bundled = data.is_some();
+        let fixed_runtime = package.get_webview2_fixed_runtime();
+        let bundled = data.is_some() || !fixed_runtime.is_empty();
         let exe = package.webview2_filename();
-        let installed = Self::is_installed();
 
         Self {
+            mode,
+            silent,
             bundled,
             exe,
             data,
-            installed,
+            fixed_runtime,
+            min_version,
+            version: None,
+            installed: false,
         }
     }
 
-    fn is_installed() -> bool {
-        use webview2_com::{Microsoft::Web::WebView2::Win32::*, *};
-        use windows::core::{PCWSTR, PWSTR};
-        let mut versioninfo = PWSTR::null();
-        let result = unsafe {
-            GetAvailableCoreWebView2BrowserVersionString(PCWSTR::null(), &mut versioninfo)
-        };
+    fn is_installed(&self) -> bool {
+        self.installed
+    }
+
+    /// Extracts the fixed version runtime next to the application, other modes use the system runtime
+    fn install(&self, transaction: &mut Transaction, path: &Path) -> Result<()> {
+        if self.mode != Webview2Bundle::FixedRuntime {
+            return Ok(());
+        }
+
+        info!("Extracting WebView2 fixed runtime...");
+        let runtime_path = path.join(WEBVIEW2_FIXED_RUNTIME_DIR);
+        for (name, data) in &self.fixed_runtime {
+            transaction.write_file(&runtime_path.join(name), data)?;
+        }
+
+        Ok(())
+    }
 
-        if result.is_err() || versioninfo == PWSTR::null() {
-            return false;
+    fn files(&self) -> Vec<(String, &[u8])> {
+        if self.mode != Webview2Bundle::FixedRuntime {
+            return Vec::new();
         }
 
-        let version = take_pwstr(versioninfo);
-        if version.len() > 0 {
-            println!("WebView2 version: {}", version);
-            return true;
+        self.fixed_runtime
+            .iter()
+            .map(|(name, data)| {
+                (
+                    format!("{}/{}", WEBVIEW2_FIXED_RUNTIME_DIR, name),
+                    data.as_slice(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl WebView2 {
+    /// Detects the installed runtime, the fixed version runtime is used regardless of it
+    pub fn detect(&mut self, installers: &dyn Installers) {
+        if !uses_system_runtime(self.mode) {
+            return;
+        }
+
+        self.version = installers.webview2_version();
+        self.installed = self
+            .version
+            .as_deref()
+            .is_some_and(|version| is_supported_version(version, self.min_version.as_deref()));
+    }
+
+    /// Installs the system wide runtime with the bootstrapper or offline installer
+    pub fn install_runtime(&self, quiet: bool, installers: &dyn Installers) -> Result<()> {
+        match self.mode {
+            Webview2Bundle::Skip | Webview2Bundle::FixedRuntime => Ok(()),
+            Webview2Bundle::DownloadBootstrapper => {
+                let installer_dir = TempDir::new("webview2")?;
+                let installer_path = installer_dir.path().join(&self.exe);
+                info!("Downloading installer: {}", WEBVIEW2_EVERGREEN_URL);
+                installers.download(WEBVIEW2_EVERGREEN_URL, &installer_path)?;
+                self.run_installer(quiet, &installer_path, installers)?;
+                self.check_installed_version(installers)
+            }
+            Webview2Bundle::EmbedBootstrapper | Webview2Bundle::OfflineInstaller => {
+                let data = self
+                    .data
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("WebView2 installer is not bundled."))?;
+
+                // Copy the installer to a new temp directory, removed once it has run
+                let installer_dir = TempDir::new("webview2")?;
+                let installer_path = installer_dir.path().join(&self.exe);
+                fs::write(&installer_path, data)
+                    .with_context(|| format!("Failed to write installer {:?}", installer_path))?;
+                self.run_installer(quiet, &installer_path, installers)?;
+                self.check_installed_version(installers)
+            }
         }
+    }
+
+    /// Whether the application uses the system wide WebView2 runtime, which must be installed first
+    pub fn uses_system_runtime(&self) -> bool {
+        uses_system_runtime(self.mode)
+    }
 
-        false
+    /// Size of the fixed version runtime extracted next to the application
+    pub fn fixed_runtime_size(&self) -> u64 {
+        self.fixed_runtime
+            .iter()
+            .map(|(_, data)| data.len() as u64)
+            .sum()
     }
 
-    fn install(&self, quiet: bool, _path: &PathBuf) -> Result<()> {
-        let args = if quiet {
-            vec!["/silent", "/install"]
+    /// Runs the bootstrapper or offline installer, both take the same `/silent /install` flags
+    fn run_installer(
+        &self,
+        quiet: bool,
+        installer_path: &Path,
+        installers: &dyn Installers,
+    ) -> Result<()> {
+        let args = if quiet || self.silent {
+            vec!["/silent".to_string(), "/install".to_string()]
         } else {
-            vec!["/install"]
+            vec!["/install".to_string()]
         };
 
-        // Copy the installer to a temp location
-        let temp_dir = env::temp_dir();
-        let installer_path = temp_dir.join(self.exe.clone());
-        fs::write(&installer_path, self.data.as_ref().unwrap()).expect("Failed to write installer");
-
         // Run the installer
-        println!("Running installer: '{:?}', args={:?}", installer_path, args);
-        let mut cmd = Process::new(installer_path).args(&args).spawn()?;
-        let result: i32 = cmd
-            .wait()?
-            .code()
-            .ok_or_else(|| anyhow!("Unable to get installer exit code."))?;
+        info!("Running installer: '{:?}', args={:?}", installer_path, args);
+        let result = installers.run(installer_path, &args)?;
+        info!("WebView2 installer exited with code {}", result);
 
         match result {
             0 => Ok(()),           // success
@@ -76,4 +166,33 @@ impl Bundle for WebView2 {
             _ => Err(anyhow!("Installer failed with exit code: {}", result)),
         }
     }
+
+    /// Checks the installer upgraded the runtime to at least the minimum version
+    fn check_installed_version(&self, installers: &dyn Installers) -> Result<()> {
+        let Some(min_version) = &self.min_version else {
+            return Ok(());
+        };
+
+        match installers.webview2_version() {
+            Some(version) if is_supported_version(&version, Some(min_version)) => Ok(()),
+            Some(version) => Err(anyhow!(
+                "WebView2 runtime {} is older than the required version {}",
+                version,
+                min_version
+            )),
+            None => Err(anyhow!("WebView2 runtime was not installed")),
+        }
+    }
+}
+
+/// Whether the runtime version is new enough, any version is when there's no minimum
+fn is_supported_version(version: &str, min_version: Option<&str>) -> bool {
+    match min_version {
+        Some(min_version) => meets_min_version(version, min_version),
+        None => true,
+    }
+}
+
+fn uses_system_runtime(mode: Webview2Bundle) -> bool {
+    !matches!(mode, Webview2Bundle::Skip | Webview2Bundle::FixedRuntime)
 }
@@ -1,18 +1,20 @@
 use windows::core::PCWSTR;
 use windows::Win32::Foundation::HWND;
 use windows::Win32::UI::WindowsAndMessaging::{
-    MessageBoxW, IDYES, MB_ICONERROR, MB_ICONQUESTION, MB_OK, MB_YESNO,
+    MessageBoxW, IDYES, MB_ICONERROR, MB_ICONINFORMATION, MB_ICONQUESTION, MB_OK, MB_YESNO,
 };
 
+use crate::logging::log_path;
 use crate::to_wide::ToWide;
 
-pub fn show_overwrite_repair_dialog(title: &str, name: &str, version: &str, silent: bool) -> bool {
+/// Asks to repair or replace the existing installation, accepted without asking when silent
+pub fn show_overwrite_dialog(title: &str, version: &str, message: &str, silent: bool) -> bool {
     if silent {
         return true;
     }
 
     let lp_title = format!("{} Setup ({})", title, version).to_wide_null();
-    let lp_text = format!("{} is already installed.\nThis application is installed on your computer. If it is not functioning correctly, you can attempt to repair it.\n\nDo you want to attempt to repair it?", name).to_wide_null();
+    let lp_text = message.to_wide_null();
 
     let result = unsafe {
         MessageBoxW(
@@ -26,7 +28,74 @@ pub fn show_overwrite_repair_dialog(title: &str, name: &str, version: &str, sile
     return result == IDYES;
 }
 
-pub fn show_error_dialog(title: &str, message: &str) {
+/// Asks to install the missing prerequisites, accepted without asking when silent
+pub fn show_prerequisites_dialog(
+    title: &str,
+    version: &str,
+    prerequisites: &[String],
+    silent: bool,
+) -> bool {
+    if silent {
+        return true;
+    }
+
+    let lp_title = format!("{} Setup ({})", title, version).to_wide_null();
+    let lp_text = format!(
+        "{} requires the following components, which will be installed:\n\n{}\n\nDo you want to continue?",
+        title,
+        prerequisites
+            .iter()
+            .map(|prerequisite| format!("- {}", prerequisite))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+    .to_wide_null();
+
+    let result = unsafe {
+        MessageBoxW(
+            HWND(std::ptr::null_mut()),
+            PCWSTR(lp_text.as_ptr()),
+            PCWSTR(lp_title.as_ptr()),
+            MB_YESNO | MB_ICONQUESTION,
+        )
+    };
+
+    return result == IDYES;
+}
+
+pub fn show_info_dialog(title: &str, message: &str, silent: bool) {
+    if silent {
+        return;
+    }
+
+    let lp_title = title.to_wide_null();
+    let lp_message = message.to_wide_null();
+
+    unsafe {
+        MessageBoxW(
+            HWND(std::ptr::null_mut()),
+            PCWSTR(lp_message.as_ptr()),
+            PCWSTR(lp_title.as_ptr()),
+            MB_OK | MB_ICONINFORMATION,
+        )
+    };
+}
+
+pub fn show_error_dialog(title: &str, message: &str, silent: bool) {
+    if silent {
+        return;
+    }
+
+    // Point at the log file for the details
+    let message = match log_path() {
+        Some(log_path) => format!(
+            "{}\n\nSee the log file for details:\n{}",
+            message,
+            log_path.display()
+        ),
+        None => message.to_string(),
+    };
+
     // Convert the title and message to wide strings
     let lp_title = title.to_wide_null();
     let lp_message = message.to_wide_null();
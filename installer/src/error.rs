@@ -0,0 +1,177 @@
+use crate::verify::VerifyReport;
+
+use std::fmt;
+
+/// Why an install didn't complete, each with a documented exit code for deployment tools
+#[derive(Debug)]
+pub enum InstallError {
+    /// The installation failed, for a reason without a more specific code
+    Failed(anyhow::Error),
+    /// The user cancelled the installation, or declined the prerequisites
+    Cancelled,
+    /// There isn't enough free disk space in the installation directory
+    InsufficientDiskSpace { required: u64, available: u64 },
+    /// A prerequisite or WebView2 installer failed
+    PrerequisiteFailed(anyhow::Error),
+    /// The package bundled in the setup executable is truncated or corrupt
+    CorruptPackage(anyhow::Error),
+    /// The existing installation couldn't be replaced, as its files are in use
+    FilesInUse(anyhow::Error),
+    /// The installation failed, and the previous installation was restored
+    RolledBack(anyhow::Error),
+    /// Prerequisites are missing, and `--silent` was passed without `--accept-prereqs`
+    PrerequisitesNotAccepted(Vec<String>),
+    /// A newer version is installed, and downgrades aren't allowed
+    DowngradeBlocked { installed: String, version: String },
+    /// `--verify` found missing or modified files
+    VerificationFailed(VerifyReport),
+    /// The setup relaunched elevated to install per-machine failed, with its exit code
+    ElevatedSetupFailed(i32),
+}
+
+/// The prerequisites were installed, but need a reboot to finish (`ERROR_SUCCESS_REBOOT_REQUIRED`)
+pub const EXIT_CODE_REBOOT_REQUIRED: i32 = 3010;
+
+impl InstallError {
+    /// The process exit code, documented in the README
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            InstallError::Failed(_) => 1,
+            InstallError::Cancelled => 2,
+            InstallError::InsufficientDiskSpace { .. } => 3,
+            InstallError::PrerequisiteFailed(_) => 4,
+            InstallError::CorruptPackage(_) => 5,
+            InstallError::FilesInUse(_) => 6,
+            InstallError::RolledBack(_) => 7,
+            InstallError::PrerequisitesNotAccepted(_) => 8,
+            InstallError::DowngradeBlocked { .. } => 9,
+            InstallError::VerificationFailed(_) => 10,
+            InstallError::ElevatedSetupFailed(exit_code) => *exit_code,
+        }
+    }
+
+    /// Title of the error dialog
+    pub fn title(&self) -> &'static str {
+        match self {
+            InstallError::Cancelled => "Installation cancelled",
+            InstallError::InsufficientDiskSpace { .. } => "Not enough disk space",
+            InstallError::PrerequisiteFailed(_) => "Prerequisite installation failed",
+            InstallError::CorruptPackage(_) => "Setup is corrupt",
+            InstallError::FilesInUse(_) => "Files in use",
+            InstallError::DowngradeBlocked { .. } => "Newer version installed",
+            InstallError::VerificationFailed(_) => "Installation is damaged",
+            _ => "Installation failed",
+        }
+    }
+}
+
+impl fmt::Display for InstallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallError::Failed(e) => write!(f, "Installation failed: {:#}", e),
+            InstallError::Cancelled => write!(f, "Installation was cancelled"),
+            InstallError::InsufficientDiskSpace {
+                required,
+                available,
+            } => write!(
+                f,
+                "At least {} disk space is required to install, there is only {} available",
+                crate::format_bytes(*required),
+                crate::format_bytes(*available)
+            ),
+            InstallError::PrerequisiteFailed(e) => {
+                write!(f, "A prerequisite failed to install: {:#}", e)
+            }
+            InstallError::CorruptPackage(e) => write!(
+                f,
+                "The setup executable is corrupt, please download it again: {:#}",
+                e
+            ),
+            InstallError::FilesInUse(e) => write!(
+                f,
+                "The existing installation is in use, close the application and try again: {:#}",
+                e
+            ),
+            InstallError::RolledBack(e) => write!(
+                f,
+                "Installation failed, the previous installation was restored: {:#}",
+                e
+            ),
+            InstallError::PrerequisitesNotAccepted(prerequisites) => write!(
+                f,
+                "Missing prerequisites: {}. Pass --accept-prereqs to install them silently",
+                prerequisites.join(", ")
+            ),
+            InstallError::DowngradeBlocked { installed, version } => write!(
+                f,
+                "Version {} is already installed, which is newer than {}. Pass --allow-downgrade to replace it",
+                installed, version
+            ),
+            InstallError::VerificationFailed(report) => write!(
+                f,
+                "The installation is damaged, run setup with --repair to fix it.\n\n{}",
+                report
+            ),
+            InstallError::ElevatedSetupFailed(exit_code) => write!(
+                f,
+                "The elevated setup failed with exit code {}",
+                exit_code
+            ),
+        }
+    }
+}
+
+impl From<anyhow::Error> for InstallError {
+    fn from(error: anyhow::Error) -> Self {
+        InstallError::Failed(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        // ElevatedSetupFailed passes the exit code of the elevated setup through
+        let errors = [
+            InstallError::Failed(anyhow!("failed")),
+            InstallError::Cancelled,
+            InstallError::InsufficientDiskSpace {
+                required: 2048,
+                available: 1024,
+            },
+            InstallError::PrerequisiteFailed(anyhow!("failed")),
+            InstallError::CorruptPackage(anyhow!("failed")),
+            InstallError::FilesInUse(anyhow!("failed")),
+            InstallError::RolledBack(anyhow!("failed")),
+            InstallError::PrerequisitesNotAccepted(vec!["WebView2".to_string()]),
+            InstallError::DowngradeBlocked {
+                installed: "2.0.0".to_string(),
+                version: "1.0.0".to_string(),
+            },
+            InstallError::VerificationFailed(VerifyReport::default()),
+        ];
+
+        let mut codes: Vec<i32> = errors.iter().map(InstallError::exit_code).collect();
+        codes.push(EXIT_CODE_REBOOT_REQUIRED);
+        let count = codes.len();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), count);
+        assert!(!codes.contains(&0));
+    }
+
+    #[test]
+    fn test_display() {
+        let error = InstallError::InsufficientDiskSpace {
+            required: 2048,
+            available: 1024,
+        };
+        assert_eq!(
+            error.to_string(),
+            "At least 2.00 KB disk space is required to install, there is only 1.00 KB available"
+        );
+    }
+}
@@ -0,0 +1,2286 @@
+use crate::bundle::{Application, Bundle, Prerequisites, WebView2};
+use crate::error::{InstallError, EXIT_CODE_REBOOT_REQUIRED};
+use crate::format_bytes;
+use crate::lnk::ShellLink;
+use crate::platform::{Platform, RegistryValue};
+use crate::transaction::{journal_path, remove_leftover_backups, Operation, Recovery, Transaction};
+use crate::verify::{installed_path, relative_path, verify_files, VerifyReport};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bundler::file_association::AssociationPlan;
+use bundler::install_dir::{
+    default_install_dir, expand_install_dir, install_dir_policy_key, INSTALL_DIR_POLICY_VALUE,
+};
+use bundler::prerequisite::InstallOutcome;
+use bundler::prerequisite::RegistryRoot;
+use bundler::receipt::{
+    InstallReceipt, ReceiptFile, ReceiptRegistryValue, INSTALL_RECEIPT_VERSION,
+};
+use bundler::scope::Scope;
+use bundler::shortcut::{shortcut_file_name, APP_USER_MODEL_ID_KEY};
+use bundler::webview2::WEBVIEW2_PREREQUISITE;
+use bundler::{SetupManifest, SetupPackage};
+use chrono::prelude::*;
+use log::{error, info, warn};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use semver::Version;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+
+pub const UNINSTALL_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
+
+/// How to install, from the command line
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    /// Install without any dialogs
+    pub silent: bool,
+    /// Directory to install to, instead of `%LOCALAPPDATA%\{identifier}`
+    pub install_dir: Option<PathBuf>,
+    /// Start the application once it's installed
+    pub launch: bool,
+    /// Overwrite an existing installation without asking
+    pub force: bool,
+    /// Install missing prerequisites without asking
+    pub accept_prereqs: bool,
+    /// Replace a newer installed version, even if the package doesn't allow downgrades
+    pub allow_downgrade: bool,
+    /// Who to install for, from `--per-user` or `--per-machine`
+    pub scope: Option<Scope>,
+}
+
+/// What's in the installation directory before installing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExistingInstall {
+    /// Nothing, the directory doesn't exist or is empty
+    None,
+    /// An installation of the application, with its install receipt
+    Receipt,
+    /// An installation of the application without an install receipt, in the `InstallLocation` of
+    /// its uninstall entry
+    UninstallEntry,
+}
+
+/// How the package's version compares to the existing installation
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstallKind {
+    /// Nothing is installed
+    Fresh,
+    /// An older version is installed, it's replaced without asking
+    Upgrade { installed: String },
+    /// The same version is installed, asks to repair it
+    Repair,
+    /// A newer version is installed, blocked unless downgrades are allowed
+    Downgrade { installed: String },
+    /// Something is installed, but its version is missing or isn't semver, asks to overwrite it
+    Unknown,
+}
+
+impl InstallKind {
+    /// Compares the installed version with the package version using semver
+    pub fn detect(installed: Option<&str>, version: &str) -> Self {
+        let parse = |version: &str| Version::parse(version.trim().trim_start_matches(['v', 'V']));
+        let Some(installed) = installed else {
+            return InstallKind::Unknown;
+        };
+        match (parse(installed), parse(version)) {
+            (Ok(a), Ok(b)) if a < b => InstallKind::Upgrade {
+                installed: installed.to_string(),
+            },
+            (Ok(a), Ok(b)) if a > b => InstallKind::Downgrade {
+                installed: installed.to_string(),
+            },
+            (Ok(_), Ok(_)) => InstallKind::Repair,
+            _ => InstallKind::Unknown,
+        }
+    }
+
+    /// The question asked before replacing the installation, none when it's replaced without asking
+    pub fn confirmation(&self, name: &str, version: &str) -> Option<String> {
+        const REPAIR: &str = "This application is installed on your computer. If it is not functioning correctly, you can attempt to repair it.\n\nDo you want to attempt to repair it?";
+        match self {
+            InstallKind::Fresh | InstallKind::Upgrade { .. } => None,
+            InstallKind::Repair => Some(format!(
+                "{} {} is already installed.\n{}",
+                name, version, REPAIR
+            )),
+            InstallKind::Downgrade { installed } => Some(format!(
+                "A newer version of {} ({}) is already installed.\n\nDo you want to replace it with the older version {}?",
+                name, installed, version
+            )),
+            InstallKind::Unknown => Some(format!("{} is already installed.\n{}", name, REPAIR)),
+        }
+    }
+}
+
+/// Installs a package, overwriting and rolling back any existing installation
+pub struct Installer {
+    platform: Platform,
+    options: InstallOptions,
+}
+
+impl Installer {
+    pub fn new(platform: Platform, options: InstallOptions) -> Self {
+        Installer { platform, options }
+    }
+
+    /// Shows an error dialog, unless installing silently
+    pub fn show_error(&self, error: &InstallError) {
+        // The elevated setup has already shown its own error
+        if !self.options.silent && !matches!(error, InstallError::ElevatedSetupFailed(_)) {
+            self.platform
+                .dialogs
+                .show_error(error.title(), &error.to_string());
+        }
+    }
+
+    /// Shows information, unless installing silently
+    fn show_info(&self, title: &str, message: &str) {
+        if !self.options.silent {
+            self.platform.dialogs.show_info(title, message);
+        }
+    }
+
+    /// Installs the bundled application, mapping failures to the documented exit codes
+    pub fn install(&self, package: &SetupPackage) -> Result<InstallOutcome, InstallError> {
+        let options = &self.options;
+        let platform = &self.platform;
+        let manifest = &package.manifest;
+        info!("Application: {} {}", manifest.name, manifest.version);
+
+        // Handle bundled application
+        if !package.files.contains_key(&manifest.application) {
+            return Err(InstallError::CorruptPackage(anyhow!(
+                "Application {} is not bundled",
+                manifest.application
+            )));
+        }
+        let app = Application::load(package);
+        info!("Application size: {}", format_bytes(app.data.len() as u64));
+
+        // Handle bundled WebView2 runtime
+        let mut webview2 = WebView2::load(package);
+        webview2.detect(platform.installers.as_ref());
+        info!("Webview2 mode: {:?}", webview2.mode);
+        info!("Webview2 bundled: {}", webview2.bundled);
+
+        // Determine the installation directory
+        info!("Determining install directory...");
+        let scope = self.scope(manifest)?;
+        let root_path = self.root_path(manifest, scope)?;
+        info!("Installation Directory: {:?}", root_path);
+
+        // Finish or undo an install that was interrupted, before looking at the directory
+        self.recover(&root_path)?;
+        self.remove_leftover_backups(manifest, &root_path);
+
+        // Check if there is enough space to install the application, on the disk the directory will be
+        let required_space = app.size + webview2.fixed_runtime_size();
+        info!("Required disk space: {}", format_bytes(required_space));
+        let disk_path = root_path
+            .ancestors()
+            .find(|path| platform.fs.exists(path))
+            .unwrap_or(&root_path);
+        match platform.disk.free_space(disk_path) {
+            Ok(free_space) => {
+                if free_space < required_space {
+                    return Err(InstallError::InsufficientDiskSpace {
+                        required: required_space,
+                        available: free_space,
+                    });
+                }
+                info!(
+                    "There is {} free space available at destination, this package requires {}.",
+                    format_bytes(free_space),
+                    format_bytes(required_space)
+                );
+            }
+            Err(e) => warn!("Error: {}", e),
+        }
+
+        // Check if the application is already installed, and compare the versions
+        let existing = self.existing_install(manifest, &root_path, scope)?;
+        let is_installed = existing != ExistingInstall::None;
+        let kind = if is_installed {
+            InstallKind::detect(
+                self.installed_version(manifest, &root_path, scope)
+                    .as_deref(),
+                &manifest.version,
+            )
+        } else {
+            InstallKind::Fresh
+        };
+        info!("Install kind: {:?}", kind);
+        if is_installed {
+            self.confirm_overwrite(manifest, &kind, existing, &root_path)?;
+        }
+
+        // Prerequisites change the system, so they're only handled once nothing else can stop the
+        // install. Check if WebView2 runtime is installed, and at least the minimum version
+        let webview2_installed = webview2.is_installed();
+        info!("Webview2 installed: {}", webview2_installed);
+        let install_webview2 = webview2.uses_system_runtime() && !webview2_installed;
+
+        // Ask before installing any missing prerequisites, which are shown by their titles and
+        // recorded in the receipt by their names
+        let prerequisites = Prerequisites::load(package);
+        let missing = prerequisites.missing(platform.system.as_ref());
+        let mut missing_prerequisites: Vec<String> = missing
+            .iter()
+            .map(|prerequisite| prerequisite.title().to_string())
+            .collect();
+        let mut installed_prerequisites: Vec<String> = missing
+            .iter()
+            .map(|prerequisite| prerequisite.name.clone())
+            .collect();
+        if install_webview2 {
+            missing_prerequisites.insert(0, "Microsoft Edge WebView2 Runtime".to_string());
+            installed_prerequisites.insert(0, WEBVIEW2_PREREQUISITE.to_string());
+        }
+        if !missing_prerequisites.is_empty() && !options.accept_prereqs {
+            if options.silent {
+                return Err(InstallError::PrerequisitesNotAccepted(
+                    missing_prerequisites,
+                ));
+            }
+
+            let accepted = platform.dialogs.confirm_prerequisites(
+                &manifest.title,
+                &manifest.version,
+                &missing_prerequisites,
+            );
+            if !accepted {
+                info!("User declined to install the prerequisites");
+                return Err(InstallError::Cancelled);
+            }
+        }
+
+        if install_webview2 {
+            match (&webview2.version, &webview2.min_version) {
+                (Some(version), Some(min_version)) => info!(
+                    "Upgrading webview2 runtime from {} to at least {}...",
+                    version, min_version
+                ),
+                _ => info!("Installing webview2 runtime..."),
+            }
+            webview2
+                .install_runtime(options.silent, platform.installers.as_ref())
+                .context("Failed to install the WebView2 runtime")
+                .map_err(InstallError::PrerequisiteFailed)?;
+        }
+
+        // Install the prerequisites in order, skipping those already installed
+        let outcome = prerequisites
+            .install_missing(platform.system.as_ref(), platform.installers.as_ref())
+            .context("Failed to install prerequisites")
+            .map_err(InstallError::PrerequisiteFailed)?;
+
+        // The journal is written next to the installation directory, whose parent may not exist yet
+        if let Some(parent) = root_path.parent() {
+            platform
+                .fs
+                .create_dir_all(parent)
+                .with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        let mut transaction = Transaction::begin(
+            platform.fs.as_ref(),
+            platform.registry.as_ref(),
+            &journal_path(&root_path),
+        )?;
+
+        // Move the existing installation aside, it's restored if the install fails
+        if is_installed {
+            let mut root_path_renamed = root_path.as_os_str().to_os_string();
+            root_path_renamed.push(format!("_{}", generate_random_string(8)));
+            let root_path_renamed = PathBuf::from(root_path_renamed);
+            info!(
+                "Moving existing installation to {:?} to allow rollback...",
+                root_path_renamed
+            );
+            if let Err(e) = transaction.move_dir(&root_path, &root_path_renamed) {
+                let _ = transaction.rollback();
+                return Err(InstallError::FilesInUse(e));
+            }
+        }
+
+        // Install the application, with the fixed WebView2 runtime next to it
+        let install_result = transaction
+            .create_dir(&root_path)
+            .and_then(|_| webview2.install(&mut transaction, &root_path))
+            .and_then(|_| app.install(&mut transaction, &root_path))
+            .and_then(|_| {
+                write_uninstall_entry(&mut transaction, platform, manifest, &root_path, scope)
+                    .context("Failed to write uninstall registry key")
+            })
+            .and_then(|_| {
+                write_shortcuts(&mut transaction, platform, manifest, &root_path, scope)
+                    .context("Failed to create shortcuts")
+            })
+            .and_then(|_| {
+                register_app_user_model_id(&mut transaction, manifest, &root_path, scope)
+                    .context("Failed to register the AppUserModelID")
+            })
+            .and_then(|_| {
+                write_file_associations(&mut transaction, manifest, &root_path, scope)
+                    .context("Failed to register file associations")
+            })
+            .and_then(|_| {
+                write_receipt(
+                    &mut transaction,
+                    platform,
+                    manifest,
+                    &root_path,
+                    scope,
+                    &installed_prerequisites,
+                )
+                .context("Failed to write install receipt")
+            });
+
+        // Handle rollback if installation fails
+        if let Err(install_error) = install_result {
+            error!("Installation failed! {:#}", install_error);
+            let _ = platform.processes.kill_processes_in(&root_path);
+            return Err(match transaction.rollback() {
+                Ok(_) if is_installed => InstallError::RolledBack(install_error),
+                Ok(_) => InstallError::Failed(install_error),
+                Err(e) => {
+                    error!(
+                        "Failed to roll back, it'll be finished the next time setup is run: {:#}",
+                        e
+                    );
+                    InstallError::Failed(install_error)
+                }
+            });
+        }
+
+        transaction
+            .commit()
+            .context("Failed to remove the previous installation")?;
+        if !manifest.file_associations.is_empty() {
+            platform.shell.notify_associations_changed();
+        }
+        info!("Installation completed successfully!");
+
+        // The application is installed, so failing to start it doesn't fail the install
+        if let Err(e) = self.launch(&root_path.join(&app.exe)) {
+            warn!("{:#}", e);
+        }
+
+        Ok(outcome)
+    }
+
+    /// Compares the installed files with the package, or with the install receipt when another
+    /// version is installed
+    pub fn verify(&self, package: &SetupPackage) -> Result<VerifyReport, InstallError> {
+        let manifest = &package.manifest;
+        let scope = self.scope(manifest)?;
+        let root_path = self.root_path(manifest, scope)?;
+        self.ensure_installed(manifest, &root_path, scope)?;
+
+        let expected = match self.receipt(&root_path) {
+            Some(receipt) if receipt.manifest.version != manifest.version => {
+                info!(
+                    "Version {} is installed, verifying against its install receipt",
+                    receipt.manifest.version
+                );
+                receipt.files
+            }
+            _ => package_files(package)
+                .iter()
+                .map(|(path, data)| ReceiptFile::new(path, data))
+                .collect(),
+        };
+        let report = verify_files(self.platform.fs.as_ref(), &root_path, &expected);
+        info!(
+            "Verified {} files in {:?}: {}",
+            expected.len(),
+            root_path,
+            report
+        );
+        if !report.is_intact() {
+            return Err(InstallError::VerificationFailed(report));
+        }
+
+        self.show_info(
+            &manifest.title,
+            &format!("{} is installed correctly.", manifest.name),
+        );
+        Ok(report)
+    }
+
+    /// Rewrites the missing and modified files of the installation, leaving any other files alone
+    pub fn repair(&self, package: &SetupPackage) -> Result<VerifyReport, InstallError> {
+        let manifest = &package.manifest;
+        let scope = self.scope(manifest)?;
+        let root_path = self.root_path(manifest, scope)?;
+        self.recover(&root_path)?;
+        self.remove_leftover_backups(manifest, &root_path);
+        self.ensure_installed(manifest, &root_path, scope)?;
+        if let Some(receipt) = self.receipt(&root_path) {
+            if receipt.manifest.version != manifest.version {
+                return Err(InstallError::Failed(anyhow!(
+                    "Version {} is installed, run setup without --repair to install {}",
+                    receipt.manifest.version,
+                    manifest.version
+                )));
+            }
+        }
+
+        let files = package_files(package);
+        let expected: Vec<ReceiptFile> = files
+            .iter()
+            .map(|(path, data)| ReceiptFile::new(path, data))
+            .collect();
+        let report = verify_files(self.platform.fs.as_ref(), &root_path, &expected);
+        info!(
+            "Verified {} files in {:?}: {}",
+            expected.len(),
+            root_path,
+            report
+        );
+        if report.is_intact() {
+            info!("Nothing to repair");
+            self.show_info(
+                &manifest.title,
+                &format!(
+                    "{} is installed correctly, nothing needed repairing.",
+                    manifest.name
+                ),
+            );
+            return Ok(report);
+        }
+
+        // Only stop the application when its files are about to be replaced
+        if let Err(e) = self.platform.processes.kill_processes_in(&root_path) {
+            warn!("Failed to terminate processes: {}", e);
+        }
+
+        // An interrupted repair removes the rewritten files, which were damaged anyway
+        let mut transaction = Transaction::begin(
+            self.platform.fs.as_ref(),
+            self.platform.registry.as_ref(),
+            &journal_path(&root_path),
+        )?;
+        for path in report.damaged() {
+            let Some((_, data)) = files.iter().find(|(file, _)| file == path) else {
+                continue;
+            };
+            info!("Repairing {}", path);
+            if let Err(e) = transaction.write_file(&installed_path(&root_path, path), data) {
+                error!("Repair failed! {:#}", e);
+                let _ = transaction.rollback();
+                return Err(InstallError::FilesInUse(e));
+            }
+        }
+        transaction.commit()?;
+        info!("Repair completed successfully!");
+
+        self.show_info(
+            &manifest.title,
+            &format!("{} was repaired.\n\n{}", manifest.name, report),
+        );
+        Ok(report)
+    }
+
+    /// Fails unless the application is installed in the installation directory
+    fn ensure_installed(
+        &self,
+        manifest: &SetupManifest,
+        root_path: &Path,
+        scope: Scope,
+    ) -> Result<()> {
+        if self.existing_install(manifest, root_path, scope)? == ExistingInstall::None {
+            bail!("{} is not installed in {:?}", manifest.name, root_path);
+        }
+        Ok(())
+    }
+
+    /// The install receipt in the installation directory, if it can be read
+    fn receipt(&self, root_path: &Path) -> Option<InstallReceipt> {
+        let data = self
+            .platform
+            .fs
+            .read(&InstallReceipt::path(root_path))
+            .ok()?;
+        InstallReceipt::from_slice(&data).ok()
+    }
+
+    /// Rolls back or finishes an interrupted install
+    pub fn recover(&self, root_path: &Path) -> Result<Option<Recovery>> {
+        let platform = &self.platform;
+        let journal_path = journal_path(root_path);
+        if platform.fs.exists(&journal_path) {
+            // The interrupted install may have launched the application
+            let _ = platform.processes.kill_processes_in(root_path);
+        }
+
+        let recovery = Transaction::recover(
+            platform.fs.as_ref(),
+            platform.registry.as_ref(),
+            &journal_path,
+        )
+        .context("Failed to recover the interrupted install")?;
+        match recovery {
+            Some(Recovery::RolledBack) => info!("Rolled back the interrupted install"),
+            Some(Recovery::Finished) => info!("Finished the interrupted install"),
+            None => {}
+        }
+
+        Ok(recovery)
+    }
+
+    /// Removes the backups left next to the installation directory by versions of setup before the
+    /// install journal, which only installed to `%LOCALAPPDATA%\{identifier}`. Backups elsewhere
+    /// are always in a journal, so similarly named directories next to other install directories
+    /// are never removed.
+    fn remove_leftover_backups(&self, manifest: &SetupManifest, root_path: &Path) {
+        let Ok(local_app_data) = self.platform.folders.local_app_data() else {
+            return;
+        };
+        if root_path == local_app_data.join(&manifest.identifier) {
+            remove_leftover_backups(self.platform.fs.as_ref(), root_path, &manifest.application);
+        }
+    }
+
+    /// Who to install for, from `installScope`, the command line and any existing installation
+    pub fn scope(&self, manifest: &SetupManifest) -> Result<Scope, InstallError> {
+        let installed = [Scope::PerUser, Scope::PerMachine]
+            .into_iter()
+            .find(|scope| {
+                self.platform
+                    .registry
+                    .value(
+                        scope.registry_root(),
+                        &uninstall_key(manifest),
+                        "UninstallString",
+                    )
+                    .is_some()
+            });
+        let scope = manifest
+            .install_scope
+            .resolve(self.options.scope, installed)?;
+        info!(
+            "Install scope: {:?} (allowed {:?}, installed {:?})",
+            scope, manifest.install_scope, installed
+        );
+        Ok(scope)
+    }
+
+    /// Whether setup has to run elevated, to install per-machine
+    pub fn needs_elevation(&self, manifest: &SetupManifest) -> Result<bool, InstallError> {
+        Ok(self.scope(manifest)? == Scope::PerMachine && !self.platform.elevation.is_elevated())
+    }
+
+    /// Installs per-machine by running setup again elevated, then starts the application unelevated
+    pub fn install_elevated(
+        &self,
+        manifest: &SetupManifest,
+    ) -> Result<InstallOutcome, InstallError> {
+        info!("Relaunching setup elevated to install per-machine...");
+        let exit_code = self
+            .platform
+            .elevation
+            .relaunch_elevated(&[Scope::PerMachine.flag(), "--no-launch"])?;
+        info!("Elevated setup exited with code {:?}", exit_code);
+        let outcome = match exit_code {
+            None => return Err(InstallError::Cancelled),
+            Some(0) => InstallOutcome::Installed,
+            Some(EXIT_CODE_REBOOT_REQUIRED) => InstallOutcome::RebootRequired,
+            Some(exit_code) => return Err(InstallError::ElevatedSetupFailed(exit_code)),
+        };
+
+        // The application is installed, so failing to start it doesn't fail the install
+        let root_path = self.root_path(manifest, Scope::PerMachine)?;
+        if let Err(e) = self.launch(&root_path.join(&manifest.application)) {
+            warn!("{:#}", e);
+        }
+        Ok(outcome)
+    }
+
+    /// The installation directory, from the first of:
+    /// 1. The `InstallDir` policy value set by admins for the application
+    /// 2. `--install-dir`
+    /// 3. The `InstallLocation` of an existing installation
+    /// 4. The `installDir` template of the package, or the default of the install scope
+    fn root_path(&self, manifest: &SetupManifest, scope: Scope) -> Result<PathBuf> {
+        let registry = &self.platform.registry;
+        let policy_key = install_dir_policy_key(&manifest.identifier);
+        if let Some(RegistryValue::String(template)) = registry.value(
+            RegistryRoot::LocalMachine,
+            &policy_key,
+            INSTALL_DIR_POLICY_VALUE,
+        ) {
+            if self.options.install_dir.is_some() {
+                warn!("Ignoring --install-dir, the install directory is set by policy");
+            }
+            return self
+                .expand_root_path(&template, manifest)
+                .context("Invalid install directory policy");
+        }
+
+        if let Some(install_dir) = &self.options.install_dir {
+            return Ok(std::env::current_dir()
+                .context("Failed to get current directory")?
+                .join(install_dir));
+        }
+
+        if let Some(RegistryValue::String(location)) = registry.value(
+            scope.registry_root(),
+            &uninstall_key(manifest),
+            "InstallLocation",
+        ) {
+            if !location.is_empty() {
+                return Ok(PathBuf::from(location));
+            }
+        }
+
+        let template = match &manifest.install_dir {
+            Some(template) => template,
+            None => default_install_dir(scope),
+        };
+        self.expand_root_path(template, manifest)
+    }
+
+    /// Expands an installation directory template, which must give an absolute path
+    fn expand_root_path(&self, template: &str, manifest: &SetupManifest) -> Result<PathBuf> {
+        let folders = &self.platform.folders;
+        let root_path = expand_install_dir(template, |name| {
+            let folder = match name {
+                "LocalAppData" => folders
+                    .local_app_data()
+                    .context("Failed to get local app data path")?,
+                "ProgramFiles" => folders
+                    .program_files()
+                    .context("Failed to get program files path")?,
+                "productName" => return Ok(manifest.name.clone()),
+                _ => return Ok(manifest.identifier.clone()),
+            };
+            Ok(folder.to_string_lossy().into_owned())
+        })?;
+
+        // Templates are Windows paths, the separators are converted for the fakes on other OSes
+        let root_path = PathBuf::from(root_path.replace('\\', MAIN_SEPARATOR_STR));
+        if !root_path.has_root() {
+            bail!("Install directory {:?} isn't an absolute path", root_path);
+        }
+        Ok(root_path)
+    }
+
+    /// What the installation directory holds, failing if it has files that aren't an installation
+    /// of the application, so they're never replaced
+    fn existing_install(
+        &self,
+        manifest: &SetupManifest,
+        root_path: &Path,
+        scope: Scope,
+    ) -> Result<ExistingInstall> {
+        if !self.platform.fs.exists(root_path) {
+            return Ok(ExistingInstall::None);
+        }
+        let entries =
+            self.platform.fs.read_dir(root_path).with_context(|| {
+                format!("Failed to read installation directory {:?}", root_path)
+            })?;
+        if entries.is_empty() {
+            return Ok(ExistingInstall::None);
+        }
+
+        if let Some(receipt) = self.receipt(root_path) {
+            if receipt.manifest.identifier == manifest.identifier {
+                return Ok(ExistingInstall::Receipt);
+            }
+        }
+
+        // Installed by a version of setup that didn't write a receipt
+        if let Some(RegistryValue::String(location)) = self.platform.registry.value(
+            scope.registry_root(),
+            &uninstall_key(manifest),
+            "InstallLocation",
+        ) {
+            if Path::new(&location) == root_path {
+                return Ok(ExistingInstall::UninstallEntry);
+            }
+        }
+
+        bail!(
+            "{:?} isn't empty and isn't an installation of {}, choose another install directory",
+            root_path,
+            manifest.name
+        )
+    }
+
+    /// The version in the install receipt, or the `DisplayVersion` of the uninstall entry
+    fn installed_version(
+        &self,
+        manifest: &SetupManifest,
+        root_path: &Path,
+        scope: Scope,
+    ) -> Option<String> {
+        if let Some(receipt) = self.receipt(root_path) {
+            return Some(receipt.manifest.version);
+        }
+
+        match self.platform.registry.value(
+            scope.registry_root(),
+            &uninstall_key(manifest),
+            "DisplayVersion",
+        ) {
+            Some(RegistryValue::String(version)) => Some(version),
+            _ => None,
+        }
+    }
+
+    /// Asks to overwrite the existing installation, and stops the application if it's running.
+    ///
+    /// `--force` and `--silent` only skip asking when the installation has an install receipt.
+    fn confirm_overwrite(
+        &self,
+        manifest: &SetupManifest,
+        kind: &InstallKind,
+        existing: ExistingInstall,
+        root_path: &Path,
+    ) -> Result<(), InstallError> {
+        if let InstallKind::Downgrade { installed } = kind {
+            if !manifest.allow_downgrades && !self.options.allow_downgrade {
+                return Err(InstallError::DowngradeBlocked {
+                    installed: installed.clone(),
+                    version: manifest.version.clone(),
+                });
+            }
+        }
+
+        if let Some(message) = kind.confirmation(&manifest.name, &manifest.version) {
+            let has_receipt = existing == ExistingInstall::Receipt;
+            if self.options.silent && !has_receipt {
+                return Err(InstallError::Failed(anyhow!(
+                    "{:?} has no install receipt, run setup without --silent to confirm replacing it",
+                    root_path
+                )));
+            }
+            let result = (has_receipt && (self.options.force || self.options.silent))
+                || self.platform.dialogs.confirm_overwrite(
+                    &manifest.title,
+                    &manifest.version,
+                    &message,
+                );
+
+            if !result {
+                info!("User cancelled installation");
+                return Err(InstallError::Cancelled);
+            }
+            info!("User chose to overwrite existing installation.");
+        } else if let InstallKind::Upgrade { installed } = kind {
+            info!("Upgrading from {} to {}", installed, manifest.version);
+        }
+
+        // Force stop the application if it is running
+        match self.platform.processes.kill_processes_in(root_path) {
+            Ok(_) => info!("All processes from {:?} have been terminated.", root_path),
+            Err(e) => warn!("Failed to terminate processes: {}", e),
+        }
+
+        Ok(())
+    }
+
+    /// Starts the installed application, unless `--no-launch` was passed
+    fn launch(&self, exe_path: &Path) -> Result<()> {
+        if !self.options.launch {
+            return Ok(());
+        }
+
+        info!("About to launch: {:?}", exe_path);
+        self.platform
+            .launcher
+            .launch(exe_path)
+            .with_context(|| format!("Failed to start application at {:?}", exe_path))
+    }
+}
+
+/// Key of the Add/Remove Programs entry, in the registry hive of the install scope
+fn uninstall_key(manifest: &SetupManifest) -> String {
+    format!("{}\\{}", UNINSTALL_KEY, manifest.identifier)
+}
+
+/// Name of the application shown to users, the `--title` passed to the bundler or the product name
+fn display_name(manifest: &SetupManifest) -> &str {
+    match manifest.title.is_empty() {
+        true => &manifest.name,
+        false => &manifest.title,
+    }
+}
+
+/// Registers the application identifier as the AppUserModelID of the application, which is set on
+/// its shortcuts, so its notifications show its name and icon
+fn register_app_user_model_id(
+    transaction: &mut Transaction,
+    manifest: &SetupManifest,
+    root_path: &Path,
+    scope: Scope,
+) -> Result<()> {
+    let root = scope.registry_root();
+    let key = format!("{}\\{}", APP_USER_MODEL_ID_KEY, manifest.identifier);
+    info!("Registering AppUserModelID {:?}\\{}", root, key);
+
+    transaction.set_registry_value(root, &key, "DisplayName", display_name(manifest).into())?;
+    if let Some(icon) = &manifest.icon {
+        let icon_uri = root_path.join(icon).to_string_lossy().to_string();
+        transaction.set_registry_value(root, &key, "IconUri", icon_uri.into())?;
+    }
+    Ok(())
+}
+
+/// Registers the file types opened with the application, adding it to the `Open with` menu of
+/// their extensions
+fn write_file_associations(
+    transaction: &mut Transaction,
+    manifest: &SetupManifest,
+    root_path: &Path,
+    scope: Scope,
+) -> Result<()> {
+    let root = scope.registry_root();
+    let exe_path = root_path.join(&manifest.application);
+    let plan = AssociationPlan::new(
+        &manifest.file_associations,
+        &manifest.identifier,
+        &exe_path.to_string_lossy(),
+    );
+    for value in plan.values {
+        info!("Registering file association {:?}\\{}", root, value.key);
+        transaction.set_registry_value(root, &value.key, &value.name, value.value.into())?;
+    }
+    Ok(())
+}
+
+/// Creates the Start menu and desktop shortcuts to the application
+fn write_shortcuts(
+    transaction: &mut Transaction,
+    platform: &Platform,
+    manifest: &SetupManifest,
+    root_path: &Path,
+    scope: Scope,
+) -> Result<()> {
+    let shortcuts = &manifest.shortcuts;
+    let mut paths = Vec::new();
+    if shortcuts.start_menu {
+        let mut programs = platform.folders.programs(scope)?;
+        if let Some(folder) = &shortcuts.folder {
+            programs.push(folder);
+        }
+        paths.push(programs);
+    }
+    if shortcuts.desktop {
+        paths.push(platform.folders.desktop(scope)?);
+    }
+
+    let title = display_name(manifest);
+    let exe_path = root_path.join(&manifest.application);
+    let link = ShellLink {
+        target: exe_path.to_string_lossy().to_string(),
+        working_dir: Some(root_path.to_string_lossy().to_string()),
+        description: Some(title.to_string()),
+        icon_location: Some(exe_path.to_string_lossy().to_string()),
+        app_user_model_id: Some(manifest.identifier.clone()),
+        ..Default::default()
+    }
+    .to_bytes();
+
+    for path in paths {
+        let path = path.join(shortcut_file_name(title));
+        info!("Creating shortcut {:?}", path);
+        transaction.replace_file(&path, &link)?;
+    }
+    Ok(())
+}
+
+/// Writes the Add/Remove Programs entry of the installation
+fn write_uninstall_entry(
+    transaction: &mut Transaction,
+    platform: &Platform,
+    manifest: &SetupManifest,
+    root_path: &Path,
+    scope: Scope,
+) -> Result<()> {
+    let root = scope.registry_root();
+    let key = uninstall_key(manifest);
+    info!("Writing uninstall registry key {:?}\\{}", root, key);
+    let root_path_str = root_path.to_string_lossy().to_string();
+    let main_exe_path = root_path
+        .join(&manifest.application)
+        .to_string_lossy()
+        .to_string();
+
+    let folder_size = platform
+        .fs
+        .dir_size(root_path)
+        .with_context(|| format!("Failed to get the size of {:?}", root_path))?;
+
+    let now = Local::now();
+    let formatted_date = format!("{}{:02}{:02}", now.year(), now.month(), now.day());
+
+    // The path is quoted, as it has spaces under Program Files. The uninstaller relaunches itself
+    // elevated to remove a per-machine installation
+    let uninstall_cmd = match scope {
+        Scope::PerUser => format!("\"{}\" --uninstall", &main_exe_path),
+        Scope::PerMachine => format!("\"{}\" --uninstall {}", &main_exe_path, scope.flag()),
+    };
+
+    let values = [
+        ("DisplayIcon", format!("\"{}\"", main_exe_path).into()),
+        ("DisplayName", manifest.name.as_str().into()),
+        ("DisplayVersion", manifest.version.as_str().into()),
+        ("InstallDate", formatted_date.into()),
+        ("InstallLocation", root_path_str.clone().into()),
+        ("Publisher", "".into()), // TODO: Set publisher
+        ("UninstallString", uninstall_cmd.clone().into()),
+        ("EstimatedSize", (folder_size as u32 / 1024).into()),
+        ("NoModify", 1u32.into()),
+        ("NoRepair", 1u32.into()),
+        ("Language", 0x0409u32.into()),
+    ];
+    for (name, value) in values {
+        transaction.set_registry_value(root, &key, name, value)?;
+    }
+    info!(
+        "Wrote uninstall registry key, UninstallString={:?}, InstallLocation={:?}",
+        uninstall_cmd, root_path_str
+    );
+
+    Ok(())
+}
+
+/// Files installed from the package, by their `/` separated path relative to the installation
+/// directory
+fn package_files(package: &SetupPackage) -> Vec<(String, Vec<u8>)> {
+    let app = Application::load(package);
+    let webview2 = WebView2::load(package);
+    app.files()
+        .into_iter()
+        .chain(webview2.files())
+        .map(|(path, data)| (path, data.to_vec()))
+        .collect()
+}
+
+/// Writes the install receipt, listing the files, shortcuts and registry values of the install
+fn write_receipt(
+    transaction: &mut Transaction,
+    platform: &Platform,
+    manifest: &SetupManifest,
+    root_path: &Path,
+    scope: Scope,
+    prerequisites: &[String],
+) -> Result<()> {
+    let mut files = Vec::new();
+    let mut shortcuts = Vec::new();
+    let mut directories = Vec::new();
+    let mut registry = Vec::new();
+    for operation in transaction.operations() {
+        match operation {
+            Operation::WriteFile { path, .. } => match relative_path(root_path, path) {
+                Some(relative_path) => {
+                    // Hashed as written to disk, so verifying compares against what was installed
+                    let data = platform
+                        .fs
+                        .read(path)
+                        .with_context(|| format!("Failed to read {:?}", path))?;
+                    files.push(ReceiptFile::new(&relative_path, &data));
+                }
+                None => shortcuts.push(path.clone()),
+            },
+            Operation::SetRegistryValue {
+                root, key, name, ..
+            } => registry.push(ReceiptRegistryValue {
+                root: *root,
+                key: key.clone(),
+                name: name.clone(),
+            }),
+            Operation::CreateDirectory { path } if relative_path(root_path, path).is_none() => {
+                directories.push(path.clone())
+            }
+            Operation::MoveDirectory { .. } | Operation::CreateDirectory { .. } => {}
+        }
+    }
+
+    let receipt = InstallReceipt {
+        schema_version: INSTALL_RECEIPT_VERSION,
+        manifest: manifest.clone(),
+        installed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        installer_version: env!("CARGO_PKG_VERSION").to_string(),
+        scope,
+        files,
+        shortcuts,
+        directories,
+        registry,
+        prerequisites: prerequisites.to_vec(),
+    };
+    let receipt_path = InstallReceipt::path(root_path);
+    info!(
+        "Writing install receipt {:?} with {} files",
+        receipt_path,
+        receipt.files.len()
+    );
+    transaction.write_file(&receipt_path, &serde_json::to_vec_pretty(&receipt)?)
+}
+
+pub(crate) fn generate_random_string(length: usize) -> String {
+    let rng = thread_rng();
+    rng.sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::fake::FakePlatform;
+    use crate::platform::{FileSystem, Registry};
+    use bundler::file_association::FileAssociation;
+    use bundler::plugin_config::Webview2Bundle;
+    use bundler::prerequisite::{DetectionRule, Prerequisite};
+    use bundler::scope::InstallScope;
+    use bundler::shortcut::Shortcuts;
+    use bundler::webview2::{
+        Webview2Arch, Webview2Install, WEBVIEW2_EVERGREEN_EXE, WEBVIEW2_EVERGREEN_URL,
+    };
+    use std::collections::HashMap;
+
+    const IDENTIFIER: &str = "com.example.app";
+    const APP_DATA: &str = "/appdata";
+
+    fn package(app_data: &[u8]) -> SetupPackage {
+        let manifest = SetupManifest {
+            name: "Example".to_string(),
+            title: "Example App".to_string(),
+            version: "1.2.3".to_string(),
+            identifier: IDENTIFIER.to_string(),
+            application: "example.exe".to_string(),
+            webview2: Some(Webview2Install {
+                mode: Webview2Bundle::Skip,
+                silent: false,
+                arch: Webview2Arch::X64,
+                fixed_runtime_path: None,
+                min_version: None,
+            }),
+            prerequisites: Vec::new(),
+            allow_downgrades: false,
+            install_scope: InstallScope::PerUser,
+            install_dir: None,
+            shortcuts: Shortcuts::default(),
+            icon: None,
+            file_associations: Vec::new(),
+        };
+        let mut files = HashMap::new();
+        files.insert("example.exe".to_string(), app_data.to_vec());
+        SetupPackage { files, manifest }
+    }
+
+    fn options() -> InstallOptions {
+        InstallOptions {
+            launch: true,
+            ..Default::default()
+        }
+    }
+
+    fn install(
+        fake: &FakePlatform,
+        options: InstallOptions,
+        package: &SetupPackage,
+    ) -> Result<InstallOutcome, InstallError> {
+        Installer::new(fake.platform(), options).install(package)
+    }
+
+    #[test]
+    fn test_install() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+
+        let outcome = install(&fake, options(), &package(b"app")).unwrap();
+        assert_eq!(outcome, InstallOutcome::Installed);
+
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let exe_path = root_path.join("example.exe");
+        assert_eq!(fake.fs.read(&exe_path).unwrap(), b"app");
+        assert_eq!(*fake.launcher.launched.borrow(), vec![exe_path.clone()]);
+        assert!(fake.processes.killed.borrow().is_empty());
+        assert!(fake.dialogs.shown.borrow().is_empty());
+
+        let key = format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER);
+        let uninstall_string = format!("\"{}\" --uninstall", exe_path.to_string_lossy());
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::CurrentUser, &key, "UninstallString"),
+            Some(RegistryValue::String(uninstall_string))
+        );
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::CurrentUser, &key, "DisplayVersion"),
+            Some(RegistryValue::String("1.2.3".to_string()))
+        );
+
+        let receipt =
+            InstallReceipt::from_slice(&fake.fs.read(&InstallReceipt::path(&root_path)).unwrap())
+                .unwrap();
+        assert_eq!(receipt.manifest, package(b"app").manifest);
+        assert_eq!(receipt.files, vec![ReceiptFile::new("example.exe", b"app")]);
+        let shortcut = PathBuf::from("/Start Menu/Programs/Example App.lnk");
+        assert_eq!(fake.fs.read(&shortcut).unwrap()[..4], [0x4C, 0, 0, 0]);
+        assert_eq!(receipt.shortcuts, vec![shortcut]);
+        assert!(receipt.directories.is_empty());
+        assert!(receipt.registry.contains(&ReceiptRegistryValue {
+            root: RegistryRoot::CurrentUser,
+            key,
+            name: "UninstallString".to_string(),
+        }));
+        assert!(receipt.prerequisites.is_empty());
+    }
+
+    #[test]
+    fn test_install_shortcuts() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        package.manifest.title = "Example: App".to_string();
+        package.manifest.shortcuts = Shortcuts {
+            start_menu: true,
+            desktop: true,
+            folder: Some("Example Co".to_string()),
+        };
+
+        install(&fake, options(), &package).unwrap();
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let start_menu_folder = PathBuf::from("/Start Menu/Programs/Example Co");
+        let shortcuts = vec![
+            start_menu_folder.join("Example_ App.lnk"),
+            PathBuf::from("/Desktop/Example_ App.lnk"),
+        ];
+        let link = ShellLink {
+            target: root_path.join("example.exe").to_string_lossy().to_string(),
+            working_dir: Some(root_path.to_string_lossy().to_string()),
+            description: Some("Example: App".to_string()),
+            icon_location: Some(root_path.join("example.exe").to_string_lossy().to_string()),
+            app_user_model_id: Some(IDENTIFIER.to_string()),
+            ..Default::default()
+        };
+        for shortcut in &shortcuts {
+            assert_eq!(fake.fs.read(shortcut).unwrap(), link.to_bytes());
+        }
+
+        let receipt = Installer::new(fake.platform(), options())
+            .receipt(&root_path)
+            .unwrap();
+        assert_eq!(receipt.shortcuts, shortcuts);
+        assert_eq!(receipt.directories, vec![start_menu_folder]);
+    }
+
+    #[test]
+    fn test_install_app_user_model_id() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        package.manifest.icon = Some("icon.png".to_string());
+        package
+            .files
+            .insert("icon.png".to_string(), b"icon".to_vec());
+
+        install(&fake, options(), &package).unwrap();
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let icon_path = root_path.join("icon.png");
+        assert_eq!(fake.fs.read(&icon_path).unwrap(), b"icon");
+
+        let key = format!("{}\\{}", APP_USER_MODEL_ID_KEY, IDENTIFIER);
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::CurrentUser, &key, "DisplayName"),
+            Some(RegistryValue::String("Example App".to_string()))
+        );
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::CurrentUser, &key, "IconUri"),
+            Some(RegistryValue::String(
+                icon_path.to_string_lossy().to_string()
+            ))
+        );
+
+        let installer = Installer::new(fake.platform(), options());
+        let receipt = installer.receipt(&root_path).unwrap();
+        assert!(receipt
+            .registry_keys()
+            .contains(&(RegistryRoot::CurrentUser, key.as_str())));
+        assert!(receipt
+            .files
+            .contains(&ReceiptFile::new("icon.png", b"icon")));
+        assert!(installer.verify(&package).unwrap().is_intact());
+    }
+
+    #[test]
+    fn test_install_file_associations() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        package.manifest.file_associations = vec![FileAssociation {
+            extensions: vec!["example".to_string()],
+            name: Some("Example Document".to_string()),
+            description: None,
+            mime_type: None,
+        }];
+
+        let root = RegistryRoot::CurrentUser;
+        let default_app = RegistryValue::String("Other.Document".to_string());
+        fake.registry
+            .set_value(root, "Software\\Classes\\.example", "", default_app.clone())
+            .unwrap();
+
+        install(&fake, options(), &package).unwrap();
+        assert_eq!(fake.shell.associations_changed.get(), 1);
+
+        let prog_id = "com.example.app.ExampleDocument";
+        let prog_id_key = format!("Software\\Classes\\{}", prog_id);
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let exe_path = root_path.join("example.exe");
+        assert_eq!(
+            fake.registry.value(root, &prog_id_key, ""),
+            Some(RegistryValue::String("Example Document".to_string()))
+        );
+        assert_eq!(
+            fake.registry
+                .value(root, &format!("{}\\shell\\open\\command", prog_id_key), ""),
+            Some(RegistryValue::String(format!(
+                "\"{}\" \"%1\"",
+                exe_path.display()
+            )))
+        );
+        // The user's default application for the extension is kept
+        assert_eq!(
+            fake.registry.value(root, "Software\\Classes\\.example", ""),
+            Some(default_app)
+        );
+        assert_eq!(
+            fake.registry.value(
+                root,
+                "Software\\Classes\\.example\\OpenWithProgids",
+                prog_id
+            ),
+            Some(RegistryValue::String(String::new()))
+        );
+
+        let installer = Installer::new(fake.platform(), options());
+        let receipt = installer.receipt(&root_path).unwrap();
+        assert_eq!(receipt.manifest.file_associations.len(), 1);
+        assert!(receipt
+            .registry_keys()
+            .contains(&(root, prog_id_key.as_str())));
+    }
+
+    #[test]
+    fn test_install_without_shortcuts() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        package.manifest.shortcuts.start_menu = false;
+
+        install(&fake, options(), &package).unwrap();
+        assert!(fake
+            .fs
+            .read_dir(Path::new("/Start Menu/Programs"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_install_no_launch() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let options = InstallOptions {
+            launch: false,
+            ..options()
+        };
+
+        install(&fake, options, &package(b"app")).unwrap();
+        assert!(fake.launcher.launched.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_install_dir() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let install_dir = PathBuf::from("/custom");
+        let options = InstallOptions {
+            install_dir: Some(install_dir.clone()),
+            ..options()
+        };
+
+        install(&fake, options, &package(b"app")).unwrap();
+        assert!(fake.fs.exists(&install_dir.join("example.exe")));
+        assert!(!fake.fs.exists(&Path::new(APP_DATA).join(IDENTIFIER)));
+
+        // Upgrades find the installation at its recorded InstallLocation
+        let mut package = package(b"app v2");
+        package.manifest.version = "1.3.0".to_string();
+        install(&fake, self::options(), &package).unwrap();
+        assert_eq!(
+            fake.fs.read(&install_dir.join("example.exe")).unwrap(),
+            b"app v2"
+        );
+        assert!(!fake.fs.exists(&Path::new(APP_DATA).join(IDENTIFIER)));
+    }
+
+    #[test]
+    fn test_install_dir_template() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        package.manifest.install_dir = Some("{LocalAppData}\\Programs\\{productName}".to_string());
+
+        install(&fake, options(), &package).unwrap();
+        let root_path = Path::new(APP_DATA).join("Programs").join("Example");
+        assert!(fake.fs.exists(&root_path.join("example.exe")));
+
+        package.manifest.install_dir = Some("Programs\\{productName}".to_string());
+        let error = install(&FakePlatform::new(Path::new(APP_DATA)), options(), &package);
+        assert!(matches!(error.unwrap_err(), InstallError::Failed(_)));
+    }
+
+    #[test]
+    fn test_install_dir_not_an_installation() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let programs = Path::new(APP_DATA).join("Programs");
+        let other_app = programs.join("Other").join("other.exe");
+        fake.fs.create_dir_all(other_app.parent().unwrap()).unwrap();
+        fake.fs.write(&other_app, b"other").unwrap();
+        let mut package = package(b"app");
+        package.manifest.install_dir = Some("{LocalAppData}\\Programs".to_string());
+
+        // Other software in the directory is never moved aside or replaced
+        let error = install(&fake, options(), &package).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+        assert!(error
+            .to_string()
+            .contains("isn't an installation of Example"));
+        assert_eq!(fake.fs.read(&other_app).unwrap(), b"other");
+        assert_eq!(
+            fake.fs.read_dir(&programs).unwrap(),
+            vec![programs.join("Other")]
+        );
+        assert_eq!(
+            fake.fs.read_dir(Path::new(APP_DATA)).unwrap(),
+            vec![programs.clone()]
+        );
+
+        // Nor with --install-dir, even when installing silently
+        let options = InstallOptions {
+            install_dir: Some(programs.clone()),
+            silent: true,
+            force: true,
+            ..options()
+        };
+        assert!(install(&fake, options, &package).is_err());
+        assert_eq!(fake.fs.read(&other_app).unwrap(), b"other");
+        assert!(fake.processes.killed.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_install_dir_policy() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.registry
+            .set_value(
+                RegistryRoot::LocalMachine,
+                &install_dir_policy_key(IDENTIFIER),
+                INSTALL_DIR_POLICY_VALUE,
+                "{ProgramFiles}\\Managed\\{identifier}".into(),
+            )
+            .unwrap();
+        let options = InstallOptions {
+            install_dir: Some(PathBuf::from("/custom")),
+            ..options()
+        };
+
+        install(&fake, options, &package(b"app")).unwrap();
+        let root_path = Path::new("/Program Files/Managed").join(IDENTIFIER);
+        assert!(fake.fs.exists(&root_path.join("example.exe")));
+        assert!(!fake.fs.exists(Path::new("/custom")));
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = install_previous_files(&fake);
+        fake.fs.write(&root_path.join("stale.dll"), b"old").unwrap();
+
+        install(&fake, options(), &package(b"new")).unwrap();
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"new"
+        );
+        assert!(!fake.fs.exists(&root_path.join("stale.dll")));
+        assert_eq!(*fake.processes.killed.borrow(), vec![root_path.clone()]);
+        assert_eq!(fake.dialogs.shown.borrow().len(), 1);
+
+        // The rollback directory is removed
+        assert_eq!(
+            fake.fs.read_dir(Path::new(APP_DATA)).unwrap(),
+            vec![root_path]
+        );
+    }
+
+    #[test]
+    fn test_overwrite_cancelled() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.dialogs.answer = false;
+        let root_path = install_previous_files(&fake);
+
+        let error = install(&fake, options(), &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Cancelled));
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"old"
+        );
+        assert!(fake.processes.killed.borrow().is_empty());
+        assert_eq!(uninstall_value_names(&fake), vec!["InstallLocation"]);
+    }
+
+    #[test]
+    fn test_overwrite_forced() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        install(&fake, options(), &package(b"old")).unwrap();
+        fake.dialogs.answer = false;
+        fake.dialogs.shown.borrow_mut().clear();
+
+        for options in [
+            InstallOptions {
+                force: true,
+                ..options()
+            },
+            InstallOptions {
+                silent: true,
+                ..options()
+            },
+        ] {
+            install(&fake, options, &package(b"new")).unwrap();
+            assert_eq!(
+                fake.fs.read(&root_path.join("example.exe")).unwrap(),
+                b"new"
+            );
+        }
+        assert!(fake.dialogs.shown.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_overwrite_without_receipt() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.dialogs.answer = false;
+        let root_path = install_previous_files(&fake);
+
+        // Without a receipt, --force still asks
+        let options = InstallOptions {
+            force: true,
+            ..options()
+        };
+        let error = install(&fake, options, &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Cancelled));
+        assert_eq!(fake.dialogs.shown.borrow().len(), 1);
+
+        // And installing silently fails, rather than replacing it without asking
+        let options = InstallOptions {
+            silent: true,
+            force: true,
+            ..self::options()
+        };
+        let error = install(&fake, options, &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+        assert!(error.to_string().contains("no install receipt"));
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"old"
+        );
+        assert!(fake.processes.killed.borrow().is_empty());
+    }
+
+    /// Installs `example.exe` and its uninstall entry, like setup did before install receipts
+    /// The names of the values of the application's uninstall entry
+    fn uninstall_value_names(fake: &FakePlatform) -> Vec<String> {
+        let key = format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER);
+        let values = fake.registry.key(RegistryRoot::CurrentUser, &key);
+        values
+            .into_iter()
+            .flat_map(|values| values.into_keys())
+            .collect()
+    }
+
+    fn install_previous_files(fake: &FakePlatform) -> PathBuf {
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        fake.fs.create_dir_all(&root_path).unwrap();
+        fake.fs
+            .write(&root_path.join("example.exe"), b"old")
+            .unwrap();
+        fake.registry
+            .set_value(
+                RegistryRoot::CurrentUser,
+                &format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER),
+                "InstallLocation",
+                root_path.to_string_lossy().to_string().into(),
+            )
+            .unwrap();
+        root_path
+    }
+
+    /// Installs `example.exe` with the version in the uninstall entry
+    fn install_previous_version(fake: &FakePlatform, version: &str) -> PathBuf {
+        let root_path = install_previous_files(fake);
+        fake.registry
+            .set_value(
+                RegistryRoot::CurrentUser,
+                &format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER),
+                "DisplayVersion",
+                version.into(),
+            )
+            .unwrap();
+        root_path
+    }
+
+    #[test]
+    fn test_install_kind() {
+        assert_eq!(
+            InstallKind::detect(Some("1.0.0"), "1.2.3"),
+            InstallKind::Upgrade {
+                installed: "1.0.0".to_string()
+            }
+        );
+        assert_eq!(
+            InstallKind::detect(Some("1.2.3"), "1.2.3"),
+            InstallKind::Repair
+        );
+        assert_eq!(
+            InstallKind::detect(Some("v1.2.3"), "1.2.3"),
+            InstallKind::Repair
+        );
+        assert_eq!(
+            InstallKind::detect(Some("1.10.0"), "1.9.0"),
+            InstallKind::Downgrade {
+                installed: "1.10.0".to_string()
+            }
+        );
+        assert_eq!(
+            InstallKind::detect(Some("1.0.0"), "1.0.0-beta.1"),
+            InstallKind::Downgrade {
+                installed: "1.0.0".to_string()
+            }
+        );
+        assert_eq!(
+            InstallKind::detect(Some("1.0"), "1.2.3"),
+            InstallKind::Unknown
+        );
+        assert_eq!(InstallKind::detect(None, "1.2.3"), InstallKind::Unknown);
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.dialogs.answer = false;
+        let root_path = install_previous_version(&fake, "1.0.0");
+
+        install(&fake, options(), &package(b"new")).unwrap();
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"new"
+        );
+        assert!(fake.dialogs.shown.borrow().is_empty());
+        assert_eq!(*fake.processes.killed.borrow(), vec![root_path]);
+    }
+
+    #[test]
+    fn test_repair_same_version() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.dialogs.answer = false;
+        install_previous_version(&fake, "1.2.3");
+
+        let error = install(&fake, options(), &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Cancelled));
+        let shown = fake.dialogs.shown.borrow();
+        assert_eq!(shown.len(), 1);
+        assert!(shown[0].starts_with("overwrite: Example App: Example 1.2.3 is already installed."));
+        assert!(shown[0].ends_with("Do you want to attempt to repair it?"));
+    }
+
+    #[test]
+    fn test_installed_version_from_receipt() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        install(&fake, options(), &package(b"old")).unwrap();
+        fake.registry
+            .delete_key(RegistryRoot::CurrentUser, UNINSTALL_KEY)
+            .unwrap();
+
+        // The receipt has the same version, so it's repaired
+        let mut fake = fake;
+        fake.dialogs.answer = false;
+        let error = install(&fake, options(), &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Cancelled));
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"old"
+        );
+        assert!(fake.dialogs.shown.borrow()[0].contains("Example 1.2.3 is already installed"));
+    }
+
+    #[test]
+    fn test_downgrade_blocked() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = install_previous_version(&fake, "2.0.0");
+        let options = InstallOptions {
+            force: true,
+            ..options()
+        };
+
+        let error = install(&fake, options, &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::DowngradeBlocked { .. }));
+        assert_eq!(error.exit_code(), 9);
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"old"
+        );
+        assert!(fake.processes.killed.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_downgrade_blocked_before_prerequisites() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        install_previous_version(&fake, "2.0.0");
+        let mut package = package_with_prerequisite();
+        if let Some(webview2) = &mut package.manifest.webview2 {
+            webview2.mode = Webview2Bundle::DownloadBootstrapper;
+        }
+        let options = InstallOptions {
+            force: true,
+            accept_prereqs: true,
+            ..options()
+        };
+
+        let error = install(&fake, options, &package).unwrap_err();
+        assert!(matches!(error, InstallError::DowngradeBlocked { .. }));
+        assert!(fake.installers.downloaded.borrow().is_empty());
+        assert!(fake.installers.ran.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_downgrade_allowed() {
+        for (allow_downgrades, allow_downgrade) in [(true, false), (false, true)] {
+            let fake = FakePlatform::new(Path::new(APP_DATA));
+            let root_path = install_previous_version(&fake, "2.0.0");
+            let mut package = package(b"new");
+            package.manifest.allow_downgrades = allow_downgrades;
+            let options = InstallOptions {
+                allow_downgrade,
+                ..options()
+            };
+
+            install(&fake, options, &package).unwrap();
+            assert_eq!(
+                fake.fs.read(&root_path.join("example.exe")).unwrap(),
+                b"new"
+            );
+            assert_eq!(
+                *fake.dialogs.shown.borrow(),
+                vec!["overwrite: Example App: A newer version of Example (2.0.0) is already installed.\n\nDo you want to replace it with the older version 1.2.3?".to_string()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let installer = Installer::new(fake.platform(), options());
+        let error = installer.verify(&package(b"app")).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+
+        installer.install(&package(b"app")).unwrap();
+        assert!(installer.verify(&package(b"app")).unwrap().is_intact());
+        assert_eq!(
+            *fake.dialogs.shown.borrow(),
+            vec!["info: Example App: Example is installed correctly.".to_string()]
+        );
+
+        // User data isn't part of the package
+        fake.fs
+            .write(&root_path.join("settings.json"), b"{}")
+            .unwrap();
+        assert!(installer.verify(&package(b"app")).unwrap().is_intact());
+
+        fake.fs
+            .write(&root_path.join("example.exe"), b"bad")
+            .unwrap();
+        let error = installer.verify(&package(b"app")).unwrap_err();
+        assert_eq!(error.exit_code(), 10);
+        assert!(matches!(
+            error,
+            InstallError::VerificationFailed(VerifyReport { ref modified, .. })
+                if *modified == vec!["example.exe".to_string()]
+        ));
+
+        fake.fs.remove_file(&root_path.join("example.exe")).unwrap();
+        let error = installer.verify(&package(b"app")).unwrap_err();
+        assert!(matches!(
+            error,
+            InstallError::VerificationFailed(VerifyReport { ref missing, .. })
+                if *missing == vec!["example.exe".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_verify_other_version() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let installer = Installer::new(fake.platform(), options());
+        let mut previous = package(b"old");
+        previous.manifest.version = "1.0.0".to_string();
+        installer.install(&previous).unwrap();
+
+        // Compared with the receipt of the installed version, not the package
+        assert!(installer.verify(&package(b"new")).unwrap().is_intact());
+        let error = installer.repair(&package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+    }
+
+    #[test]
+    fn test_repair_damaged_files() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let installer = Installer::new(fake.platform(), options());
+        installer.install(&package(b"app")).unwrap();
+        fake.fs
+            .write(&root_path.join("settings.json"), b"{}")
+            .unwrap();
+
+        // Nothing is stopped or changed when the installation is intact
+        let changes = fake.fs.changes();
+        assert!(installer.repair(&package(b"app")).unwrap().is_intact());
+        assert_eq!(fake.fs.changes(), changes);
+        assert!(fake.processes.killed.borrow().is_empty());
+
+        fake.fs
+            .write(&root_path.join("example.exe"), b"bad")
+            .unwrap();
+        let report = installer.repair(&package(b"app")).unwrap();
+        assert_eq!(report.modified, vec!["example.exe".to_string()]);
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"app"
+        );
+        assert_eq!(
+            fake.fs.read(&root_path.join("settings.json")).unwrap(),
+            b"{}"
+        );
+        assert_eq!(*fake.processes.killed.borrow(), vec![root_path.clone()]);
+        assert!(installer.verify(&package(b"app")).unwrap().is_intact());
+        assert_eq!(
+            fake.fs.read_dir(Path::new(APP_DATA)).unwrap(),
+            vec![root_path]
+        );
+    }
+
+    #[test]
+    fn test_rollback() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = install_previous_files(&fake);
+        fake.fs.lock(&InstallReceipt::path(&root_path));
+
+        let error = install(&fake, options(), &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::RolledBack(_)));
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"old"
+        );
+        assert_eq!(
+            fake.fs.read_dir(Path::new(APP_DATA)).unwrap(),
+            vec![root_path]
+        );
+        assert_eq!(uninstall_value_names(&fake), vec!["InstallLocation"]);
+    }
+
+    #[test]
+    fn test_rollback_removes_created_keys() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root = RegistryRoot::CurrentUser;
+        let other_app = format!("{}\\com.example.other", UNINSTALL_KEY);
+        fake.registry
+            .set_value(root, &other_app, "DisplayName", "Other".into())
+            .unwrap();
+        fake.registry
+            .set_value(
+                root,
+                "Software\\Classes\\.example",
+                "",
+                "Other.Document".into(),
+            )
+            .unwrap();
+        let mut package = package(b"new");
+        package.manifest.icon = Some("icon.png".to_string());
+        package.manifest.file_associations = vec![FileAssociation {
+            extensions: vec!["example".to_string(), "sample".to_string()],
+            name: Some("Example Document".to_string()),
+            description: None,
+            mime_type: None,
+        }];
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        fake.fs.lock(&InstallReceipt::path(&root_path));
+
+        let error = install(&fake, options(), &package).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+        for key in [
+            format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER),
+            format!("{}\\{}", APP_USER_MODEL_ID_KEY, IDENTIFIER),
+            "Software\\Classes\\com.example.app.ExampleDocument".to_string(),
+            "Software\\Classes\\.example\\OpenWithProgids".to_string(),
+            "Software\\Classes\\.sample".to_string(),
+        ] {
+            assert!(!fake.registry.key_exists(root, &key), "{} exists", key);
+        }
+        // Keys that existed before the install are kept
+        assert!(fake.registry.key(root, &other_app).is_some());
+        assert_eq!(
+            fake.registry.value(root, "Software\\Classes\\.example", ""),
+            Some("Other.Document".into())
+        );
+    }
+
+    #[test]
+    fn test_failed_without_previous_installation() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        fake.fs.lock(&root_path.join("example.exe"));
+
+        let error = install(&fake, options(), &package(b"new")).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+        assert_eq!(error.exit_code(), 1);
+        assert!(!fake.fs.exists(&root_path));
+        assert!(fake.launcher.launched.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_launch_failed() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.launcher.fail = true;
+
+        install(&fake, options(), &package(b"app")).unwrap();
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        assert_eq!(
+            fake.fs.read(&root_path.join("example.exe")).unwrap(),
+            b"app"
+        );
+        assert!(!fake.fs.exists(&journal_path(&root_path)));
+    }
+
+    #[test]
+    fn test_insufficient_disk_space() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.disk.free_space = Some(2);
+
+        let error = install(&fake, options(), &package(b"app")).unwrap_err();
+        assert!(matches!(
+            error,
+            InstallError::InsufficientDiskSpace {
+                required: 3,
+                available: 2
+            }
+        ));
+        assert!(!fake.fs.exists(&Path::new(APP_DATA).join(IDENTIFIER)));
+    }
+
+    #[test]
+    fn test_unknown_disk_space() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.disk.free_space = None;
+
+        install(&fake, options(), &package(b"app")).unwrap();
+    }
+
+    #[test]
+    fn test_application_not_bundled() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        package.files.clear();
+
+        let error = install(&fake, options(), &package).unwrap_err();
+        assert!(matches!(error, InstallError::CorruptPackage(_)));
+    }
+
+    #[test]
+    fn test_fixed_runtime() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package(b"app");
+        if let Some(webview2) = &mut package.manifest.webview2 {
+            webview2.mode = Webview2Bundle::FixedRuntime;
+        }
+        package.files.insert(
+            "WebView2Runtime/msedgewebview2.exe".to_string(),
+            b"runtime".to_vec(),
+        );
+
+        install(&fake, options(), &package).unwrap();
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        assert_eq!(
+            fake.fs
+                .read(&root_path.join("WebView2Runtime/msedgewebview2.exe"))
+                .unwrap(),
+            b"runtime"
+        );
+    }
+
+    #[test]
+    fn test_remove_leftover_backups() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let leftover = Path::new(APP_DATA).join("com.example.app_x7Yq2LmP");
+        fake.fs.create_dir_all(&leftover).unwrap();
+        fake.fs
+            .write(&leftover.join("example.exe"), b"old")
+            .unwrap();
+        let look_alike = Path::new(APP_DATA).join("com.example.app_Archives");
+        fake.fs.create_dir_all(&look_alike).unwrap();
+        fake.fs
+            .write(&look_alike.join("notes.txt"), b"notes")
+            .unwrap();
+
+        install(&fake, options(), &package(b"new")).unwrap();
+        assert_eq!(
+            fake.fs.read_dir(Path::new(APP_DATA)).unwrap(),
+            vec![root_path, look_alike]
+        );
+    }
+
+    #[test]
+    fn test_keep_look_alike_backups() {
+        // Next to another install directory, even a directory holding the application is kept
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let tools = Path::new("/Tools");
+        let sibling = tools.join("Example_Settings");
+        fake.fs.create_dir_all(&sibling).unwrap();
+        fake.fs
+            .write(&sibling.join("example.exe"), b"mine")
+            .unwrap();
+        let options = InstallOptions {
+            install_dir: Some(tools.join("Example")),
+            ..options()
+        };
+
+        let installer = Installer::new(fake.platform(), options);
+        installer.install(&package(b"new")).unwrap();
+        installer.repair(&package(b"new")).unwrap();
+        assert_eq!(fake.fs.read(&sibling.join("example.exe")).unwrap(), b"mine");
+        assert_eq!(
+            fake.fs.read_dir(tools).unwrap(),
+            vec![tools.join("Example"), sibling]
+        );
+    }
+
+    /// Crashes after every number of changes while overwriting, and runs setup again
+    #[test]
+    fn test_recover_interrupted_install() {
+        let key = format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER);
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let previous_installation = || {
+            let fake = FakePlatform::new(Path::new(APP_DATA));
+            install_previous_version(&fake, "1.0.0");
+            fake
+        };
+
+        let fake = previous_installation();
+        let start = fake.fs.changes();
+        install(&fake, options(), &package(b"new")).unwrap();
+        let changes = fake.fs.changes() - start;
+
+        for crash_after in 0..changes {
+            let fake = previous_installation();
+            fake.fs.crash_after(crash_after);
+            assert!(install(&fake, options(), &package(b"new")).is_err());
+
+            // The interrupted install is undone or finished when setup next runs
+            fake.fs.restart();
+            let installer = Installer::new(fake.platform(), options());
+            let recovery = installer.recover(&root_path).unwrap();
+            let (app, version) = match recovery {
+                Some(Recovery::Finished) => (b"new", "1.2.3"),
+                _ => (b"old", "1.0.0"),
+            };
+            assert_eq!(fake.fs.read(&root_path.join("example.exe")).unwrap(), app);
+            assert_eq!(
+                fake.registry
+                    .value(RegistryRoot::CurrentUser, &key, "DisplayVersion"),
+                Some(version.into()),
+                "crash after {} changes",
+                crash_after
+            );
+            assert_eq!(
+                fake.fs.read_dir(Path::new(APP_DATA)).unwrap(),
+                vec![root_path.clone()],
+                "crash after {} changes",
+                crash_after
+            );
+
+            // And setup installs normally
+            installer.install(&package(b"new")).unwrap();
+            assert_eq!(
+                fake.fs.read(&root_path.join("example.exe")).unwrap(),
+                b"new"
+            );
+        }
+    }
+
+    fn package_with_prerequisite() -> SetupPackage {
+        let mut package = package(b"app");
+        package.manifest.prerequisites.push(Prerequisite {
+            name: "vcredist".to_string(),
+            title: Some("Visual C++ Redistributable".to_string()),
+            detect: Some(DetectionRule::Registry {
+                key: "HKLM\\SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64".to_string(),
+                value: None,
+                equals: None,
+                min_version: None,
+            }),
+            path: None,
+            payload: None,
+            url: None,
+            args: Vec::new(),
+            exit_codes: vec![0],
+            reboot_exit_codes: vec![3010],
+        });
+        package
+    }
+
+    #[test]
+    fn test_prerequisites_declined() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.dialogs.answer = false;
+
+        let error = install(&fake, options(), &package_with_prerequisite()).unwrap_err();
+        assert!(matches!(error, InstallError::Cancelled));
+        assert_eq!(
+            *fake.dialogs.shown.borrow(),
+            vec!["prerequisites: Example App: Visual C++ Redistributable".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_prerequisites_not_accepted_silently() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let options = InstallOptions {
+            silent: true,
+            ..options()
+        };
+
+        let error = install(&fake, options, &package_with_prerequisite()).unwrap_err();
+        assert!(matches!(error, InstallError::PrerequisitesNotAccepted(_)));
+        assert!(fake.dialogs.shown.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_prerequisites_installed() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.registry
+            .set_value(
+                RegistryRoot::LocalMachine,
+                "SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64",
+                "Installed",
+                1u32.into(),
+            )
+            .unwrap();
+
+        install(&fake, options(), &package_with_prerequisite()).unwrap();
+        assert!(fake.dialogs.shown.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_receipt_prerequisite_names() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let mut package = package_with_prerequisite();
+        package.manifest.prerequisites[0].url =
+            Some("https://example.com/vc_redist.x64.exe".to_string());
+        if let Some(webview2) = &mut package.manifest.webview2 {
+            webview2.mode = Webview2Bundle::DownloadBootstrapper;
+        }
+
+        install(&fake, options(), &package).unwrap();
+        assert_eq!(
+            *fake.dialogs.shown.borrow(),
+            vec!["prerequisites: Example App: Microsoft Edge WebView2 Runtime, Visual C++ Redistributable".to_string()]
+        );
+        let root_path = Path::new(APP_DATA).join(IDENTIFIER);
+        let receipt = Installer::new(fake.platform(), options())
+            .receipt(&root_path)
+            .unwrap();
+        assert_eq!(
+            receipt.prerequisites,
+            vec![WEBVIEW2_PREREQUISITE.to_string(), "vcredist".to_string()]
+        );
+    }
+
+    fn package_downloading_webview2() -> SetupPackage {
+        let mut package = package(b"app");
+        if let Some(webview2) = &mut package.manifest.webview2 {
+            webview2.mode = Webview2Bundle::DownloadBootstrapper;
+        }
+        package
+    }
+
+    #[test]
+    fn test_webview2_downloaded() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+
+        install(&fake, options(), &package_downloading_webview2()).unwrap();
+        assert_eq!(
+            *fake.dialogs.shown.borrow(),
+            vec!["prerequisites: Example App: Microsoft Edge WebView2 Runtime".to_string()]
+        );
+        assert_eq!(
+            *fake.installers.downloaded.borrow(),
+            vec![WEBVIEW2_EVERGREEN_URL.to_string()]
+        );
+        assert_eq!(
+            *fake.installers.ran.borrow(),
+            vec![(
+                WEBVIEW2_EVERGREEN_EXE.to_string(),
+                vec!["/install".to_string()]
+            )]
+        );
+        assert!(fake
+            .fs
+            .exists(&Path::new(APP_DATA).join(IDENTIFIER).join("example.exe")));
+    }
+
+    #[test]
+    fn test_webview2_download_failed() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.installers.fail_downloads = true;
+
+        let error = install(&fake, options(), &package_downloading_webview2()).unwrap_err();
+        assert!(matches!(error, InstallError::PrerequisiteFailed(_)));
+        assert_eq!(error.exit_code(), 4);
+        assert!(fake.installers.ran.borrow().is_empty());
+        assert!(!fake.fs.exists(&Path::new(APP_DATA).join(IDENTIFIER)));
+    }
+
+    #[test]
+    fn test_webview2_already_installed() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.installers.webview2_version = Some("130.0.2849.80".to_string());
+
+        install(&fake, options(), &package_downloading_webview2()).unwrap();
+        assert!(fake.dialogs.shown.borrow().is_empty());
+        assert!(fake.installers.downloaded.borrow().is_empty());
+        assert!(fake.installers.ran.borrow().is_empty());
+    }
+
+    fn per_machine_package(install_scope: InstallScope) -> SetupPackage {
+        let mut package = package(b"app");
+        package.manifest.install_scope = install_scope;
+        package
+    }
+
+    #[test]
+    fn test_install_per_machine() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.elevation.elevated = true;
+        let package = per_machine_package(InstallScope::PerMachine);
+        let installer = Installer::new(fake.platform(), options());
+        assert!(!installer.needs_elevation(&package.manifest).unwrap());
+
+        installer.install(&package).unwrap();
+        let exe_path = Path::new("/Program Files/Example/example.exe");
+        assert_eq!(fake.fs.read(exe_path).unwrap(), b"app");
+        assert!(!fake.fs.exists(&Path::new(APP_DATA).join(IDENTIFIER)));
+
+        let key = format!("{}\\{}", UNINSTALL_KEY, IDENTIFIER);
+        let uninstall_string = format!(
+            "\"{}\" --uninstall --per-machine",
+            exe_path.to_string_lossy()
+        );
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::LocalMachine, &key, "UninstallString"),
+            Some(RegistryValue::String(uninstall_string))
+        );
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::LocalMachine, &key, "DisplayIcon"),
+            Some(RegistryValue::String(format!(
+                "\"{}\"",
+                exe_path.to_string_lossy()
+            )))
+        );
+        assert_eq!(
+            fake.registry
+                .value(RegistryRoot::CurrentUser, &key, "UninstallString"),
+            None
+        );
+        let receipt = installer.receipt(exe_path.parent().unwrap()).unwrap();
+        assert_eq!(receipt.scope, Scope::PerMachine);
+    }
+
+    #[test]
+    fn test_install_scope_both() {
+        let mut fake = FakePlatform::new(Path::new(APP_DATA));
+        fake.elevation.elevated = true;
+        let package = per_machine_package(InstallScope::Both);
+        let installer = Installer::new(fake.platform(), options());
+        assert_eq!(installer.scope(&package.manifest).unwrap(), Scope::PerUser);
+
+        // An existing per-machine installation is upgraded in place
+        let options = InstallOptions {
+            scope: Some(Scope::PerMachine),
+            ..options()
+        };
+        install(&fake, options, &package).unwrap();
+        let installer = Installer::new(fake.platform(), self::options());
+        assert_eq!(
+            installer.scope(&package.manifest).unwrap(),
+            Scope::PerMachine
+        );
+
+        // Unless the user asks for per-user
+        let options = InstallOptions {
+            scope: Some(Scope::PerUser),
+            ..self::options()
+        };
+        let installer = Installer::new(fake.platform(), options);
+        assert_eq!(installer.scope(&package.manifest).unwrap(), Scope::PerUser);
+    }
+
+    #[test]
+    fn test_install_scope_not_allowed() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let options = InstallOptions {
+            scope: Some(Scope::PerMachine),
+            ..options()
+        };
+        let error = install(&fake, options, &package(b"app")).unwrap_err();
+        assert!(matches!(error, InstallError::Failed(_)));
+        assert!(!fake.fs.exists(Path::new("/Program Files/Example")));
+    }
+
+    #[test]
+    fn test_install_elevated() {
+        let fake = FakePlatform::new(Path::new(APP_DATA));
+        let package = per_machine_package(InstallScope::PerMachine);
+        let installer = Installer::new(fake.platform(), options());
+        assert!(installer.needs_elevation(&package.manifest).unwrap());
+
+        let outcome = installer.install_elevated(&package.manifest).unwrap();
+        assert_eq!(outcome, InstallOutcome::Installed);
+        assert_eq!(
+            *fake.elevation.relaunched.borrow(),
+            vec![vec!["--per-machine".to_string(), "--no-launch".to_string()]]
+        );
+        assert_eq!(
+            *fake.launcher.launched.borrow(),
+            vec![PathBuf::from("/Program Files/Example/example.exe")]
+        );
+
+        let install_elevated = |exit_code| {
+            let mut fake = fake.clone();
+            fake.elevation.exit_code = exit_code;
+            Installer::new(fake.platform(), options()).install_elevated(&package.manifest)
+        };
+        assert_eq!(
+            install_elevated(Some(EXIT_CODE_REBOOT_REQUIRED)).unwrap(),
+            InstallOutcome::RebootRequired
+        );
+        assert!(matches!(
+            install_elevated(None).unwrap_err(),
+            InstallError::Cancelled
+        ));
+        let error = install_elevated(Some(7)).unwrap_err();
+        assert!(matches!(error, InstallError::ElevatedSetupFailed(7)));
+        assert_eq!(error.exit_code(), 7);
+    }
+}
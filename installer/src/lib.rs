@@ -0,0 +1,32 @@
+//! Installs an application bundled into the setup executable by the bundler
+//!
+//! The install flow only reaches the system through [`platform::Platform`], so it's tested with the
+//! fakes in [`platform::fake`] on any OS.
+
+mod bundle;
+#[cfg(windows)]
+mod dialogs;
+pub mod error;
+pub mod install;
+pub mod lnk;
+pub mod logging;
+pub mod platform;
+#[cfg(windows)]
+mod process;
+#[cfg(windows)]
+mod to_wide;
+pub mod transaction;
+pub mod verify;
+
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB", "PB", "EB"];
+    let mut size = bytes as f64;
+    let mut unit_index = 0;
+
+    while size >= 1024.0 && unit_index < units.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    format!("{:.2} {}", size, units[unit_index])
+}
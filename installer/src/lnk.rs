@@ -0,0 +1,299 @@
+//! Writes Windows shortcut (`.lnk`) files, in the [MS-SHLLINK] format
+//!
+//! The target is stored as a local path in the `LinkInfo` structure rather than as a shell item ID
+//! list, so shortcuts are written without COM and tested on any OS. The shell resolves the path
+//! when the shortcut is first used.
+//!
+//! [MS-SHLLINK]: https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink
+
+const HEADER_SIZE: u32 = 0x4C;
+/// `00021401-0000-0000-C000-000000000046`
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+// LinkFlags
+const HAS_LINK_INFO: u32 = 0x02;
+const HAS_NAME: u32 = 0x04;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+const SW_SHOWNORMAL: u32 = 1;
+
+const LINK_INFO_HEADER_SIZE: u32 = 0x24;
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x01;
+const DRIVE_FIXED: u32 = 3;
+
+const PROPERTY_STORE_SIGNATURE: u32 = 0xA000_0009;
+/// Version of a serialized property storage, `1SPS`
+const PROPERTY_STORAGE_VERSION: u32 = 0x5350_5331;
+/// Format ID of the `System.AppUserModel` properties, `9F4C2855-9F79-4B39-A8D0-E1D42DE1D5F3`
+const APP_USER_MODEL_FMTID: [u8; 16] = [
+    0x55, 0x28, 0x4C, 0x9F, 0x79, 0x9F, 0x39, 0x4B, 0xA8, 0xD0, 0xE1, 0xD4, 0x2D, 0xE1, 0xD5, 0xF3,
+];
+/// Property ID of `System.AppUserModel.ID`
+const APP_USER_MODEL_ID_PID: u32 = 5;
+const VT_LPWSTR: u16 = 0x1F;
+
+/// A shortcut to a file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShellLink {
+    /// Absolute path of the target
+    pub target: String,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    /// Shown as the tooltip of the shortcut
+    pub description: Option<String>,
+    /// File containing the icon, the target's own icon is used when not set
+    pub icon_location: Option<String>,
+    pub icon_index: i32,
+    /// `System.AppUserModel.ID` of the shortcut, grouping the application's windows on the taskbar
+    /// and attributing its notifications
+    pub app_user_model_id: Option<String>,
+}
+
+impl ShellLink {
+    /// The contents of the `.lnk` file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // StringData is in the order of its flags
+        let strings = [
+            (HAS_NAME, &self.description),
+            (HAS_WORKING_DIR, &self.working_dir),
+            (HAS_ARGUMENTS, &self.arguments),
+            (HAS_ICON_LOCATION, &self.icon_location),
+        ];
+        let flags = strings
+            .iter()
+            .filter(|(_, value)| value.is_some())
+            .fold(HAS_LINK_INFO | IS_UNICODE, |flags, (flag, _)| flags | flag);
+
+        let mut data = Vec::new();
+        write_header(&mut data, flags, self.icon_index);
+        write_link_info(&mut data, &self.target);
+        for value in strings.iter().filter_map(|(_, value)| value.as_ref()) {
+            let value = utf16(value);
+            data.extend((value.len() as u16 / 2).to_le_bytes());
+            data.extend(value);
+        }
+        if let Some(app_user_model_id) = &self.app_user_model_id {
+            write_app_user_model_id(&mut data, app_user_model_id);
+        }
+        // TerminalBlock, ending the ExtraData
+        data.extend(0u32.to_le_bytes());
+        data
+    }
+}
+
+/// Writes the ShellLinkHeader, without file times or attributes as the target doesn't exist yet
+fn write_header(data: &mut Vec<u8>, flags: u32, icon_index: i32) {
+    data.extend(HEADER_SIZE.to_le_bytes());
+    data.extend(LINK_CLSID);
+    data.extend(flags.to_le_bytes());
+    // FileAttributes, CreationTime, AccessTime, WriteTime and FileSize
+    data.extend([0u8; 4 + 8 * 3 + 4]);
+    data.extend(icon_index.to_le_bytes());
+    data.extend(SW_SHOWNORMAL.to_le_bytes());
+    // HotKey and the reserved fields
+    data.extend([0u8; 2 + 2 + 4 + 4]);
+}
+
+/// Writes the LinkInfo with the target as the local base path, on a fixed drive without a label
+fn write_link_info(data: &mut Vec<u8>, target: &str) {
+    let volume_id = [0x11, DRIVE_FIXED, 0, 0x10]
+        .into_iter()
+        .flat_map(u32::to_le_bytes)
+        .chain([0])
+        .collect::<Vec<u8>>();
+    // Characters outside ASCII are only kept in the Unicode path
+    let ansi_path = target
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .chain([0])
+        .collect::<Vec<u8>>();
+    let unicode_path = utf16_null(target);
+
+    let volume_id_offset = LINK_INFO_HEADER_SIZE;
+    let local_base_path_offset = volume_id_offset + volume_id.len() as u32;
+    let common_path_suffix_offset = local_base_path_offset + ansi_path.len() as u32;
+    let local_base_path_offset_unicode = common_path_suffix_offset + 1;
+    let common_path_suffix_offset_unicode =
+        local_base_path_offset_unicode + unicode_path.len() as u32;
+    let size = common_path_suffix_offset_unicode + 2;
+
+    for value in [
+        size,
+        LINK_INFO_HEADER_SIZE,
+        VOLUME_ID_AND_LOCAL_BASE_PATH,
+        volume_id_offset,
+        local_base_path_offset,
+        // CommonNetworkRelativeLinkOffset
+        0,
+        common_path_suffix_offset,
+        local_base_path_offset_unicode,
+        common_path_suffix_offset_unicode,
+    ] {
+        data.extend(value.to_le_bytes());
+    }
+    data.extend(volume_id);
+    data.extend(ansi_path);
+    // Empty common path suffixes
+    data.push(0);
+    data.extend(unicode_path);
+    data.extend([0, 0]);
+}
+
+/// Writes a PropertyStoreDataBlock with the `System.AppUserModel.ID` property
+fn write_app_user_model_id(data: &mut Vec<u8>, app_user_model_id: &str) {
+    let value = utf16_null(app_user_model_id);
+    let padding = value.len().next_multiple_of(4) - value.len();
+    // ValueSize, Id, Reserved, then the type, padding and length of the typed value
+    let value_size = 4 + 4 + 1 + 2 + 2 + 4 + value.len() + padding;
+    // StorageSize, Version, FormatID, the value and the terminating empty value
+    let storage_size = 4 + 4 + 16 + value_size + 4;
+    // BlockSize, BlockSignature, the storage and the terminating empty storage
+    let block_size = 4 + 4 + storage_size + 4;
+
+    data.extend((block_size as u32).to_le_bytes());
+    data.extend(PROPERTY_STORE_SIGNATURE.to_le_bytes());
+    data.extend((storage_size as u32).to_le_bytes());
+    data.extend(PROPERTY_STORAGE_VERSION.to_le_bytes());
+    data.extend(APP_USER_MODEL_FMTID);
+    data.extend((value_size as u32).to_le_bytes());
+    data.extend(APP_USER_MODEL_ID_PID.to_le_bytes());
+    data.push(0);
+    data.extend(VT_LPWSTR.to_le_bytes());
+    data.extend(0u16.to_le_bytes());
+    data.extend((value.len() as u32 / 2).to_le_bytes());
+    data.extend(value);
+    data.extend(vec![0; padding]);
+    data.extend(0u32.to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+}
+
+/// UTF-16LE bytes of a string
+fn utf16(value: &str) -> Vec<u8> {
+    value.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// Null terminated UTF-16LE bytes of a string
+fn utf16_null(value: &str) -> Vec<u8> {
+    let mut bytes = utf16(value);
+    bytes.extend([0, 0]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn shell_link() -> ShellLink {
+        ShellLink {
+            target: "C:\\Program Files\\Démo\\demo.exe".to_string(),
+            arguments: None,
+            working_dir: Some("C:\\Program Files\\Démo".to_string()),
+            description: Some("Demo App".to_string()),
+            icon_location: Some("C:\\Program Files\\Démo\\demo.exe".to_string()),
+            icon_index: 0,
+            app_user_model_id: Some("com.gussy.demo".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let data = shell_link().to_bytes();
+        assert_eq!(u32_at(&data, 0), 0x4C);
+        assert_eq!(
+            data[4..20],
+            [
+                0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x46
+            ]
+        );
+        // HasLinkInfo, HasName, HasWorkingDir, HasIconLocation and IsUnicode
+        assert_eq!(u32_at(&data, 20), 0xD6);
+        assert_eq!(u32_at(&data, 56), 0);
+        assert_eq!(u32_at(&data, 60), SW_SHOWNORMAL);
+        assert!(data[64..76].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_link_info() {
+        let data = shell_link().to_bytes();
+        let link_info = &data[76..76 + u32_at(&data, 76) as usize];
+        assert_eq!(u32_at(link_info, 4), 0x24);
+        assert_eq!(u32_at(link_info, 8), VOLUME_ID_AND_LOCAL_BASE_PATH);
+
+        let volume_id = &link_info[u32_at(link_info, 12) as usize..];
+        assert_eq!(u32_at(volume_id, 0), 0x11);
+        assert_eq!(u32_at(volume_id, 4), DRIVE_FIXED);
+
+        let local_base_path = u32_at(link_info, 16) as usize;
+        assert_eq!(
+            &link_info[local_base_path..local_base_path + 31],
+            b"C:\\Program Files\\D?mo\\demo.exe\0"
+        );
+        let local_base_path_unicode = u32_at(link_info, 28) as usize;
+        let suffix_unicode = u32_at(link_info, 32) as usize;
+        assert_eq!(
+            link_info[local_base_path_unicode..suffix_unicode],
+            utf16_null("C:\\Program Files\\Démo\\demo.exe")
+        );
+        assert_eq!(suffix_unicode + 2, link_info.len());
+    }
+
+    #[test]
+    fn test_string_data_and_property_store() {
+        let data = shell_link().to_bytes();
+        let mut offset = 76 + u32_at(&data, 76) as usize;
+        for expected in [
+            "Demo App",
+            "C:\\Program Files\\Démo",
+            "C:\\Program Files\\Démo\\demo.exe",
+        ] {
+            let length = u16_at(&data, offset) as usize;
+            assert_eq!(data[offset + 2..offset + 2 + length * 2], utf16(expected));
+            offset += 2 + length * 2;
+        }
+
+        let block = &data[offset..];
+        let block_size = u32_at(block, 0) as usize;
+        assert_eq!(u32_at(block, 4), PROPERTY_STORE_SIGNATURE);
+        assert_eq!(&block[12..16], b"1SPS");
+        assert_eq!(block[16..32], APP_USER_MODEL_FMTID);
+        let value_size = u32_at(block, 32) as usize;
+        assert_eq!(value_size % 4, 1);
+        assert_eq!(u32_at(block, 36), APP_USER_MODEL_ID_PID);
+        assert_eq!(u16_at(block, 41), VT_LPWSTR);
+        assert_eq!(u32_at(block, 45), 15);
+        assert_eq!(block[49..49 + 30], utf16_null("com.gussy.demo"));
+        assert_eq!(u32_at(block, 8) as usize, 24 + value_size + 4);
+        assert_eq!(u32_at(block, 32 + value_size), 0);
+
+        // The end of the property store, and the TerminalBlock
+        assert_eq!(block[block_size - 4..block_size], [0; 4]);
+        assert_eq!(block.len(), block_size + 4);
+        assert_eq!(block[block_size..], [0; 4]);
+    }
+
+    #[test]
+    fn test_minimal() {
+        let link = ShellLink {
+            target: "C:\\app.exe".to_string(),
+            ..Default::default()
+        };
+        let data = link.to_bytes();
+        assert_eq!(u32_at(&data, 20), HAS_LINK_INFO | IS_UNICODE);
+        let link_info_size = u32_at(&data, 76) as usize;
+        assert_eq!(data.len(), 76 + link_info_size + 4);
+    }
+}
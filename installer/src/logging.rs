@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use log::LevelFilter;
+use simplelog::{
+    ColorChoice, CombinedLogger, ConfigBuilder, SharedLogger, TermLogger, TerminalMode, WriteLogger,
+};
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The log file, once logging is initialised
+static LOG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// The default log file, `%TEMP%\{identifier}-setup.log`
+pub fn default_log_path(identifier: &str) -> PathBuf {
+    env::temp_dir().join(format!("{}-setup.log", identifier))
+}
+
+/// The log file, if logging has been initialised
+pub fn log_path() -> Option<&'static Path> {
+    LOG_PATH.get().map(PathBuf::as_path)
+}
+
+/// Logs to the file with timestamps, and to the console which is only visible in debug builds
+pub fn init(log_path: &Path) -> Result<()> {
+    let config = ConfigBuilder::new()
+        .set_time_format_rfc3339()
+        .set_target_level(LevelFilter::Off)
+        .set_thread_level(LevelFilter::Off)
+        .build();
+
+    let log_file = File::create(log_path)
+        .with_context(|| format!("Failed to create log file {:?}", log_path))?;
+    let loggers: Vec<Box<dyn SharedLogger>> = vec![
+        WriteLogger::new(LevelFilter::Debug, config.clone(), log_file),
+        TermLogger::new(
+            LevelFilter::Info,
+            config,
+            TerminalMode::Mixed,
+            ColorChoice::Never,
+        ),
+    ];
+    CombinedLogger::init(loggers).context("Failed to initialise logging")?;
+
+    let _ = LOG_PATH.set(log_path.to_path_buf());
+    log::info!("Logging to {:?}", log_path);
+    Ok(())
+}
+
+/// Logs panics, which are otherwise only written to the console, and shows an error dialog
+#[cfg_attr(not(windows), allow(unused_variables))]
+pub fn log_panics(silent: bool) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        default_hook(info);
+        #[cfg(windows)]
+        crate::dialogs::show_error_dialog(
+            "Installation failed",
+            &format!("An unexpected error occurred.\n\n{}", info),
+            silent,
+        );
+    }));
+}
@@ -1,185 +1,172 @@
 // Prevent additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod bundle;
-mod dialogs;
-mod process;
-mod to_wide;
-mod windows;
-
-use crate::bundle::Bundle;
-use crate::bundle::{Application, WebView2};
-use crate::dialogs::{show_error_dialog, show_overwrite_repair_dialog};
-use crate::process::find_and_kill_processes_from_directory;
-use crate::windows::{get_free_space, get_local_app_data};
-
-use bundler::extract_package;
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use installer::error::{InstallError, EXIT_CODE_REBOOT_REQUIRED};
+use installer::install::{InstallOptions, Installer};
+use installer::logging::{self, default_log_path};
+use installer::platform::Platform;
+
+use anyhow::Context;
+use bundler::prerequisite::InstallOutcome;
+use bundler::scope::Scope;
+use bundler::try_extract_package;
+use clap::Parser;
+use log::{error, info};
 use std::path::{Path, PathBuf};
-use std::{fs, io};
 
-fn main() {
-    // Get name of the currently running bniary at runtime
-    let binary_name = PathBuf::from(
-        std::env::current_exe()
-            .unwrap()
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string(),
-    );
-
-    // Extract packages
-    let package = extract_package(&binary_name);
-    let manifest = &package.manifest;
-    println!("Application: {}", manifest.name);
-
-    // Handle bundled application
-    let app = Application::load(&package);
-    println!(
-        "Application size: {}",
-        format_bytes(app.data.len().try_into().unwrap())
-    );
+/// Dependencies of the setup stub, found in the compiled executable by the bundler for its SBOM
+#[used]
+static STUB_DEPENDENCIES: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/stub-dependencies.bin"));
+
+/// Tauri Windows Installer setup
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_override_self = true)]
+struct Args {
+    /// Install without any dialogs, running the prerequisite installers silently
+    #[arg(short, long)]
+    silent: bool,
+
+    /// Install for the current user only, if the package allows both
+    #[arg(long, conflicts_with = "per_machine")]
+    per_user: bool,
+
+    /// Install for every user in `%ProgramFiles%`, relaunching setup elevated
+    #[arg(long)]
+    per_machine: bool,
+
+    /// Directory to install to, instead of the package's `installDir` or an existing installation
+    #[arg(long, value_name = "PATH")]
+    install_dir: Option<PathBuf>,
+
+    /// Don't start the application once it's installed
+    #[arg(long)]
+    no_launch: bool,
+
+    /// Overwrite an existing installation without asking
+    #[arg(long)]
+    force: bool,
+
+    /// Install missing prerequisites without asking, required to install them with `--silent`
+    #[arg(long)]
+    accept_prereqs: bool,
+
+    /// Replace a newer installed version with this one
+    #[arg(long)]
+    allow_downgrade: bool,
+
+    /// Check the installed files against the package, reporting any missing or modified files
+    #[arg(long, conflicts_with = "repair")]
+    verify: bool,
+
+    /// Rewrite only the missing or modified installed files, leaving any other files alone
+    #[arg(long)]
+    repair: bool,
+
+    /// Write the install log to this file, instead of `%TEMP%\{identifier}-setup.log`
+    #[arg(long, value_name = "FILE")]
+    log: Option<PathBuf>,
+}
 
-    // Handle bundled WebView2 runtime
-    let webview2 = WebView2::load(&package);
-    println!("Webview2 bundled: {}", webview2.bundled);
-
-    // Check if WebView2 runtime is installed
-    let webview2_installed = webview2.installed;
-    println!("Webview2 installed: {}", webview2_installed);
-    if !webview2_installed {
-        println!("Installing webview2 runtime...");
-        webview2
-            .install(false, &PathBuf::new())
-            .expect("Failed to install webview2 runtime");
-    }
+fn main() {
+    let args = Args::parse();
+    logging::log_panics(args.silent);
 
-    // Determine the installation directory
-    println!("Determining install directory...");
-    let appdata = get_local_app_data().expect("Failed to get local app data path");
-    let root_path = Path::new(&appdata).join(&manifest.identifier);
-    if !root_path.exists() {
-        fs::create_dir_all(&root_path).expect("Failed to create installation directory");
-    }
-    let root_path_str = root_path.to_str().unwrap();
-    println!("Installation Directory: {:?}", root_path_str);
-
-    // Check if there is enough space to install the application
-    let required_space = app.size as u64;
-    println!("Required disk space: {}", format_bytes(required_space));
-
-    // Check if there is enough space to install the application
-    match get_free_space(root_path_str) {
-        Ok(free_space) => {
-            if free_space < required_space {
-                show_error_dialog(
-                    "Not enough disk space",
-                    &format!(
-                        "{} requires at least {} disk space to be installed. There is only {} available.",
-                        manifest.title,
-                        format_bytes(required_space),
-                        format_bytes(free_space)
-                    ),
-                );
-                return;
-            } else {
-                println!(
-                    "There is {} free space available at destination, this package requires {}.",
-                    format_bytes(free_space),
-                    format_bytes(required_space)
-                );
-            }
-        }
-        Err(e) => eprintln!("Error: {}", e),
+    // Log to the --log file straight away, the default log is named after the package identifier
+    if let Some(log_path) = &args.log {
+        init_logging(log_path);
     }
 
-    let mut root_path_renamed = String::new();
-
-    // Check if the application is already installed
-    if !is_directory_empty(&root_path).unwrap() {
-        let result =
-            show_overwrite_repair_dialog(&manifest.title, &manifest.name, &manifest.version, false);
+    let installer = Installer::new(
+        native_platform(),
+        InstallOptions {
+            silent: args.silent,
+            install_dir: args.install_dir.clone(),
+            launch: !args.no_launch && !args.repair,
+            force: args.force,
+            accept_prereqs: args.accept_prereqs,
+            allow_downgrade: args.allow_downgrade,
+            scope: match (args.per_user, args.per_machine) {
+                (true, _) => Some(Scope::PerUser),
+                (_, true) => Some(Scope::PerMachine),
+                _ => None,
+            },
+        },
+    );
 
-        if !result {
-            println!("User cancelled installation");
-            return;
+    let exit_code = match run(&args, &installer) {
+        Ok(InstallOutcome::Installed) => 0,
+        Ok(InstallOutcome::RebootRequired) => {
+            info!("A reboot is required to complete the installation");
+            EXIT_CODE_REBOOT_REQUIRED
         }
-        println!("User chose to overwrite existing installation.");
-
-        // Force stop the application if it is running
-        match find_and_kill_processes_from_directory(root_path_str) {
-            Ok(_) => println!("All processes from {} have been terminated.", root_path_str),
-            Err(e) => eprintln!("Failed to terminate processes: {}", e),
+        Err(InstallError::Cancelled) => {
+            info!("{}", InstallError::Cancelled);
+            InstallError::Cancelled.exit_code()
         }
-
-        // Rename the existing installation directory
-        root_path_renamed = format!("{}_{}", root_path_str, generate_random_string(8));
-        println!(
-            "Renaming existing directory to '{}' to allow rollback...",
-            root_path_renamed
-        );
-        fs::rename(&root_path, &root_path_renamed)
-            .expect("Failed to rename existing installation directory");
-    }
-
-    println!("Preparing and cleaning installation directory...");
-    remove_dir_all_ext::ensure_empty_dir(&root_path)
-        .expect("Failed to clean installation directory");
-
-    // Install the application
-    let quiet = false;
-    let install_result = app.install(quiet, &root_path);
-
-    // Handle rollback if installation fails
-    if install_result.is_ok() == false {
-        println!("Installation failed! {}", install_result.unwrap_err());
-        if !root_path_renamed.is_empty() {
-            println!("Rolling back installation...");
-            let _ = find_and_kill_processes_from_directory(root_path_str);
-            let _ = fs::remove_dir_all(&root_path);
-            let _ = fs::rename(&root_path_renamed, &root_path);
+        Err(e) => {
+            error!("{}", e);
+            installer.show_error(&e);
+            e.exit_code()
         }
+    };
 
-        // exit the installer with an error code
-        std::process::exit(1);
-    }
+    info!("Exiting with code {}", exit_code);
+    std::process::exit(exit_code);
+}
 
-    println!("Installation completed successfully!");
-    if !root_path_renamed.is_empty() {
-        println!("Removing rollback directory...");
-        let _ = fs::remove_dir_all(&root_path_renamed);
-    }
+#[cfg(windows)]
+fn native_platform() -> Platform {
+    Platform::native()
+}
 
-    // Write the uninstall registry keys
-    windows::write_uninstall_entry(&manifest, &root_path)
-        .expect("Failed to write uninstall registry key");
+/// Setup only runs on Windows, the install flow is tested on other platforms with fakes
+#[cfg(not(windows))]
+fn native_platform() -> Platform {
+    eprintln!("Setup can only be run on Windows");
+    std::process::exit(1);
 }
 
-fn format_bytes(bytes: u64) -> String {
-    let units = ["B", "KB", "MB", "GB", "TB", "PB", "EB"];
-    let mut size = bytes as f64;
-    let mut unit_index = 0;
+/// Extracts the package bundled in the running executable, and installs it
+fn run(args: &Args, installer: &Installer) -> Result<InstallOutcome, InstallError> {
+    // Get the path of the currently running binary at runtime
+    let binary_name = std::env::current_exe()
+        .context("Failed to get the path of the setup executable")
+        .map_err(InstallError::CorruptPackage)?;
 
-    while size >= 1024.0 && unit_index < units.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
+    // Extract packages
+    let package = try_extract_package(&binary_name).map_err(InstallError::CorruptPackage)?;
+    if args.log.is_none() {
+        init_logging(&default_log_path(&package.manifest.identifier));
     }
+    info!("{:?}", args);
+    info!(
+        "Extracted {} files from {:?}",
+        package.files.len(),
+        binary_name
+    );
 
-    format!("{:.2} {}", size, units[unit_index])
-}
+    // Installing per-machine needs an administrator, setup runs again elevated to do it
+    if !args.verify && installer.needs_elevation(&package.manifest)? {
+        return installer.install_elevated(&package.manifest);
+    }
 
-fn is_directory_empty(path: &Path) -> io::Result<bool> {
-    let mut entries = fs::read_dir(path)?;
-    Ok(entries.next().is_none())
+    if args.verify {
+        return installer
+            .verify(&package)
+            .map(|_| InstallOutcome::Installed);
+    }
+    if args.repair {
+        return installer
+            .repair(&package)
+            .map(|_| InstallOutcome::Installed);
+    }
+    installer.install(&package)
 }
 
-fn generate_random_string(length: usize) -> String {
-    let rng = thread_rng();
-    rng.sample_iter(&Alphanumeric)
-        .take(length)
-        .map(char::from)
-        .collect()
+/// Logs to the file, reporting on the console if it can't be created
+fn init_logging(log_path: &Path) {
+    if let Err(e) = logging::init(log_path) {
+        eprintln!("{:#}", e);
+    }
 }
@@ -0,0 +1,224 @@
+//! The parts of the system the install flow touches, behind traits so it can be tested off Windows
+
+pub mod fake;
+#[cfg(windows)]
+pub(crate) mod windows;
+
+use anyhow::Result;
+use bundler::prerequisite::{RegistryRoot, SystemProbe};
+use bundler::scope::Scope;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Reads and writes files, so faults can be injected into the install transaction
+pub trait FileSystem {
+    fn exists(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Writes a file, its directory must exist
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Renames a file or directory, replacing an existing file
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Paths of the entries in a directory
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    /// Total size of the files in a directory
+    fn dir_size(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// The real file system
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        remove_dir_all_ext::remove_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn dir_size(&self, path: &Path) -> io::Result<u64> {
+        fs_extra::dir::get_size(path).map_err(io::Error::other)
+    }
+}
+
+/// Locates the known folders of the current user
+pub trait KnownFolders {
+    /// `%LOCALAPPDATA%`, the default parent of the installation directory
+    fn local_app_data(&self) -> Result<PathBuf>;
+    /// `%ProgramFiles%`, the default parent of per-machine installation directories
+    fn program_files(&self) -> Result<PathBuf>;
+    /// The Start menu `Programs` folder of the current user, or of all users per-machine
+    fn programs(&self, scope: Scope) -> Result<PathBuf>;
+    /// The desktop of the current user, or the public desktop per-machine
+    fn desktop(&self, scope: Scope) -> Result<PathBuf>;
+}
+
+/// Queries the disks
+pub trait DiskInfo {
+    /// Free disk space available to the current user on the disk of the path
+    fn free_space(&self, path: &Path) -> Result<u64>;
+}
+
+/// A registry value, only the types the installer writes are supported
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RegistryValue {
+    String(String),
+    U32(u32),
+}
+
+impl From<&str> for RegistryValue {
+    fn from(value: &str) -> Self {
+        RegistryValue::String(value.to_string())
+    }
+}
+
+impl From<String> for RegistryValue {
+    fn from(value: String) -> Self {
+        RegistryValue::String(value)
+    }
+}
+
+impl From<u32> for RegistryValue {
+    fn from(value: u32) -> Self {
+        RegistryValue::U32(value)
+    }
+}
+
+/// Reads and writes the registry
+pub trait Registry {
+    /// Sets a value, creating the key if it doesn't exist
+    fn set_value(
+        &self,
+        root: RegistryRoot,
+        key: &str,
+        name: &str,
+        value: RegistryValue,
+    ) -> Result<()>;
+    /// Gets a value, if the key and value exist
+    fn value(&self, root: RegistryRoot, key: &str, name: &str) -> Option<RegistryValue>;
+    /// Deletes a value, succeeding if it doesn't exist
+    fn delete_value(&self, root: RegistryRoot, key: &str, name: &str) -> Result<()>;
+    /// Deletes a key and all its subkeys, succeeding if it doesn't exist
+    fn delete_key(&self, root: RegistryRoot, key: &str) -> Result<()>;
+    /// Whether the key exists
+    fn key_exists(&self, root: RegistryRoot, key: &str) -> bool;
+}
+
+/// Manages running processes
+pub trait ProcessManager {
+    /// Terminates all processes running an executable from the directory
+    fn kill_processes_in(&self, directory: &Path) -> Result<()>;
+}
+
+/// Asks the user, never called when installing silently
+pub trait Dialogs {
+    /// Asks to repair or replace an existing installation, with a message for how it's replaced
+    fn confirm_overwrite(&self, title: &str, version: &str, message: &str) -> bool;
+    /// Asks to install the missing prerequisites
+    fn confirm_prerequisites(&self, title: &str, version: &str, prerequisites: &[String]) -> bool;
+    /// Shows an error
+    fn show_error(&self, title: &str, message: &str);
+    /// Shows information, such as the result of verifying the installation
+    fn show_info(&self, title: &str, message: &str);
+}
+
+/// Runs setup as an administrator, for per-machine installs
+pub trait Elevation {
+    /// Whether setup is running elevated
+    fn is_elevated(&self) -> bool;
+    /// Runs setup again elevated with its arguments and the extra arguments, waiting for it to exit.
+    /// It logs to its own file. Returns its exit code, or `None` if the UAC prompt was declined
+    fn relaunch_elevated(&self, extra_args: &[&str]) -> Result<Option<i32>>;
+}
+
+/// Starts the installed application
+pub trait Launcher {
+    /// Starts the executable as a detached process
+    fn launch(&self, exe_path: &Path) -> Result<()>;
+}
+
+/// Notifies the Windows shell of changes
+pub trait Shell {
+    /// Tells Explorer the file associations changed, so it refreshes their icons and handlers
+    fn notify_associations_changed(&self);
+}
+
+/// Detects the WebView2 runtime, and downloads and runs the installers of the prerequisites
+pub trait Installers {
+    /// Version of the installed WebView2 runtime, if any
+    fn webview2_version(&self) -> Option<String>;
+    /// Downloads a file from the URL to the path
+    fn download(&self, url: &str, path: &Path) -> Result<()>;
+    /// Runs an installer and waits for it to exit, returning its exit code
+    fn run(&self, program: &Path, args: &[String]) -> Result<i32>;
+}
+
+/// Everything the install flow needs from the system
+pub struct Platform {
+    pub fs: Box<dyn FileSystem>,
+    pub folders: Box<dyn KnownFolders>,
+    pub disk: Box<dyn DiskInfo>,
+    pub registry: Box<dyn Registry>,
+    pub processes: Box<dyn ProcessManager>,
+    pub dialogs: Box<dyn Dialogs>,
+    pub launcher: Box<dyn Launcher>,
+    pub elevation: Box<dyn Elevation>,
+    pub shell: Box<dyn Shell>,
+    pub installers: Box<dyn Installers>,
+    /// Detects installed prerequisites
+    pub system: Box<dyn SystemProbe>,
+}
+
+#[cfg(windows)]
+impl Platform {
+    /// The running Windows system
+    pub fn native() -> Self {
+        use self::windows::*;
+
+        Platform {
+            fs: Box::new(StdFileSystem),
+            folders: Box::new(WindowsKnownFolders),
+            disk: Box::new(WindowsDiskInfo),
+            registry: Box::new(WindowsRegistry),
+            processes: Box::new(WindowsProcessManager),
+            dialogs: Box::new(WindowsDialogs),
+            launcher: Box::new(WindowsLauncher),
+            elevation: Box::new(WindowsElevation),
+            shell: Box::new(WindowsShell),
+            installers: Box::new(WindowsInstallers),
+            system: Box::new(WindowsSystem),
+        }
+    }
+}
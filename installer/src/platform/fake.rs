@@ -0,0 +1,607 @@
+//! In-memory fakes of the platform, recording what the install flow did
+//!
+//! Each fake shares its state between clones, so a test keeps a clone to inspect after boxing
+//! another into a [`Platform`].
+
+use super::{
+    Dialogs, DiskInfo, Elevation, FileSystem, Installers, KnownFolders, Launcher, Platform,
+    ProcessManager, Registry, RegistryValue, Shell,
+};
+
+use anyhow::{anyhow, Result};
+use bundler::prerequisite::{RegistryRoot, SystemProbe};
+use bundler::scope::Scope;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+enum FakeEntry {
+    Dir,
+    File(Vec<u8>),
+}
+
+/// An in-memory file system, which crashes after a number of changes to inject faults
+///
+/// Once crashed every change fails, like the machine lost power, until [`FakeFileSystem::restart`].
+/// Writes to single files can also be made to fail, like the file is locked.
+#[derive(Clone, Default)]
+pub struct FakeFileSystem {
+    entries: Rc<RefCell<BTreeMap<PathBuf, FakeEntry>>>,
+    changes: Rc<Cell<usize>>,
+    crash_after: Rc<Cell<Option<usize>>>,
+    locked: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", path))
+}
+
+impl FakeFileSystem {
+    /// Crashes once this many more changes have been made
+    pub fn crash_after(&self, changes: usize) {
+        self.crash_after.set(Some(self.changes.get() + changes));
+    }
+
+    /// Fails every write to the file, like it's locked by another process
+    pub fn lock(&self, path: &Path) {
+        self.locked.borrow_mut().push(path.to_path_buf());
+    }
+
+    /// Stops crashing, like setup was run again after a reboot
+    pub fn restart(&self) {
+        self.crash_after.set(None);
+    }
+
+    /// Number of changes made
+    pub fn changes(&self) -> usize {
+        self.changes.get()
+    }
+
+    /// All the files and directories
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.borrow().keys().cloned().collect()
+    }
+
+    fn change(&self) -> io::Result<()> {
+        if self
+            .crash_after
+            .get()
+            .is_some_and(|crash_after| self.changes.get() >= crash_after)
+        {
+            return Err(io::Error::other("Injected fault"));
+        }
+        self.changes.set(self.changes.get() + 1);
+        Ok(())
+    }
+
+    fn entry(&self, path: &Path) -> Option<FakeEntry> {
+        self.entries.borrow().get(path).cloned()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.entry(path) == Some(FakeEntry::Dir)
+    }
+
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !self.is_dir(parent) => {
+                Err(not_found(parent))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FileSystem for FakeFileSystem {
+    fn exists(&self, path: &Path) -> bool {
+        self.entry(path).is_some()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.entry(path) {
+            Some(FakeEntry::File(data)) => Ok(data),
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.check_parent(path)?;
+        if self.locked.borrow().iter().any(|locked| locked == path) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{:?} is locked", path),
+            ));
+        }
+        if self.is_dir(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} is a directory", path),
+            ));
+        }
+        self.change()?;
+        self.entries
+            .borrow_mut()
+            .insert(path.to_path_buf(), FakeEntry::File(data.to_vec()));
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        if self.is_dir(path) {
+            return Ok(());
+        }
+        self.change()?;
+        let mut entries = self.entries.borrow_mut();
+        for ancestor in path.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            match entries.get(ancestor) {
+                Some(FakeEntry::File(_)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{:?} is a file", ancestor),
+                    ))
+                }
+                Some(FakeEntry::Dir) => {}
+                None => {
+                    entries.insert(ancestor.to_path_buf(), FakeEntry::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let entry = self.entry(from).ok_or_else(|| not_found(from))?;
+        self.check_parent(to)?;
+        match (&entry, self.entry(to)) {
+            (_, None) | (FakeEntry::File(_), Some(FakeEntry::File(_))) => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{:?} already exists", to),
+                ))
+            }
+        }
+        self.change()?;
+
+        let mut entries = self.entries.borrow_mut();
+        let moved: Vec<PathBuf> = entries
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+        for path in moved {
+            let entry = entries.remove(&path).unwrap();
+            let relative = path.strip_prefix(from).unwrap();
+            let destination = if relative.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(relative)
+            };
+            entries.insert(destination, entry);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.entry(path) {
+            Some(FakeEntry::File(_)) => {
+                self.change()?;
+                self.entries.borrow_mut().remove(path);
+                Ok(())
+            }
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        if !self.is_dir(path) {
+            return Err(not_found(path));
+        }
+        self.change()?;
+        self.entries
+            .borrow_mut()
+            .retain(|entry, _| !entry.starts_with(path));
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(not_found(path));
+        }
+        Ok(self
+            .entries
+            .borrow()
+            .keys()
+            .filter(|entry| entry.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn dir_size(&self, path: &Path) -> io::Result<u64> {
+        if !self.is_dir(path) {
+            return Err(not_found(path));
+        }
+        Ok(self
+            .entries
+            .borrow()
+            .iter()
+            .filter(|(entry, _)| entry.starts_with(path))
+            .map(|(_, entry)| match entry {
+                FakeEntry::File(data) => data.len() as u64,
+                FakeEntry::Dir => 0,
+            })
+            .sum())
+    }
+}
+
+/// Known folders under a root directory, usually a temp directory
+#[derive(Clone)]
+pub struct FakeKnownFolders {
+    pub local_app_data: PathBuf,
+    pub program_files: PathBuf,
+    pub programs: PathBuf,
+    pub common_programs: PathBuf,
+    pub desktop: PathBuf,
+    pub public_desktop: PathBuf,
+}
+
+impl KnownFolders for FakeKnownFolders {
+    fn local_app_data(&self) -> Result<PathBuf> {
+        Ok(self.local_app_data.clone())
+    }
+
+    fn program_files(&self) -> Result<PathBuf> {
+        Ok(self.program_files.clone())
+    }
+
+    fn programs(&self, scope: Scope) -> Result<PathBuf> {
+        Ok(match scope {
+            Scope::PerUser => self.programs.clone(),
+            Scope::PerMachine => self.common_programs.clone(),
+        })
+    }
+
+    fn desktop(&self, scope: Scope) -> Result<PathBuf> {
+        Ok(match scope {
+            Scope::PerUser => self.desktop.clone(),
+            Scope::PerMachine => self.public_desktop.clone(),
+        })
+    }
+}
+
+/// A disk with a fixed amount of free space, unknown when `None`
+#[derive(Clone)]
+pub struct FakeDiskInfo {
+    pub free_space: Option<u64>,
+}
+
+impl DiskInfo for FakeDiskInfo {
+    fn free_space(&self, path: &Path) -> Result<u64> {
+        self.free_space
+            .ok_or_else(|| anyhow!("Failed to retrieve free disk space for path: {:?}", path))
+    }
+}
+
+/// Values by name, of keys by root and lowercase path
+type RegistryKeys = BTreeMap<(String, String), BTreeMap<String, RegistryValue>>;
+
+/// Registry keys, as their values by name
+#[derive(Clone, Default)]
+pub struct FakeRegistry {
+    keys: Rc<RefCell<RegistryKeys>>,
+}
+
+impl FakeRegistry {
+    fn key_name(root: RegistryRoot, key: &str) -> (String, String) {
+        (format!("{:?}", root), key.to_lowercase())
+    }
+
+    /// The values of a key, if it exists
+    pub fn key(&self, root: RegistryRoot, key: &str) -> Option<BTreeMap<String, RegistryValue>> {
+        self.keys.borrow().get(&Self::key_name(root, key)).cloned()
+    }
+}
+
+impl Registry for FakeRegistry {
+    fn set_value(
+        &self,
+        root: RegistryRoot,
+        key: &str,
+        name: &str,
+        value: RegistryValue,
+    ) -> Result<()> {
+        self.keys
+            .borrow_mut()
+            .entry(Self::key_name(root, key))
+            .or_default()
+            .insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn value(&self, root: RegistryRoot, key: &str, name: &str) -> Option<RegistryValue> {
+        self.key(root, key)?.get(name).cloned()
+    }
+
+    fn delete_value(&self, root: RegistryRoot, key: &str, name: &str) -> Result<()> {
+        if let Some(values) = self.keys.borrow_mut().get_mut(&Self::key_name(root, key)) {
+            values.remove(name);
+        }
+        Ok(())
+    }
+
+    fn delete_key(&self, root: RegistryRoot, key: &str) -> Result<()> {
+        let (root, key) = Self::key_name(root, key);
+        let subkeys = format!("{}\\", key);
+        self.keys
+            .borrow_mut()
+            .retain(|(r, k), _| *r != root || (*k != key && !k.starts_with(&subkeys)));
+        Ok(())
+    }
+
+    /// A key exists if it has values or subkeys, like the parents of the keys in the registry
+    fn key_exists(&self, root: RegistryRoot, key: &str) -> bool {
+        let (root, key) = Self::key_name(root, key);
+        let subkeys = format!("{}\\", key);
+        self.keys
+            .borrow()
+            .keys()
+            .any(|(r, k)| *r == root && (*k == key || k.starts_with(&subkeys)))
+    }
+}
+
+/// Detects prerequisites from the fake registry, no files or products are installed
+impl SystemProbe for FakeRegistry {
+    fn registry_key_exists(&self, root: RegistryRoot, key: &str) -> bool {
+        self.key(root, key).is_some()
+    }
+
+    fn registry_value(&self, root: RegistryRoot, key: &str, value: &str) -> Option<String> {
+        match Registry::value(self, root, key, value)? {
+            RegistryValue::String(value) => Some(value),
+            RegistryValue::U32(value) => Some(value.to_string()),
+        }
+    }
+
+    fn env_var(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    fn file_exists(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn file_version(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    fn product_installed(&self, _product_code: &str) -> bool {
+        false
+    }
+}
+
+/// Records the directories whose processes were killed
+#[derive(Clone, Default)]
+pub struct FakeProcessManager {
+    pub killed: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl ProcessManager for FakeProcessManager {
+    fn kill_processes_in(&self, directory: &Path) -> Result<()> {
+        self.killed.borrow_mut().push(directory.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Answers every question the same way, recording the dialogs shown
+#[derive(Clone)]
+pub struct FakeDialogs {
+    pub answer: bool,
+    pub shown: Rc<RefCell<Vec<String>>>,
+}
+
+impl FakeDialogs {
+    pub fn new(answer: bool) -> Self {
+        FakeDialogs {
+            answer,
+            shown: Rc::default(),
+        }
+    }
+}
+
+impl Dialogs for FakeDialogs {
+    fn confirm_overwrite(&self, title: &str, _version: &str, message: &str) -> bool {
+        self.shown
+            .borrow_mut()
+            .push(format!("overwrite: {}: {}", title, message));
+        self.answer
+    }
+
+    fn confirm_prerequisites(&self, title: &str, _version: &str, prerequisites: &[String]) -> bool {
+        self.shown.borrow_mut().push(format!(
+            "prerequisites: {}: {}",
+            title,
+            prerequisites.join(", ")
+        ));
+        self.answer
+    }
+
+    fn show_error(&self, title: &str, message: &str) {
+        self.shown
+            .borrow_mut()
+            .push(format!("error: {}: {}", title, message));
+    }
+
+    fn show_info(&self, title: &str, message: &str) {
+        self.shown
+            .borrow_mut()
+            .push(format!("info: {}: {}", title, message));
+    }
+}
+
+/// Records the launched executables, failing to launch when `fail` is set
+#[derive(Clone, Default)]
+pub struct FakeLauncher {
+    pub fail: bool,
+    pub launched: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl Launcher for FakeLauncher {
+    fn launch(&self, exe_path: &Path) -> Result<()> {
+        if self.fail {
+            return Err(anyhow!("Failed to start application ({:?}).", exe_path));
+        }
+        self.launched.borrow_mut().push(exe_path.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Records the arguments setup was relaunched elevated with, exiting with `exit_code` or declining
+/// the UAC prompt when `None`
+#[derive(Clone)]
+pub struct FakeElevation {
+    pub elevated: bool,
+    pub exit_code: Option<i32>,
+    pub relaunched: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl Elevation for FakeElevation {
+    fn is_elevated(&self) -> bool {
+        self.elevated
+    }
+
+    fn relaunch_elevated(&self, extra_args: &[&str]) -> Result<Option<i32>> {
+        self.relaunched
+            .borrow_mut()
+            .push(extra_args.iter().map(|arg| arg.to_string()).collect());
+        Ok(self.exit_code)
+    }
+}
+
+/// Counts the notifications of changed file associations
+#[derive(Clone, Default)]
+pub struct FakeShell {
+    pub associations_changed: Rc<Cell<usize>>,
+}
+
+impl Shell for FakeShell {
+    fn notify_associations_changed(&self) {
+        self.associations_changed
+            .set(self.associations_changed.get() + 1);
+    }
+}
+
+/// File names of the installers run, with their arguments
+type InstallerRuns = Vec<(String, Vec<String>)>;
+
+/// Records the downloads and the installers run, by their file name and arguments, exiting with
+/// `exit_code` or failing to download when `fail_downloads` is set
+#[derive(Clone, Default)]
+pub struct FakeInstallers {
+    pub webview2_version: Option<String>,
+    pub fail_downloads: bool,
+    pub exit_code: i32,
+    pub downloaded: Rc<RefCell<Vec<String>>>,
+    pub ran: Rc<RefCell<InstallerRuns>>,
+}
+
+impl Installers for FakeInstallers {
+    fn webview2_version(&self) -> Option<String> {
+        self.webview2_version.clone()
+    }
+
+    fn download(&self, url: &str, _path: &Path) -> Result<()> {
+        if self.fail_downloads {
+            return Err(anyhow!("Failed to download {}", url));
+        }
+        self.downloaded.borrow_mut().push(url.to_string());
+        Ok(())
+    }
+
+    fn run(&self, program: &Path, args: &[String]) -> Result<i32> {
+        let name = program.file_name().unwrap_or_default();
+        self.ran
+            .borrow_mut()
+            .push((name.to_string_lossy().to_string(), args.to_vec()));
+        Ok(self.exit_code)
+    }
+}
+
+/// A fake of every part of the platform, with plenty of disk space and a dialog answering yes
+#[derive(Clone)]
+pub struct FakePlatform {
+    pub fs: FakeFileSystem,
+    pub folders: FakeKnownFolders,
+    pub disk: FakeDiskInfo,
+    pub registry: FakeRegistry,
+    pub processes: FakeProcessManager,
+    pub dialogs: FakeDialogs,
+    pub launcher: FakeLauncher,
+    pub elevation: FakeElevation,
+    pub shell: FakeShell,
+    pub installers: FakeInstallers,
+}
+
+impl FakePlatform {
+    /// A platform with an empty file system, other than the local app data folder and the
+    /// `Program Files`, Start menu and desktop folders next to it, running unelevated
+    pub fn new(local_app_data: &Path) -> Self {
+        let folders = FakeKnownFolders {
+            local_app_data: local_app_data.to_path_buf(),
+            program_files: local_app_data.with_file_name("Program Files"),
+            programs: local_app_data.with_file_name("Start Menu/Programs"),
+            common_programs: local_app_data.with_file_name("ProgramData/Start Menu/Programs"),
+            desktop: local_app_data.with_file_name("Desktop"),
+            public_desktop: local_app_data.with_file_name("Public/Desktop"),
+        };
+        let fs = FakeFileSystem::default();
+        for folder in [
+            &folders.local_app_data,
+            &folders.program_files,
+            &folders.programs,
+            &folders.common_programs,
+            &folders.desktop,
+            &folders.public_desktop,
+        ] {
+            fs.create_dir_all(folder)
+                .expect("Failed to create known folder");
+        }
+
+        FakePlatform {
+            fs,
+            folders,
+            disk: FakeDiskInfo {
+                free_space: Some(u64::MAX),
+            },
+            registry: FakeRegistry::default(),
+            processes: FakeProcessManager::default(),
+            dialogs: FakeDialogs::new(true),
+            launcher: FakeLauncher::default(),
+            elevation: FakeElevation {
+                elevated: false,
+                exit_code: Some(0),
+                relaunched: Rc::default(),
+            },
+            shell: FakeShell::default(),
+            installers: FakeInstallers::default(),
+        }
+    }
+
+    /// Boxes clones of the fakes, sharing their state with this
+    pub fn platform(&self) -> Platform {
+        Platform {
+            fs: Box::new(self.fs.clone()),
+            folders: Box::new(self.folders.clone()),
+            disk: Box::new(self.disk.clone()),
+            registry: Box::new(self.registry.clone()),
+            processes: Box::new(self.processes.clone()),
+            dialogs: Box::new(self.dialogs.clone()),
+            launcher: Box::new(self.launcher.clone()),
+            elevation: Box::new(self.elevation.clone()),
+            shell: Box::new(self.shell.clone()),
+            installers: Box::new(self.installers.clone()),
+            system: Box::new(self.registry.clone()),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    Dialogs, DiskInfo, Elevation, Installers, KnownFolders, Launcher, ProcessManager, Registry,
+    RegistryValue, Shell,
+};
+use crate::dialogs::{
+    show_error_dialog, show_info_dialog, show_overwrite_dialog, show_prerequisites_dialog,
+};
+use crate::process::{find_and_kill_processes_from_directory, spawn_detached_process};
+
+use ::windows::core::PCWSTR;
+use anyhow::{anyhow, Context, Result};
+use bundler::elevation::{is_elevated, relaunch_elevated};
+use bundler::prerequisite::{RegistryRoot, SystemProbe};
+use bundler::scope::Scope;
+use log::info;
+use windows::{
+    core::{GUID, PWSTR},
+    Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW,
+        VS_FIXEDFILEINFO,
+    },
+    Win32::System::ApplicationInstallationAndServicing::{
+        MsiQueryProductStateW, INSTALLSTATE_DEFAULT,
+    },
+    Win32::System::Com::Urlmon::URLDownloadToFileW,
+    Win32::UI::Shell::{
+        FOLDERID_CommonPrograms, FOLDERID_Desktop, FOLDERID_LocalAppData, FOLDERID_ProgramFiles,
+        FOLDERID_Programs, FOLDERID_PublicDesktop, SHChangeNotify, SHGetKnownFolderPath,
+        SHCNE_ASSOCCHANGED, SHCNF_IDLIST,
+    },
+};
+use winreg::enums::*;
+use winreg::RegKey;
+
+pub fn get_local_app_data() -> Result<String> {
+    get_known_folder(&FOLDERID_LocalAppData)
+}
+
+pub fn get_program_files() -> Result<String> {
+    get_known_folder(&FOLDERID_ProgramFiles)
+}
+
+fn get_known_folder(folder_id: *const GUID) -> Result<String> {
+    unsafe {
+        let flag = windows::Win32::UI::Shell::KNOWN_FOLDER_FLAG(0);
+        let result = SHGetKnownFolderPath(folder_id, flag, None)
+            .context("Failed to get known folder path")?;
+        pwstr_to_string(result)
+    }
+}
+
+fn pwstr_to_string(input: PWSTR) -> Result<String> {
+    unsafe {
+        let hstring = input.to_hstring()?;
+        let string = hstring.to_string_lossy();
+        Ok(string.trim_end_matches('\0').to_string())
+    }
+}
+
+fn string_to_u16<P: AsRef<str>>(input: P) -> Vec<u16> {
+    let input = input.as_ref();
+    input.encode_utf16().chain(Some(0)).collect::<Vec<u16>>()
+}
+
+/// Gets the free disk space for the provided path.
+pub fn get_free_space(root_path_str: &str) -> Result<u64> {
+    let mut free_space: u64 = 0;
+    let root_pcwstr = string_to_u16(root_path_str);
+    let root_pcwstr: PCWSTR = PCWSTR(root_pcwstr.as_ptr());
+
+    let result = unsafe { GetDiskFreeSpaceExW(root_pcwstr, None, None, Some(&mut free_space)) };
+
+    if result.is_err() {
+        return Err(anyhow!(
+            "Failed to retrieve free disk space for path: {}",
+            root_path_str
+        ));
+    }
+
+    Ok(free_space)
+}
+
+/// Downloads a file from the URL to the provided path.
+fn download_file(url: &str, path: &Path) -> Result<()> {
+    let url_wide = string_to_u16(url);
+    let path_wide = string_to_u16(path.to_string_lossy());
+
+    unsafe {
+        URLDownloadToFileW(
+            None,
+            PCWSTR(url_wide.as_ptr()),
+            PCWSTR(path_wide.as_ptr()),
+            0,
+            None,
+        )
+    }
+    .map_err(|e| anyhow!("Failed to download {}: {}", url, e))
+}
+
+/// Version of the installed WebView2 runtime, if any
+fn webview2_version() -> Option<String> {
+    use webview2_com::{Microsoft::Web::WebView2::Win32::*, *};
+    let mut versioninfo = PWSTR::null();
+    let result =
+        unsafe { GetAvailableCoreWebView2BrowserVersionString(PCWSTR::null(), &mut versioninfo) };
+
+    if result.is_err() || versioninfo == PWSTR::null() {
+        return None;
+    }
+
+    let version = take_pwstr(versioninfo);
+    if version.len() > 0 {
+        info!("WebView2 version: {}", version);
+        return Some(version);
+    }
+
+    None
+}
+
+fn predef_key(root: RegistryRoot) -> RegKey {
+    let root = match root {
+        RegistryRoot::LocalMachine => HKEY_LOCAL_MACHINE,
+        RegistryRoot::CurrentUser => HKEY_CURRENT_USER,
+        RegistryRoot::ClassesRoot => HKEY_CLASSES_ROOT,
+    };
+    RegKey::predef(root)
+}
+
+pub struct WindowsKnownFolders;
+
+impl KnownFolders for WindowsKnownFolders {
+    fn local_app_data(&self) -> Result<PathBuf> {
+        get_local_app_data().map(PathBuf::from)
+    }
+
+    fn program_files(&self) -> Result<PathBuf> {
+        get_program_files().map(PathBuf::from)
+    }
+
+    fn programs(&self, scope: Scope) -> Result<PathBuf> {
+        let folder_id = match scope {
+            Scope::PerUser => &FOLDERID_Programs,
+            Scope::PerMachine => &FOLDERID_CommonPrograms,
+        };
+        get_known_folder(folder_id).map(PathBuf::from)
+    }
+
+    fn desktop(&self, scope: Scope) -> Result<PathBuf> {
+        let folder_id = match scope {
+            Scope::PerUser => &FOLDERID_Desktop,
+            Scope::PerMachine => &FOLDERID_PublicDesktop,
+        };
+        get_known_folder(folder_id).map(PathBuf::from)
+    }
+}
+
+pub struct WindowsDiskInfo;
+
+impl DiskInfo for WindowsDiskInfo {
+    fn free_space(&self, path: &Path) -> Result<u64> {
+        get_free_space(&path.to_string_lossy())
+    }
+}
+
+pub struct WindowsRegistry;
+
+impl Registry for WindowsRegistry {
+    fn set_value(
+        &self,
+        root: RegistryRoot,
+        key: &str,
+        name: &str,
+        value: RegistryValue,
+    ) -> Result<()> {
+        let (key, _) = predef_key(root)
+            .create_subkey(key)
+            .with_context(|| format!("Failed to create registry key {}", key))?;
+        match value {
+            RegistryValue::String(value) => key.set_value(name, &value),
+            RegistryValue::U32(value) => key.set_value(name, &value),
+        }
+        .with_context(|| format!("Failed to set registry value {}", name))
+    }
+
+    fn value(&self, root: RegistryRoot, key: &str, name: &str) -> Option<RegistryValue> {
+        let key = predef_key(root).open_subkey(key).ok()?;
+        key.get_value::<String, _>(name)
+            .map(RegistryValue::String)
+            .or_else(|_| key.get_value::<u32, _>(name).map(RegistryValue::U32))
+            .ok()
+    }
+
+    fn delete_value(&self, root: RegistryRoot, key: &str, name: &str) -> Result<()> {
+        let Ok(key) = predef_key(root).open_subkey_with_flags(key, KEY_SET_VALUE) else {
+            return Ok(());
+        };
+        match key.delete_value(name) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result.with_context(|| format!("Failed to delete registry value {}", name)),
+        }
+    }
+
+    fn delete_key(&self, root: RegistryRoot, key: &str) -> Result<()> {
+        match predef_key(root).delete_subkey_all(key) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result.with_context(|| format!("Failed to delete registry key {}", key)),
+        }
+    }
+    fn key_exists(&self, root: RegistryRoot, key: &str) -> bool {
+        predef_key(root).open_subkey(key).is_ok()
+    }
+}
+
+pub struct WindowsProcessManager;
+
+impl ProcessManager for WindowsProcessManager {
+    fn kill_processes_in(&self, directory: &Path) -> Result<()> {
+        find_and_kill_processes_from_directory(&directory.to_string_lossy())
+            .map_err(|e| anyhow!("{}", e))
+    }
+}
+
+pub struct WindowsDialogs;
+
+impl Dialogs for WindowsDialogs {
+    fn confirm_overwrite(&self, title: &str, version: &str, message: &str) -> bool {
+        show_overwrite_dialog(title, version, message, false)
+    }
+
+    fn confirm_prerequisites(&self, title: &str, version: &str, prerequisites: &[String]) -> bool {
+        show_prerequisites_dialog(title, version, prerequisites, false)
+    }
+
+    fn show_error(&self, title: &str, message: &str) {
+        show_error_dialog(title, message, false)
+    }
+
+    fn show_info(&self, title: &str, message: &str) {
+        show_info_dialog(title, message, false)
+    }
+}
+
+pub struct WindowsElevation;
+
+impl Elevation for WindowsElevation {
+    fn is_elevated(&self) -> bool {
+        is_elevated()
+    }
+
+    fn relaunch_elevated(&self, extra_args: &[&str]) -> Result<Option<i32>> {
+        relaunch_elevated(extra_args, crate::logging::log_path())
+    }
+}
+
+pub struct WindowsLauncher;
+
+impl Launcher for WindowsLauncher {
+    fn launch(&self, exe_path: &Path) -> Result<()> {
+        spawn_detached_process(exe_path.to_path_buf())
+    }
+}
+
+pub struct WindowsShell;
+
+impl Shell for WindowsShell {
+    fn notify_associations_changed(&self) {
+        unsafe { SHChangeNotify(SHCNE_ASSOCCHANGED, SHCNF_IDLIST, None, None) };
+    }
+}
+
+pub struct WindowsInstallers;
+
+impl Installers for WindowsInstallers {
+    fn webview2_version(&self) -> Option<String> {
+        webview2_version()
+    }
+
+    fn download(&self, url: &str, path: &Path) -> Result<()> {
+        download_file(url, path)
+    }
+
+    fn run(&self, program: &Path, args: &[String]) -> Result<i32> {
+        std::process::Command::new(program)
+            .args(args)
+            .spawn()
+            .with_context(|| format!("Failed to run {:?}", program))?
+            .wait()?
+            .code()
+            .ok_or_else(|| anyhow!("Unable to get installer exit code."))
+    }
+}
+
+/// Detects prerequisites on the running system
+pub struct WindowsSystem;
+
+impl WindowsSystem {
+    fn open_key(root: RegistryRoot, key: &str) -> Option<RegKey> {
+        predef_key(root).open_subkey(key).ok()
+    }
+}
+
+impl SystemProbe for WindowsSystem {
+    fn registry_key_exists(&self, root: RegistryRoot, key: &str) -> bool {
+        Self::open_key(root, key).is_some()
+    }
+
+    fn registry_value(&self, root: RegistryRoot, key: &str, value: &str) -> Option<String> {
+        let key = Self::open_key(root, key)?;
+        key.get_value::<String, _>(value)
+            .or_else(|_| key.get_value::<u32, _>(value).map(|v| v.to_string()))
+            .or_else(|_| key.get_value::<u64, _>(value).map(|v| v.to_string()))
+            .ok()
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn file_exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn file_version(&self, path: &Path) -> Option<String> {
+        let path_wide = string_to_u16(path.to_string_lossy());
+        let path_pcwstr = PCWSTR(path_wide.as_ptr());
+
+        unsafe {
+            let size = GetFileVersionInfoSizeW(path_pcwstr, None);
+            if size == 0 {
+                return None;
+            }
+            let mut data = vec![0u8; size as usize];
+            GetFileVersionInfoW(path_pcwstr, 0, size, data.as_mut_ptr() as _).ok()?;
+
+            let root = string_to_u16("\\");
+            let mut info: *mut std::ffi::c_void = std::ptr::null_mut();
+            let mut info_len = 0;
+            let found = VerQueryValueW(
+                data.as_ptr() as _,
+                PCWSTR(root.as_ptr()),
+                &mut info,
+                &mut info_len,
+            );
+            if !found.as_bool() || info.is_null() {
+                return None;
+            }
+
+            let info = &*(info as *const VS_FIXEDFILEINFO);
+            Some(format!(
+                "{}.{}.{}.{}",
+                info.dwFileVersionMS >> 16,
+                info.dwFileVersionMS & 0xffff,
+                info.dwFileVersionLS >> 16,
+                info.dwFileVersionLS & 0xffff
+            ))
+        }
+    }
+
+    fn product_installed(&self, product_code: &str) -> bool {
+        let product_code = string_to_u16(product_code);
+        let state = unsafe { MsiQueryProductStateW(PCWSTR(product_code.as_ptr())) };
+        state == INSTALLSTATE_DEFAULT
+    }
+}
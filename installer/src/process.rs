@@ -1,4 +1,5 @@
 use anyhow::{anyhow, bail, Result};
+use log::info;
 use std::{fs, path::PathBuf, process::Command as Process};
 use sysinfo::Signal;
 use windows::Win32::UI::WindowsAndMessaging::AllowSetForegroundWindow;
@@ -25,7 +26,7 @@ pub fn find_and_kill_processes_from_directory(
             None
         })
         .collect();
-    println!(
+    info!(
         "Found {} processes in directory {}",
         processes.len(),
         directory
@@ -33,7 +34,7 @@ pub fn find_and_kill_processes_from_directory(
 
     for pid in processes {
         if let Some(process) = system.process(pid) {
-            println!("Terminating process {:?} with PID {}", process.name(), pid);
+            info!("Terminating process {:?} with PID {}", process.name(), pid);
             process.kill_with(Signal::Kill);
         }
     }
@@ -52,7 +53,7 @@ pub fn spawn_detached_process(exe_path: PathBuf) -> Result<()> {
 
     let mut exe_launch = Process::new(&exe_to_execute);
 
-    println!("About to launch: '{}'", exe_to_execute.to_string_lossy());
+    info!("About to launch: '{}'", exe_to_execute.to_string_lossy());
     let child = exe_launch
         .spawn()
         .map_err(|z| anyhow!("Failed to start application ({}).", z))?;
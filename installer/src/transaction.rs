@@ -0,0 +1,650 @@
+//! Install transactions, journaling every change so an interrupted install is undone or finished
+//!
+//! Before each change the journal records it as planned, and once it's made as completed. The
+//! journal is replaced atomically, by writing a temporary file and renaming it over the journal. If
+//! setup crashes or the machine loses power, the next run finds the journal and either rolls back
+//! every change in reverse, or if the transaction was committed removes the backup of the previous
+//! installation. Undoing a change is idempotent, so an interrupted rollback is simply run again.
+
+use crate::platform::{FileSystem, Registry, RegistryValue};
+
+use anyhow::{Context, Result};
+use bundler::prerequisite::RegistryRoot;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A change made by the install
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Operation {
+    /// Moved the previous installation aside, restored on rollback and removed on commit
+    MoveDirectory { from: PathBuf, to: PathBuf },
+    /// Created a directory and any missing parents
+    CreateDirectory { path: PathBuf },
+    /// Wrote a file, including shortcuts, restoring the previous contents on rollback if recorded
+    WriteFile {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        previous: Option<Vec<u8>>,
+    },
+    /// Set a registry value, replacing the previous value if any. When the key didn't exist, the
+    /// highest of its parents that didn't exist either is deleted with its subkeys on rollback
+    SetRegistryValue {
+        root: RegistryRoot,
+        key: String,
+        name: String,
+        previous: Option<RegistryValue>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        created_key: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub operation: Operation,
+    /// Whether the change was made, it may have been partly made when not
+    pub completed: bool,
+}
+
+/// The changes made by a transaction, in order
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Journal {
+    /// Whether every change was made, leaving only the backup of the previous installation to remove
+    pub committed: bool,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// How an interrupted transaction was recovered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recovery {
+    /// The transaction wasn't committed, its changes were undone
+    RolledBack,
+    /// The transaction was committed, the backup of the previous installation was removed
+    Finished,
+}
+
+/// Journal of the installation directory, next to it so it survives the directory being moved
+pub fn journal_path(root_path: &Path) -> PathBuf {
+    let mut file_name = root_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".install-journal");
+    root_path.with_file_name(file_name)
+}
+
+fn temp_journal_path(journal_path: &Path) -> PathBuf {
+    let mut temp_path = journal_path.as_os_str().to_os_string();
+    temp_path.push(".tmp");
+    PathBuf::from(temp_path)
+}
+
+/// Changes made to the system by an install, which are committed or rolled back together
+pub struct Transaction<'a> {
+    fs: &'a dyn FileSystem,
+    registry: &'a dyn Registry,
+    journal_path: PathBuf,
+    journal: Journal,
+}
+
+impl<'a> Transaction<'a> {
+    /// Starts a transaction, the journal must not exist
+    pub fn begin(
+        fs: &'a dyn FileSystem,
+        registry: &'a dyn Registry,
+        journal_path: &Path,
+    ) -> Result<Self> {
+        let transaction = Transaction {
+            fs,
+            registry,
+            journal_path: journal_path.to_path_buf(),
+            journal: Journal::default(),
+        };
+        transaction.save()?;
+        Ok(transaction)
+    }
+
+    /// Rolls back or finishes the transaction of an interrupted install, if there's a journal
+    pub fn recover(
+        fs: &'a dyn FileSystem,
+        registry: &'a dyn Registry,
+        journal_path: &Path,
+    ) -> Result<Option<Recovery>> {
+        // A crash while replacing the journal leaves the new journal, which was never used
+        let temp_path = temp_journal_path(journal_path);
+        if fs.exists(&temp_path) {
+            fs.remove_file(&temp_path)
+                .with_context(|| format!("Failed to remove {:?}", temp_path))?;
+        }
+
+        if !fs.exists(journal_path) {
+            return Ok(None);
+        }
+
+        let data = fs
+            .read(journal_path)
+            .with_context(|| format!("Failed to read install journal {:?}", journal_path))?;
+        let journal: Journal = serde_json::from_slice(&data)
+            .with_context(|| format!("Failed to parse install journal {:?}", journal_path))?;
+        info!(
+            "Found the journal of an interrupted install with {} changes, committed: {}",
+            journal.entries.len(),
+            journal.committed
+        );
+
+        let transaction = Transaction {
+            fs,
+            registry,
+            journal_path: journal_path.to_path_buf(),
+            journal,
+        };
+        if transaction.journal.committed {
+            transaction.finish()?;
+            Ok(Some(Recovery::Finished))
+        } else {
+            transaction.rollback()?;
+            Ok(Some(Recovery::RolledBack))
+        }
+    }
+
+    /// Moves a directory, restored on rollback and removed on commit
+    pub fn move_dir(&mut self, from: &Path, to: &Path) -> Result<()> {
+        self.apply(Operation::MoveDirectory {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        })
+    }
+
+    /// Creates a directory and any missing parents
+    pub fn create_dir(&mut self, path: &Path) -> Result<()> {
+        if self.fs.exists(path) {
+            return Ok(());
+        }
+        self.apply(Operation::CreateDirectory {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Writes a file, creating its directory if missing
+    pub fn write_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        self.plan(Operation::WriteFile {
+            path: path.to_path_buf(),
+            previous: None,
+        })?;
+        self.fs
+            .write(path, data)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        self.complete()
+    }
+
+    /// Writes a small file outside the installation directory, such as a shortcut, journaling any
+    /// existing file so it's restored on rollback
+    pub fn replace_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        let previous = match self.fs.exists(path) {
+            true => Some(
+                self.fs
+                    .read(path)
+                    .with_context(|| format!("Failed to read {:?}", path))?,
+            ),
+            false => None,
+        };
+        self.plan(Operation::WriteFile {
+            path: path.to_path_buf(),
+            previous,
+        })?;
+        self.fs
+            .write(path, data)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        self.complete()
+    }
+
+    /// Sets a registry value, restoring the previous value on rollback, or deleting the key if it
+    /// was created
+    pub fn set_registry_value(
+        &mut self,
+        root: RegistryRoot,
+        key: &str,
+        name: &str,
+        value: RegistryValue,
+    ) -> Result<()> {
+        self.plan(Operation::SetRegistryValue {
+            root,
+            key: key.to_string(),
+            name: name.to_string(),
+            previous: self.registry.value(root, key, name),
+            created_key: self.created_key(root, key),
+        })?;
+        self.registry.set_value(root, key, name, value)?;
+        self.complete()
+    }
+
+    /// The highest key that setting a value in the key creates, if it doesn't exist
+    fn created_key(&self, root: RegistryRoot, key: &str) -> Option<String> {
+        key.match_indices('\\')
+            .map(|(index, _)| &key[..index])
+            .chain([key])
+            .find(|key| !self.registry.key_exists(root, key))
+            .map(str::to_string)
+    }
+
+    /// The changes made so far, in order
+    pub fn operations(&self) -> impl Iterator<Item = &Operation> {
+        self.journal.entries.iter().map(|entry| &entry.operation)
+    }
+
+    /// Commits the changes, removing the backup of the previous installation
+    pub fn commit(mut self) -> Result<()> {
+        self.journal.committed = true;
+        self.save()?;
+        self.finish()
+    }
+
+    /// Undoes every change in reverse, including a change that was only partly made
+    pub fn rollback(self) -> Result<()> {
+        info!(
+            "Rolling back {} changes of the install...",
+            self.journal.entries.len()
+        );
+        for entry in self.journal.entries.iter().rev() {
+            self.undo(&entry.operation)
+                .with_context(|| format!("Failed to roll back {:?}", entry.operation))?;
+        }
+        self.remove_journal()
+    }
+
+    fn finish(self) -> Result<()> {
+        for entry in &self.journal.entries {
+            if let Operation::MoveDirectory { to, .. } = &entry.operation {
+                if self.fs.exists(to) {
+                    info!("Removing the previous installation {:?}...", to);
+                    self.fs
+                        .remove_dir_all(to)
+                        .with_context(|| format!("Failed to remove {:?}", to))?;
+                }
+            }
+        }
+        self.remove_journal()
+    }
+
+    /// Records the change as planned, makes it, and records it as completed
+    fn apply(&mut self, operation: Operation) -> Result<()> {
+        self.plan(operation.clone())?;
+        match &operation {
+            Operation::MoveDirectory { from, to } => self
+                .fs
+                .rename(from, to)
+                .with_context(|| format!("Failed to move {:?} to {:?}", from, to))?,
+            Operation::CreateDirectory { path } => self
+                .fs
+                .create_dir_all(path)
+                .with_context(|| format!("Failed to create directory {:?}", path))?,
+            Operation::WriteFile { .. } | Operation::SetRegistryValue { .. } => {
+                unreachable!("Applied with their data")
+            }
+        }
+        self.complete()
+    }
+
+    fn plan(&mut self, operation: Operation) -> Result<()> {
+        self.journal.entries.push(JournalEntry {
+            operation,
+            completed: false,
+        });
+        self.save()
+    }
+
+    fn complete(&mut self) -> Result<()> {
+        if let Some(entry) = self.journal.entries.last_mut() {
+            entry.completed = true;
+        }
+        self.save()
+    }
+
+    fn undo(&self, operation: &Operation) -> Result<()> {
+        let fs = self.fs;
+        match operation {
+            Operation::MoveDirectory { from, to } => {
+                if fs.exists(to) {
+                    if fs.exists(from) {
+                        fs.remove_dir_all(from)?;
+                    }
+                    fs.rename(to, from)?;
+                    info!("Restored the previous installation");
+                }
+            }
+            Operation::CreateDirectory { path } => {
+                if fs.exists(path) {
+                    fs.remove_dir_all(path)?;
+                }
+            }
+            Operation::WriteFile {
+                path,
+                previous: Some(previous),
+            } => fs.write(path, previous)?,
+            Operation::WriteFile {
+                path,
+                previous: None,
+            } => {
+                if fs.exists(path) {
+                    fs.remove_file(path)?;
+                }
+            }
+            Operation::SetRegistryValue {
+                root,
+                key,
+                name,
+                previous,
+                created_key,
+            } => match (created_key, previous) {
+                (Some(created_key), _) => self.registry.delete_key(*root, created_key)?,
+                (None, Some(previous)) => {
+                    self.registry
+                        .set_value(*root, key, name, previous.clone())?
+                }
+                (None, None) => self.registry.delete_value(*root, key, name)?,
+            },
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec_pretty(&self.journal)?;
+        let temp_path = temp_journal_path(&self.journal_path);
+
+        self.fs
+            .write(&temp_path, &data)
+            .and_then(|_| self.fs.rename(&temp_path, &self.journal_path))
+            .with_context(|| format!("Failed to write install journal {:?}", self.journal_path))
+    }
+
+    fn remove_journal(&self) -> Result<()> {
+        if self.fs.exists(&self.journal_path) {
+            self.fs.remove_file(&self.journal_path).with_context(|| {
+                format!("Failed to remove install journal {:?}", self.journal_path)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes backups of previous installations, `{root}_XXXXXXXX`, left behind by versions of setup
+/// before the install journal. Directories that don't hold the application executable aren't
+/// backups, and are left alone.
+pub fn remove_leftover_backups(fs: &dyn FileSystem, root_path: &Path, application: &str) {
+    let (Some(parent), Some(root_name)) = (root_path.parent(), root_path.file_name()) else {
+        return;
+    };
+    let Ok(entries) = fs.read_dir(parent) else {
+        return;
+    };
+
+    let prefix = format!("{}_", root_name.to_string_lossy());
+    for path in entries {
+        let is_backup = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .and_then(|name| name.strip_prefix(&prefix).map(str::to_string))
+            .is_some_and(|suffix| {
+                suffix.len() == 8 && suffix.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+        if !is_backup || !fs.exists(&path.join(application)) {
+            continue;
+        }
+
+        info!("Removing leftover backup {:?}...", path);
+        if let Err(e) = fs.remove_dir_all(&path) {
+            warn!("Failed to remove leftover backup {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::fake::{FakeFileSystem, FakeRegistry};
+
+    fn setup() -> (FakeFileSystem, FakeRegistry, PathBuf) {
+        let fs = FakeFileSystem::default();
+        let root_path = PathBuf::from("/appdata/com.example.app");
+        fs.create_dir_all(&root_path).unwrap();
+        fs.write(&root_path.join("app.exe"), b"old").unwrap();
+        (fs, FakeRegistry::default(), root_path)
+    }
+
+    /// Replaces the installation with a new version, setting a registry value
+    fn install(fs: &FakeFileSystem, registry: &FakeRegistry, root_path: &Path) -> Result<()> {
+        let backup_path = root_path.with_file_name("com.example.app_backup01");
+        let mut transaction = Transaction::begin(fs, registry, &journal_path(root_path))?;
+        transaction.move_dir(root_path, &backup_path)?;
+        transaction.create_dir(root_path)?;
+        transaction.write_file(&root_path.join("app.exe"), b"new")?;
+        transaction.write_file(&root_path.join("runtime/msedge.dll"), b"dll")?;
+        transaction.set_registry_value(
+            RegistryRoot::CurrentUser,
+            "Software\\Example",
+            "Version",
+            "2.0.0".into(),
+        )?;
+        transaction.commit()
+    }
+
+    fn registry_version(registry: &FakeRegistry) -> Option<RegistryValue> {
+        Registry::value(
+            registry,
+            RegistryRoot::CurrentUser,
+            "Software\\Example",
+            "Version",
+        )
+    }
+
+    #[test]
+    fn test_commit() {
+        let (fs, registry, root_path) = setup();
+        install(&fs, &registry, &root_path).unwrap();
+
+        assert_eq!(fs.read(&root_path.join("app.exe")).unwrap(), b"new");
+        assert_eq!(
+            fs.read(&root_path.join("runtime/msedge.dll")).unwrap(),
+            b"dll"
+        );
+        assert_eq!(registry_version(&registry), Some("2.0.0".into()));
+        assert_eq!(
+            fs.read_dir(Path::new("/appdata")).unwrap(),
+            vec![root_path.clone()]
+        );
+    }
+
+    #[test]
+    fn test_rollback() {
+        let (fs, registry, root_path) = setup();
+        registry
+            .set_value(
+                RegistryRoot::CurrentUser,
+                "Software\\Example",
+                "Version",
+                "1.0.0".into(),
+            )
+            .unwrap();
+
+        let mut transaction =
+            Transaction::begin(&fs, &registry, &journal_path(&root_path)).unwrap();
+        transaction
+            .move_dir(&root_path, &root_path.with_file_name("backup"))
+            .unwrap();
+        transaction.create_dir(&root_path).unwrap();
+        transaction
+            .write_file(&root_path.join("app.exe"), b"new")
+            .unwrap();
+        transaction
+            .set_registry_value(
+                RegistryRoot::CurrentUser,
+                "Software\\Example",
+                "Version",
+                "2.0.0".into(),
+            )
+            .unwrap();
+        transaction.rollback().unwrap();
+
+        assert_eq!(fs.read(&root_path.join("app.exe")).unwrap(), b"old");
+        assert_eq!(registry_version(&registry), Some("1.0.0".into()));
+        assert_eq!(
+            fs.read_dir(Path::new("/appdata")).unwrap(),
+            vec![root_path.clone()]
+        );
+    }
+
+    #[test]
+    fn test_rollback_replaced_file() {
+        let (fs, registry, _) = setup();
+        let shortcut = Path::new("/Start Menu/Example.lnk");
+        let new_shortcut = Path::new("/Start Menu/Folder/Example.lnk");
+        fs.create_dir_all(shortcut.parent().unwrap()).unwrap();
+        fs.write(shortcut, b"old link").unwrap();
+
+        let journal_path = Path::new("/appdata/example.install-journal");
+        let mut transaction = Transaction::begin(&fs, &registry, journal_path).unwrap();
+        transaction.replace_file(shortcut, b"new link").unwrap();
+        transaction.replace_file(new_shortcut, b"new link").unwrap();
+        assert_eq!(fs.read(shortcut).unwrap(), b"new link");
+        transaction.rollback().unwrap();
+
+        assert_eq!(fs.read(shortcut).unwrap(), b"old link");
+        assert!(!fs.exists(new_shortcut.parent().unwrap()));
+    }
+
+    #[test]
+    fn test_rollback_created_registry_key() {
+        let (fs, registry, _) = setup();
+        let root = RegistryRoot::CurrentUser;
+        registry
+            .set_value(root, "Software\\Other", "Version", "1.0.0".into())
+            .unwrap();
+
+        let journal_path = Path::new("/appdata/example.install-journal");
+        let mut transaction = Transaction::begin(&fs, &registry, journal_path).unwrap();
+        transaction
+            .set_registry_value(root, "Software\\Example\\Shell\\Open", "", "open".into())
+            .unwrap();
+        transaction
+            .set_registry_value(root, "Software\\Example", "Version", "2.0.0".into())
+            .unwrap();
+        assert_eq!(
+            transaction
+                .operations()
+                .map(|operation| match operation {
+                    Operation::SetRegistryValue { created_key, .. } => created_key.clone(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            vec![Some("Software\\Example".to_string()), None]
+        );
+        transaction.rollback().unwrap();
+
+        assert!(!registry.key_exists(root, "Software\\Example"));
+        assert!(registry.key_exists(root, "Software\\Other"));
+    }
+
+    /// Crashes after every number of changes, and checks the next run recovers
+    #[test]
+    fn test_recover_from_crash() {
+        let (fs, registry, root_path) = setup();
+        let start = fs.changes();
+        install(&fs, &registry, &root_path).unwrap();
+        let changes = fs.changes() - start;
+
+        for crash_after in 0..changes {
+            let (fs, registry, root_path) = setup();
+            let start = fs.changes();
+            fs.crash_after(crash_after);
+            assert!(install(&fs, &registry, &root_path).is_err());
+            assert_eq!(fs.changes() - start, crash_after);
+
+            // Setup runs again after a reboot
+            fs.restart();
+            let recovery = Transaction::recover(&fs, &registry, &journal_path(&root_path)).unwrap();
+
+            let app = fs.read(&root_path.join("app.exe")).unwrap();
+            match recovery {
+                Some(Recovery::Finished) => {
+                    assert_eq!(app, b"new", "crash after {} changes", crash_after);
+                    assert_eq!(registry_version(&registry), Some("2.0.0".into()));
+                }
+                Some(Recovery::RolledBack) | None => {
+                    assert_eq!(app, b"old", "crash after {} changes", crash_after);
+                    assert_eq!(registry_version(&registry), None);
+                    assert!(!fs.exists(&root_path.join("runtime")));
+                }
+            }
+            assert_eq!(
+                fs.read_dir(Path::new("/appdata")).unwrap(),
+                vec![root_path.clone()],
+                "crash after {} changes",
+                crash_after
+            );
+        }
+    }
+
+    /// Crashes while rolling back, and checks the next run finishes the rollback
+    #[test]
+    fn test_recover_from_crash_during_rollback() {
+        let (fs, registry, root_path) = setup();
+        let mut transaction =
+            Transaction::begin(&fs, &registry, &journal_path(&root_path)).unwrap();
+        transaction
+            .move_dir(&root_path, &root_path.with_file_name("backup"))
+            .unwrap();
+        transaction.create_dir(&root_path).unwrap();
+        transaction
+            .write_file(&root_path.join("app.exe"), b"new")
+            .unwrap();
+
+        fs.crash_after(2);
+        assert!(transaction.rollback().is_err());
+        fs.restart();
+
+        let recovery = Transaction::recover(&fs, &registry, &journal_path(&root_path)).unwrap();
+        assert_eq!(recovery, Some(Recovery::RolledBack));
+        assert_eq!(fs.read(&root_path.join("app.exe")).unwrap(), b"old");
+        assert_eq!(
+            fs.read_dir(Path::new("/appdata")).unwrap(),
+            vec![root_path.clone()]
+        );
+    }
+
+    #[test]
+    fn test_remove_leftover_backups() {
+        let (fs, _, root_path) = setup();
+        for name in [
+            "com.example.app_aB3dE6gH",
+            "com.example.app_Settings",
+            "com.example.app_short",
+            "com.example.app_aB3dE6g!",
+            "com.example.other_aB3dE6gH",
+        ] {
+            let path = root_path.with_file_name(name);
+            fs.create_dir_all(&path).unwrap();
+            if name != "com.example.app_Settings" {
+                fs.write(&path.join("app.exe"), b"old").unwrap();
+            }
+        }
+
+        // Only the backup holding the application is removed, not the look-alike without it
+        remove_leftover_backups(&fs, &root_path, "app.exe");
+        assert_eq!(
+            fs.read_dir(Path::new("/appdata")).unwrap(),
+            vec![
+                root_path.clone(),
+                root_path.with_file_name("com.example.app_Settings"),
+                root_path.with_file_name("com.example.app_aB3dE6g!"),
+                root_path.with_file_name("com.example.app_short"),
+                root_path.with_file_name("com.example.other_aB3dE6gH"),
+            ]
+        );
+    }
+}
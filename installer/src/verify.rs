@@ -0,0 +1,132 @@
+//! Checks the installed files against the digests of the package or the install receipt
+
+use crate::platform::FileSystem;
+
+use bundler::receipt::ReceiptFile;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The expected files that don't match the installation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    /// Files that aren't installed
+    pub missing: Vec<String>,
+    /// Files whose size or SHA-256 differ
+    pub modified: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Whether every expected file is installed unmodified
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty()
+    }
+
+    /// The missing and modified files
+    pub fn damaged(&self) -> impl Iterator<Item = &String> {
+        self.missing.iter().chain(&self.modified)
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_intact() {
+            return write!(f, "All files are intact");
+        }
+        for (label, paths) in [("Missing", &self.missing), ("Modified", &self.modified)] {
+            if !paths.is_empty() {
+                writeln!(f, "{}: {}", label, paths.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares the expected files with the installation directory, any other files are ignored
+pub fn verify_files(
+    fs: &dyn FileSystem,
+    root_path: &Path,
+    expected: &[ReceiptFile],
+) -> VerifyReport {
+    let mut report = VerifyReport::default();
+    for file in expected {
+        let path = installed_path(root_path, &file.path);
+        if !fs.exists(&path) {
+            report.missing.push(file.path.clone());
+            continue;
+        }
+
+        let intact = fs
+            .read(&path)
+            .is_ok_and(|data| ReceiptFile::new(&file.path, &data) == *file);
+        if !intact {
+            report.modified.push(file.path.clone());
+        }
+    }
+    report
+}
+
+/// Path of an installed file, from its `/` separated path relative to the installation directory
+pub fn installed_path(root_path: &Path, relative_path: &str) -> PathBuf {
+    relative_path
+        .split('/')
+        .fold(root_path.to_path_buf(), |path, part| path.join(part))
+}
+
+/// `/` separated path of a file relative to the installation directory, if it's in it
+pub fn relative_path(root_path: &Path, path: &Path) -> Option<String> {
+    let relative_path = path.strip_prefix(root_path).ok()?;
+    Some(
+        relative_path
+            .iter()
+            .map(|part| part.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::fake::FakeFileSystem;
+
+    #[test]
+    fn test_verify_files() {
+        let fs = FakeFileSystem::default();
+        let root_path = Path::new("/app");
+        fs.create_dir_all(&root_path.join("WebView2Runtime"))
+            .unwrap();
+        fs.write(&root_path.join("app.exe"), b"app").unwrap();
+        fs.write(&root_path.join("WebView2Runtime/runtime.dll"), b"changed")
+            .unwrap();
+        fs.write(&root_path.join("settings.json"), b"user data")
+            .unwrap();
+
+        let expected = [
+            ReceiptFile::new("app.exe", b"app"),
+            ReceiptFile::new("WebView2Runtime/runtime.dll", b"runtime"),
+            ReceiptFile::new("WebView2Runtime/missing.dll", b"runtime"),
+        ];
+        let report = verify_files(&fs, root_path, &expected);
+        assert_eq!(
+            report,
+            VerifyReport {
+                missing: vec!["WebView2Runtime/missing.dll".to_string()],
+                modified: vec!["WebView2Runtime/runtime.dll".to_string()],
+            }
+        );
+        assert!(!report.is_intact());
+        assert!(verify_files(&fs, root_path, &expected[..1]).is_intact());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let root_path = Path::new("/app");
+        let path = installed_path(root_path, "WebView2Runtime/runtime.dll");
+        assert_eq!(path, Path::new("/app/WebView2Runtime/runtime.dll"));
+        assert_eq!(
+            relative_path(root_path, &path).as_deref(),
+            Some("WebView2Runtime/runtime.dll")
+        );
+        assert_eq!(relative_path(root_path, Path::new("/other/app.lnk")), None);
+    }
+}
@@ -1,12 +1,23 @@
 mod to_wide;
 
 use anyhow::Result;
+use bundler::elevation;
+use bundler::file_association::{AssociationPlan, AssociationValue};
+pub use bundler::portable::PORTABLE_MARKER;
+use bundler::prerequisite::RegistryRoot;
+use bundler::receipt::InstallReceipt;
+use bundler::shortcut::APP_USER_MODEL_ID_KEY;
+pub use bundler::webview2::WEBVIEW2_FIXED_RUNTIME_DIR;
+use log::{error, info, LevelFilter};
+use simplelog::{ConfigBuilder, WriteLogger};
 use std::env;
+use std::fs::File;
 use std::os::windows::process::CommandExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command as Process;
 use windows::core::PCWSTR;
 use windows::Win32::Foundation::HWND;
+use windows::Win32::UI::Shell::{SHChangeNotify, SHCNE_ASSOCCHANGED, SHCNF_IDLIST};
 use windows::Win32::UI::WindowsAndMessaging::{
     AllowSetForegroundWindow, MessageBoxW, MB_ICONINFORMATION, MB_ICONWARNING,
 };
@@ -17,15 +28,98 @@ use crate::to_wide::ToWide;
 
 const UNINSTALL_STR: &'static str = "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
 
+/// Environment variable WebView2 loads the runtime from, instead of the installed runtime
+const WEBVIEW2_BROWSER_EXECUTABLE_FOLDER: &str = "WEBVIEW2_BROWSER_EXECUTABLE_FOLDER";
+
 pub fn handle_uninstall(app_title: &str, app_id: &str) {
     let args: Vec<String> = std::env::args().collect();
     if args.contains(&"--uninstall".to_string()) {
-        println!("Uninstall flag detected. Running uninstall code...");
-        uninstall(app_title, app_id);
+        let silent = args.contains(&"--silent".to_string());
+        let log_path = args
+            .iter()
+            .position(|arg| arg == "--log")
+            .and_then(|i| args.get(i + 1))
+            .map(PathBuf::from)
+            .unwrap_or_else(|| env::temp_dir().join(format!("{}-uninstall.log", app_id)));
+        init_logging(&log_path);
+        info!("Uninstall flag detected. Running uninstall code...");
+
+        // Portable builds were never installed, so there is nothing to uninstall
+        if is_portable() {
+            error!("Running in portable mode, refusing to uninstall.");
+            if !silent {
+                show_info(
+                    format!("{} Uninstall", app_title).as_str(),
+                    None,
+                    "This is a portable copy of the application and is not installed. To remove it, delete its folder.",
+                );
+            }
+            std::process::exit(1);
+        }
+
+        // Per-machine installations can only be removed by an administrator
+        let per_machine = args.contains(&"--per-machine".to_string());
+        if per_machine && !elevation::is_elevated() {
+            info!("Relaunching elevated to remove the per-machine installation...");
+            let exit_code = match elevation::relaunch_elevated(&[], Some(&log_path)) {
+                Ok(Some(exit_code)) => exit_code,
+                Ok(None) => {
+                    info!("Elevation was declined");
+                    1
+                }
+                Err(e) => {
+                    error!("{:#}", e);
+                    1
+                }
+            };
+            info!("Elevated uninstaller exited with code {}", exit_code);
+            std::process::exit(exit_code);
+        }
+
+        uninstall(app_title, app_id, silent, per_machine, &log_path);
+    }
+}
+
+/// Logs the uninstall to a timestamped file, as the application may not have a console
+fn init_logging(log_path: &Path) {
+    let config = ConfigBuilder::new()
+        .set_time_format_rfc3339()
+        .set_target_level(LevelFilter::Off)
+        .set_thread_level(LevelFilter::Off)
+        .build();
+
+    // The application may have set up its own logger already, which is used instead
+    if let Ok(log_file) = File::create(log_path) {
+        let _ = WriteLogger::init(LevelFilter::Debug, config, log_file);
+    }
+}
+
+/// Returns true if the application is running from a portable build
+pub fn is_portable() -> bool {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(PORTABLE_MARKER).exists()))
+        .unwrap_or(false)
+}
+
+/// Points WebView2 at the fixed version runtime installed next to the application, if there is one.
+///
+/// Must be called before any webviews are created, returns the runtime directory when it's used.
+pub fn use_webview2_fixed_runtime() -> Option<PathBuf> {
+    let runtime_path = std::env::current_exe()
+        .ok()?
+        .parent()?
+        .join(WEBVIEW2_FIXED_RUNTIME_DIR);
+    if !runtime_path.is_dir() {
+        return None;
     }
+
+    info!("Using WebView2 fixed runtime: {:?}", runtime_path);
+    env::set_var(WEBVIEW2_BROWSER_EXECUTABLE_FOLDER, &runtime_path);
+    Some(runtime_path)
 }
 
-fn uninstall(app_title: &str, app_id: &str) {
+fn uninstall(app_title: &str, app_id: &str, silent: bool, per_machine: bool, log_path: &Path) {
     // Force stop any other instances of the application
     let _ = std::process::Command::new("taskkill")
         .args(&["/IM", &format!("{}.exe", app_id), "/F"])
@@ -40,28 +134,129 @@ fn uninstall(app_title: &str, app_id: &str) {
         .parent()
         .expect("Failed to get parent directory")
         .to_path_buf();
+
+    // Read the install receipt before the installation directory is removed
+    let receipt = match InstallReceipt::load(&root_path) {
+        Ok(receipt) => Some(receipt),
+        Err(e) => {
+            info!(
+                "No install receipt, only removing the uninstall entry: {:#}",
+                e
+            );
+            None
+        }
+    };
+
+    info!("Removing installation directory {:?}", root_path);
     if let Err(e) = remove_dir_all_ext::remove_dir_but_not_self(&root_path) {
-        eprintln!("Failed to remove installation directory: {}", e);
+        error!("Failed to remove installation directory: {}", e);
         errors = true;
     }
 
     // Remove the uninstall registry key
-    if let Err(e) = remove_uninstall_entry(&app_id) {
-        eprintln!("Failed to remove uninstall registry key: {}", e);
+    info!(
+        "Removing uninstall registry key {}\\{}",
+        UNINSTALL_STR, app_id
+    );
+    if let Err(e) = remove_uninstall_entry(&app_id, per_machine) {
+        error!("Failed to remove uninstall registry key: {}", e);
         errors = true;
     }
 
-    // Show the result
+    // Remove the AppUserModelID registration, also when there's no receipt listing it
+    let app_user_model_id_key = format!("{}\\{}", APP_USER_MODEL_ID_KEY, app_id);
+    let root = match per_machine {
+        true => RegistryRoot::LocalMachine,
+        false => RegistryRoot::CurrentUser,
+    };
+    info!(
+        "Removing AppUserModelID {:?}\\{}",
+        root, app_user_model_id_key
+    );
+    if let Err(e) = remove_registry_key(root, &app_user_model_id_key) {
+        error!("Failed to remove AppUserModelID: {}", e);
+        errors = true;
+    }
+
+    // Remove the shortcuts and other registry keys the installer wrote
+    if let Some(receipt) = &receipt {
+        for shortcut in &receipt.shortcuts {
+            info!("Removing shortcut {:?}", shortcut);
+            if let Err(e) = remove_file_if_exists(shortcut) {
+                error!("Failed to remove shortcut: {}", e);
+                errors = true;
+            }
+        }
+
+        // The Start menu folders are left if anything else was put in them
+        for directory in receipt.directories.iter().rev() {
+            if std::fs::remove_dir(directory).is_ok() {
+                info!("Removed shortcut folder {:?}", directory);
+            }
+        }
+
+        // The extension keys are shared with other applications, so only the OpenWithProgids
+        // values still set by the installer are removed from them
+        let manifest = &receipt.manifest;
+        let associations =
+            AssociationPlan::new(&manifest.file_associations, &manifest.identifier, "");
+        let root = receipt.scope.registry_root();
+        for value in associations.values.iter().filter(|value| value.shared) {
+            info!(
+                "Removing file association {:?}\\{} {}",
+                root, value.key, value.name
+            );
+            if let Err(e) = remove_registry_value_if_unchanged(root, value) {
+                error!("Failed to remove file association: {}", e);
+                errors = true;
+            }
+        }
+
+        let uninstall_key = format!("{}\\{}", UNINSTALL_STR, app_id);
+        for (root, key) in receipt.registry_keys() {
+            if key == uninstall_key || associations.is_shared_key(key) {
+                continue;
+            }
+            info!("Removing registry key {:?}\\{}", root, key);
+            if let Err(e) = remove_registry_key(root, key) {
+                error!("Failed to remove registry key: {}", e);
+                errors = true;
+            }
+        }
+
+        if !associations.values.is_empty() {
+            // The OpenWithProgids keys are removed before their extension keys
+            let shared_keys = associations
+                .values
+                .iter()
+                .filter(|value| value.shared)
+                .map(|value| &value.key)
+                .chain(&associations.extension_keys);
+            for key in shared_keys {
+                if let Err(e) = remove_registry_key_if_empty(root, key) {
+                    error!("Failed to remove registry key: {}", e);
+                    errors = true;
+                }
+            }
+            unsafe { SHChangeNotify(SHCNE_ASSOCCHANGED, SHCNF_IDLIST, None, None) };
+        }
+    }
+
+    // Show the result, unless running silently (e.g. from a package manager)
     if !errors {
-        println!("Uninstall completed successfully!");
-        show_info(
-            format!("{} Uninstall", app_title).as_str(),
-            None,
-            "The application was successfully uninstalled.",
-        );
+        info!("Uninstall completed successfully!");
+        if !silent {
+            show_info(
+                format!("{} Uninstall", app_title).as_str(),
+                None,
+                "The application was successfully uninstalled.",
+            );
+        }
     } else {
-        eprintln!("Uninstall completed with errors.");
-        show_uninstall_complete_with_errors()
+        error!("Uninstall completed with errors.");
+        if !silent {
+            show_uninstall_complete_with_errors(log_path)
+        }
     }
 
     // Delete the executable
@@ -71,13 +266,63 @@ fn uninstall(app_title: &str, app_id: &str) {
     std::process::exit(errors as i32);
 }
 
-fn remove_uninstall_entry(app_id: &str) -> Result<()> {
-    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let uninstall_key = hkcu.create_subkey(UNINSTALL_STR)?.0;
+fn remove_uninstall_entry(app_id: &str, per_machine: bool) -> Result<()> {
+    let root = RegKey::predef(if per_machine {
+        HKEY_LOCAL_MACHINE
+    } else {
+        HKEY_CURRENT_USER
+    });
+    let uninstall_key = root.create_subkey(UNINSTALL_STR)?.0;
     uninstall_key.delete_subkey(&app_id)?;
     Ok(())
 }
 
+fn predef_key(root: RegistryRoot) -> RegKey {
+    RegKey::predef(match root {
+        RegistryRoot::LocalMachine => HKEY_LOCAL_MACHINE,
+        RegistryRoot::CurrentUser => HKEY_CURRENT_USER,
+        RegistryRoot::ClassesRoot => HKEY_CLASSES_ROOT,
+    })
+}
+
+/// Removes a key and its subkeys, succeeding if it doesn't exist
+fn remove_registry_key(root: RegistryRoot, key: &str) -> Result<()> {
+    match predef_key(root).delete_subkey_all(key) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Removes a key if it has no values or subkeys left, succeeding if it doesn't exist
+fn remove_registry_key_if_empty(root: RegistryRoot, key: &str) -> Result<()> {
+    let Ok(subkey) = predef_key(root).open_subkey(key) else {
+        return Ok(());
+    };
+    let info = subkey.query_info()?;
+    if info.sub_keys == 0 && info.values == 0 {
+        predef_key(root).delete_subkey(key)?;
+    }
+    Ok(())
+}
+
+/// Removes a value written by the installer, unless another application has since replaced it
+fn remove_registry_value_if_unchanged(root: RegistryRoot, value: &AssociationValue) -> Result<()> {
+    let Ok(key) = predef_key(root).open_subkey_with_flags(&value.key, KEY_READ | KEY_WRITE) else {
+        return Ok(());
+    };
+    match key.get_value::<String, _>(&value.name) {
+        Ok(current) if current == value.value => Ok(key.delete_value(&value.name)?),
+        _ => Ok(()),
+    }
+}
+
+fn remove_file_if_exists(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
 fn show_info(title: &str, parent: Option<HWND>, text: &str) {
     let lp_title = title.to_wide_null();
     let lp_text = text.to_wide_null();
@@ -92,9 +337,9 @@ fn show_info(title: &str, parent: Option<HWND>, text: &str) {
     }
 }
 
-fn show_uninstall_complete_with_errors() {
+fn show_uninstall_complete_with_errors(log_path: &Path) {
     let lp_title = "Uninstall Complete".to_wide_null();
-    let lp_text = "The application was uninstalled, but there were errors during the process. Please check the logs for more information.".to_wide_null();
+    let lp_text = format!("The application was uninstalled, but there were errors during the process. Please check the log file for more information:\n{}", log_path.display()).to_wide_null();
 
     unsafe {
         MessageBoxW(
@@ -107,7 +352,7 @@ fn show_uninstall_complete_with_errors() {
 }
 
 fn register_intent_to_delete_self(delay_seconds: usize, current_directory: &Path) -> Result<()> {
-    println!("Deleting self...");
+    info!("Deleting self...");
     let current_exe = env::current_exe()?.to_string_lossy().to_string();
     let command = format!(
         "choice /C Y /N /D Y /T {} & Del \"{}\" & Rmdir \"{}\"",
@@ -115,7 +360,7 @@ fn register_intent_to_delete_self(delay_seconds: usize, current_directory: &Path
         current_exe,
         current_directory.file_name().unwrap().to_string_lossy()
     );
-    println!("Running: cmd.exe /C {}", command);
+    info!("Running: cmd.exe /C {}", command);
 
     const CREATE_NO_WINDOW: u32 = 0x08000000;
     let child = Process::new("cmd.exe")